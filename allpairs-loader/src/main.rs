@@ -1,32 +1,877 @@
-use std::fs;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use anyhow::Result;
-use clap::Parser;
+use allpairs::{LoadAllpairsError, MappedFile, MaybeGzReader};
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use ppm_table::io::Checkpoint;
+use ppm_table::ppm::Ppm;
+use ppm_table::{DuplicatePolicy, MergePolicy, PpmTable, PpmTableBuilder};
+use regex::Regex;
 
-/// Parses an allpairs file into a PPM table and save the table to disk.
+/// Converts allpairs files into PPM tables, computes PPM tables from handins, and summarizes PPM
+/// tables.
 #[derive(Parser, Debug)]
-#[command(version)]
-struct Args {
-    /// Path to the allpairs file.
+#[command(version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// The arguments of the convert subcommand, which runs if no subcommand is given.
+    #[command(flatten)]
+    convert: Option<ConvertArgs>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    Convert(ConvertArgs),
+    Compare(CompareArgs),
+    Inspect(InspectArgs),
+}
+
+/// Parses an allpairs file into a PPM table and saves the table to disk.
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    /// Path to the allpairs file, which may be gzipped, or - to read from stdin.
     in_file: PathBuf,
-    /// Path for the outputted PPM table file.
+    /// Path for the outputted PPM table file, or - for stdout.
     out_file: PathBuf,
+    /// Format of the output. If omitted, it is inferred from the extension of the output file:
+    /// JSON for .json, CSV for .csv, and postcard otherwise.
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+    /// Additional allpairs files to combine with the first, e.g. chunks of one large run.
+    #[arg(long = "merge")]
+    merge_files: Vec<PathBuf>,
+    /// How to handle a pair that appears in several files with different PPMs.
+    #[arg(long, value_enum, default_value_t = OnConflict::Error)]
+    on_conflict: OnConflict,
+    /// How to handle a pair that appears more than once within a single file.
+    #[arg(long, value_enum, default_value_t = OnDuplicate::Overwrite)]
+    on_duplicate: OnDuplicate,
+    /// PPM to use for pairs missing from the input, e.g. 1000000 for files that leave out
+    /// dissimilar pairs. Without it, missing pairs are an error.
+    #[arg(long, value_name = "PPM")]
+    default_ppm: Option<u32>,
+    /// Compress the PPM table with zstd.
+    #[arg(long)]
+    compress: bool,
+    /// Skip malformed lines in the allpairs files instead of failing.
+    #[arg(long)]
+    skip_malformed: bool,
+    /// Store each PPM in two bytes if they all fit, or four otherwise, instead of compressing.
+    #[arg(long, conflicts_with = "compress")]
+    compact: bool,
+    /// Save progress on the first allpairs file every N lines to OUT_FILE.checkpoint, which is
+    /// removed once the table is saved.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    checkpoint_every: Option<u64>,
+    /// Resume loading the first allpairs file from a checkpoint saved by --checkpoint-every.
+    #[arg(long, value_name = "CHECKPOINT")]
+    resume: Option<PathBuf>,
+    /// Key the table by the first capture group of this regex in each path, instead of by the
+    /// whole path.
+    #[arg(long, value_name = "REGEX", conflicts_with = "handin_name")]
+    id_regex: Option<String>,
+    /// Key the table by the directories between the first directory of each path and this file
+    /// name, as cabal does.
+    #[arg(long = "handin-name", value_name = "FILE")]
+    handin_name: Option<String>,
+    /// Keep keys as written instead of putting them into Unicode Normalization Form C, which makes
+    /// names written with precomposed characters on some lines and combining characters on
+    /// others one key.
+    #[arg(long)]
+    no_normalize_unicode: bool,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+/// Compares every pair of handins in a directory, as lexscan would, and saves the PPM table to
+/// disk without needing an allpairs file.
+#[derive(Args, Debug)]
+struct CompareArgs {
+    /// Directory to search for handins, e.g. a2 for handins at a2/001/a2.py. The table is keyed by
+    /// the paths from its parent directory.
+    dir: PathBuf,
+    /// File name of the handins to compare.
+    #[arg(long = "handin-name", value_name = "FILE")]
+    handin_name: String,
+    /// Path for the outputted PPM table file, or - for stdout.
+    #[arg(long = "out", value_name = "OUT_FILE")]
+    out_file: PathBuf,
+    /// Format of the output, as for the convert subcommand.
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+    /// Compress the PPM table with zstd.
+    #[arg(long)]
+    compress: bool,
+    /// Store each PPM in two bytes if they all fit, or four otherwise, instead of compressing.
+    #[arg(long, conflicts_with = "compress")]
+    compact: bool,
+}
+
+/// Prints the number of keys and edges, a summary of the PPMs, and the closest pairs of a PPM
+/// table.
+#[derive(Args, Debug)]
+struct InspectArgs {
+    /// Path to a PPM table file or an allpairs file, which may be gzipped, or - to read from stdin.
+    file: PathBuf,
+    /// What kind of file to read. If omitted, PPM table files are recognized by their magic
+    /// bytes and anything else is read as an allpairs file.
+    #[arg(long, value_enum)]
+    input_format: Option<InputFormat>,
+    /// The number of closest pairs to print.
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    top: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum InputFormat {
+    /// A framed PPM table file, as written by the convert subcommand.
+    Table,
+    /// An allpairs file, which may be gzipped.
+    Allpairs,
+}
+
+impl InputFormat {
+    fn detect(bytes: &[u8]) -> Self {
+        let magics = [
+            ppm_table::io::MAGIC,
+            ppm_table::io::COMPRESSED_MAGIC,
+            ppm_table::io::COMPACT_MAGIC,
+        ];
+        if magics.iter().any(|magic| bytes.starts_with(*magic)) {
+            InputFormat::Table
+        } else {
+            InputFormat::Allpairs
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Format {
+    /// A framed PPM table file, as read by cabal's --ppm-table.
+    Postcard,
+    /// A JSON list of `{"l", "r", "ppm"}` edges.
+    Json,
+    /// `left,right,similarity_ppm` records.
+    Csv,
+}
+
+impl Format {
+    fn infer(out_file: &Path) -> Self {
+        match out_file
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("json") => Format::Json,
+            Some("csv") => Format::Csv,
+            _ => Format::Postcard,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OnConflict {
+    KeepMin,
+    KeepLast,
+    Error,
+}
+
+impl From<OnConflict> for MergePolicy {
+    fn from(value: OnConflict) -> Self {
+        match value {
+            OnConflict::KeepMin => MergePolicy::KeepMin,
+            OnConflict::KeepLast => MergePolicy::KeepLast,
+            OnConflict::Error => MergePolicy::Error,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OnDuplicate {
+    Overwrite,
+    KeepFirst,
+    KeepMin,
+    KeepMax,
+    Error,
+}
 
-    let contents = fs::read_to_string(args.in_file)?;
+impl From<OnDuplicate> for DuplicatePolicy {
+    fn from(value: OnDuplicate) -> Self {
+        match value {
+            OnDuplicate::Overwrite => DuplicatePolicy::Overwrite,
+            OnDuplicate::KeepFirst => DuplicatePolicy::KeepFirst,
+            OnDuplicate::KeepMin => DuplicatePolicy::KeepMin,
+            OnDuplicate::KeepMax => DuplicatePolicy::KeepMax,
+            OnDuplicate::Error => DuplicatePolicy::Error,
+        }
+    }
+}
+
+/// Where and how often to save progress while loading.
+#[derive(Clone, Copy, Debug)]
+struct Checkpointing<'a> {
+    every: u64,
+    path: &'a Path,
+}
+
+/// How the keys of the table are made from the paths in the allpairs files.
+#[derive(Clone, Copy, Debug, Default)]
+struct KeyOptions<'a> {
+    id_regex: Option<&'a Regex>,
+    normalize_unicode: bool,
+}
+
+impl KeyOptions<'_> {
+    fn key(&self, path: String, line_number: u64) -> Result<String> {
+        let key = match self.id_regex {
+            Some(id_regex) => extract_id(id_regex, path, line_number)?,
+            None => path,
+        };
+        Ok(match self.normalize_unicode {
+            true => allpairs::normalize_unicode(&key).into_owned(),
+            false => key,
+        })
+    }
+}
+
+/// Opens `path` for reading, or stdin if `path` is -.
+fn open(path: &Path) -> Result<Box<dyn BufRead>> {
+    if path.as_os_str() != "-" {
+        return Ok(Box::new(BufReader::new(File::open(path)?)));
+    }
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        bail!("- reads the input from stdin, but stdin is a terminal; pipe the input in instead");
+    }
+    Ok(Box::new(stdin.lock()))
+}
 
-    let ppm_table = allpairs::load(contents)?;
+/// Builds the regex that IDs are extracted from paths with, if either option is given.
+fn id_regex(id_regex: Option<&str>, handin_name: Option<&str>) -> Result<Option<Regex>> {
+    let pattern = match (id_regex, handin_name) {
+        (Some(pattern), _) => pattern.to_string(),
+        (None, Some(handin_name)) => format!(r"^[^/]+/(.+)/{}", regex::escape(handin_name)),
+        (None, None) => return Ok(None),
+    };
+    let regex = Regex::new(&pattern).with_context(|| format!("invalid ID regex {}", pattern))?;
+    if regex.captures_len() < 2 {
+        bail!(
+            "the ID regex {} has no capture group to extract IDs with",
+            pattern
+        );
+    }
+    Ok(Some(regex))
+}
 
-    let out = postcard::to_stdvec(&ppm_table)?;
+fn extract_id(id_regex: &Regex, path: String, line_number: u64) -> Result<String> {
+    match id_regex
+        .captures(&path)
+        .and_then(|captures| captures.get(1))
+    {
+        Some(id) => Ok(id.as_str().to_string()),
+        None => bail!(
+            "the path {} on line {} does not match the ID regex {}",
+            path,
+            line_number,
+            id_regex
+        ),
+    }
+}
 
-    let mut file = BufWriter::new(File::create(args.out_file.clone())?);
-    file.write_all(&out)?;
+fn load_builder(
+    file: &Path,
+    on_duplicate: OnDuplicate,
+    skip_malformed: bool,
+    key_options: KeyOptions,
+    checkpointing: Option<Checkpointing>,
+    resume: Option<&Path>,
+) -> Result<PpmTableBuilder> {
+    // Only regular files can be mapped into memory, so stdin and pipes are read as they stream
+    // in.
+    let mapped = match file.as_os_str() != "-" && fs::metadata(file)?.is_file() {
+        true => Some(MappedFile::open(file)?),
+        false => None,
+    };
+    let reader: Box<dyn BufRead> = match &mapped {
+        Some(mapped) => Box::new(&mapped[..]),
+        None => open(file)?,
+    };
+    let mut reader = MaybeGzReader::new(reader)?;
+    let checkpoint = match resume {
+        Some(resume) => {
+            let checkpoint = ppm_table::io::read_checkpoint(BufReader::new(File::open(resume)?))?;
+            // A gzipped file cannot be seeked, so skip ahead by reading.
+            let skipped = io::copy(
+                &mut reader.by_ref().take(checkpoint.offset),
+                &mut io::sink(),
+            )?;
+            if skipped < checkpoint.offset {
+                bail!(
+                    "{} is shorter than the checkpoint {}",
+                    file.display(),
+                    resume.display()
+                );
+            }
+            checkpoint
+        }
+        None => Checkpoint {
+            builder: PpmTableBuilder::new(),
+            offset: 0,
+            lines: 0,
+            first_line: None,
+            skipped: None,
+        },
+    };
+    let checkpoint = Checkpoint {
+        builder: checkpoint
+            .builder
+            .with_duplicate_policy(on_duplicate.into()),
+        ..checkpoint
+    };
+    let (builder, skipped) = add_lines(
+        reader,
+        checkpoint,
+        skip_malformed,
+        key_options,
+        checkpointing,
+    )?;
+    if let Some((skipped, first)) = skipped {
+        eprintln!(
+            "{}: skipped {} malformed lines (first at line {})",
+            file.display(),
+            skipped,
+            first
+        );
+    }
+    Ok(builder)
+}
 
+/// Adds the lines of `reader`, which continues from `checkpoint`, to the checkpoint's builder.
+/// Returns the builder along with the number of malformed lines that were skipped, including those
+/// before the checkpoint, and the number of the first of them.
+fn add_lines(
+    reader: impl BufRead,
+    checkpoint: Checkpoint,
+    skip_malformed: bool,
+    key_options: KeyOptions,
+    checkpointing: Option<Checkpointing>,
+) -> Result<(PpmTableBuilder, Option<(u64, u64)>)> {
+    let Checkpoint {
+        mut builder,
+        offset,
+        mut lines,
+        first_line,
+        mut skipped,
+    } = checkpoint;
+    let read = Rc::new(Cell::new(0));
+    let reader = CountingReader {
+        inner: reader,
+        read: Rc::clone(&read),
+    };
+    let first_line = Cell::new(first_line.map(|(columns, line)| (columns as usize, line as usize)));
+    for edge in allpairs::parse_reader_from_line(reader, lines as usize + 1, &first_line) {
+        lines += 1;
+        match edge {
+            Err(err) if skip_malformed && err.line_number().is_some() => {
+                skipped = Some(skipped.map_or((1, lines), |(count, first)| (count + 1, first)));
+            }
+            edge => {
+                let (l, r, ppm) = edge?;
+                let (l, r) = (key_options.key(l, lines)?, key_options.key(r, lines)?);
+                builder
+                    .try_add_ppm(l, r, ppm)
+                    .map_err(|err| LoadAllpairsError::conflicting_duplicate(err, lines as usize))?;
+            }
+        }
+        if let Some(checkpointing) = checkpointing.filter(|c| lines % c.every == 0) {
+            let checkpoint = Checkpoint {
+                builder,
+                // Lines are read one at a time, so this is exactly where the next line starts.
+                offset: offset + read.get(),
+                lines,
+                first_line: first_line
+                    .get()
+                    .map(|(columns, line)| (columns as u64, line as u64)),
+                skipped,
+            };
+            save_checkpoint(&checkpoint, checkpointing.path)?;
+            builder = checkpoint.builder;
+        }
+    }
+    Ok((builder, skipped))
+}
+
+/// Writes `checkpoint` to a temporary file and moves it into place, so that an interrupted write
+/// never leaves a corrupt checkpoint behind.
+fn save_checkpoint(checkpoint: &Checkpoint, path: &Path) -> Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    ppm_table::io::write_checkpoint(BufWriter::new(File::create(&temporary)?), checkpoint)?;
+    fs::rename(temporary, path)?;
     Ok(())
 }
+
+/// Counts the bytes consumed from a reader, which can be read after the reader has been moved.
+struct CountingReader<R> {
+    inner: R,
+    read: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.set(self.read.get() + n as u64);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.read.set(self.read.get() + amt as u64);
+    }
+}
+
+/// Writes the table in `format`. `compress` and `compact` choose how postcard tables are stored.
+fn write_table(
+    mut writer: impl Write,
+    ppm_table: &PpmTable,
+    format: Format,
+    compress: bool,
+    compact: bool,
+) -> Result<()> {
+    match format {
+        Format::Postcard if compress => {
+            ppm_table::io::write_table_compressed(writer, ppm_table, 0)?
+        }
+        Format::Postcard if compact => ppm_table::io::write_table_compact(writer, ppm_table)?,
+        Format::Postcard => ppm_table::io::write_table(writer, ppm_table)?,
+        Format::Json => {
+            serde_json::to_writer(&mut writer, ppm_table)?;
+            writer.flush()?;
+        }
+        Format::Csv => allpairs::write_csv(ppm_table, writer)?,
+    }
+    Ok(())
+}
+
+/// Chooses the format to write `out_file` in, inferring it from the extension if `format` is not
+/// given.
+fn output_format(
+    out_file: &Path,
+    format: Option<Format>,
+    compress: bool,
+    compact: bool,
+) -> Result<Format> {
+    let format = format.unwrap_or_else(|| Format::infer(out_file));
+    if format != Format::Postcard && (compress || compact) {
+        bail!("--compress and --compact only apply to the postcard format");
+    }
+    Ok(format)
+}
+
+/// Writes the table to `out_file`, or to stdout if it is -, and prints a summary of it.
+fn save_table(
+    ppm_table: &PpmTable,
+    out_file: &Path,
+    format: Format,
+    compress: bool,
+    compact: bool,
+) -> Result<()> {
+    let summary = format!(
+        "parsed {} submissions, {} pairs",
+        ppm_table.len(),
+        ppm_table.num_edges()
+    );
+
+    if out_file.as_os_str() == "-" {
+        // Keep stdout for the table.
+        eprintln!("{}", summary);
+        write_table(io::stdout().lock(), ppm_table, format, compress, compact)
+    } else {
+        println!("{}", summary);
+        let file = BufWriter::new(File::create(out_file)?);
+        write_table(file, ppm_table, format, compress, compact)
+    }
+}
+
+fn convert(args: ConvertArgs) -> Result<()> {
+    let format = output_format(&args.out_file, args.format, args.compress, args.compact)?;
+
+    let mut checkpoint_path = args.out_file.clone().into_os_string();
+    checkpoint_path.push(".checkpoint");
+    let checkpoint_path = PathBuf::from(checkpoint_path);
+    let checkpointing = args.checkpoint_every.map(|every| Checkpointing {
+        every,
+        path: &checkpoint_path,
+    });
+
+    let id_regex = id_regex(args.id_regex.as_deref(), args.handin_name.as_deref())?;
+    let key_options = KeyOptions {
+        id_regex: id_regex.as_ref(),
+        normalize_unicode: !args.no_normalize_unicode,
+    };
+    let mut builder = load_builder(
+        &args.in_file,
+        args.on_duplicate,
+        args.skip_malformed,
+        key_options,
+        checkpointing,
+        args.resume.as_deref(),
+    )?;
+    for merge_file in args.merge_files {
+        let chunk = load_builder(
+            &merge_file,
+            args.on_duplicate,
+            args.skip_malformed,
+            key_options,
+            None,
+            None,
+        )?;
+        builder.merge(chunk, args.on_conflict.into())?;
+    }
+    let ppm_table = match args.default_ppm {
+        Some(default_ppm) => builder.build_with_default(default_ppm),
+        None => builder.build().map_err(LoadAllpairsError::from)?,
+    };
+    save_table(
+        &ppm_table,
+        &args.out_file,
+        format,
+        args.compress,
+        args.compact,
+    )?;
+    if checkpointing.is_some() && checkpoint_path.exists() {
+        fs::remove_file(checkpoint_path)?;
+    }
+
+    Ok(())
+}
+
+fn compare(args: CompareArgs) -> Result<()> {
+    let format = output_format(&args.out_file, args.format, args.compress, args.compact)?;
+    let computed = allpairs::compute_from_dir(&args.dir, &args.handin_name)?;
+    for skipped in &computed.skipped {
+        eprintln!(
+            "warning: skipped {}: {}",
+            skipped.path.display(),
+            skipped.error
+        );
+    }
+    save_table(
+        &computed.table,
+        &args.out_file,
+        format,
+        args.compress,
+        args.compact,
+    )
+}
+
+/// Writes the summary printed by the inspect subcommand.
+fn write_summary(mut writer: impl Write, ppm_table: &PpmTable, top: usize) -> io::Result<()> {
+    writeln!(writer, "keys: {}", ppm_table.len())?;
+    writeln!(writer, "edges: {}", ppm_table.num_edges())?;
+    let (Some((_, _, min)), Some((_, _, max)), Some(stats)) = (
+        ppm_table.min_edge(),
+        ppm_table.max_edge(),
+        ppm_table.stats(),
+    ) else {
+        return Ok(());
+    };
+    writeln!(writer, "min PPM: {} ({:.2}%)", min, Ppm(min))?;
+    writeln!(writer, "max PPM: {} ({:.2}%)", max, Ppm(max))?;
+    writeln!(writer, "mean PPM: {:.1}", stats.mean)?;
+    writeln!(writer, "median PPM: {:.1}", stats.median)?;
+    writeln!(writer, "closest pairs:")?;
+    for (l, r, ppm) in ppm_table.top_k(top) {
+        writeln!(writer, "  {:.2}%  {}  {}", Ppm(ppm), l, r)?;
+    }
+    Ok(())
+}
+
+fn inspect(args: InspectArgs) -> Result<()> {
+    let mut bytes = Vec::new();
+    open(&args.file)?.read_to_end(&mut bytes)?;
+    let ppm_table = match args
+        .input_format
+        .unwrap_or_else(|| InputFormat::detect(&bytes))
+    {
+        InputFormat::Table => ppm_table::io::read_table(bytes.as_slice())?,
+        InputFormat::Allpairs => allpairs::load_from_reader(MaybeGzReader::new(bytes.as_slice())?)?,
+    };
+    write_summary(io::stdout().lock(), &ppm_table, args.top)?;
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match (cli.command, cli.convert) {
+        (Some(Command::Compare(args)), _) => compare(args),
+        (Some(Command::Inspect(args)), _) => inspect(args),
+        (Some(Command::Convert(args)), _) | (None, Some(args)) => convert(args),
+        (None, None) => unreachable!("clap requires the convert arguments without a subcommand"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::RandomState;
+
+    use super::*;
+
+    const FILE_CONTENTS: &str = concat!(
+        "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+        "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+        "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        "  2301     31   5000   5100 a2-anonymous/003/a2.py a2-anonymous/004/a2.py\n",
+        "  2410     40   5236   5100 a2-anonymous/002/a2.py a2-anonymous/004/a2.py\n",
+        "  2520     52   5260   5100 a2-anonymous/001/a2.py a2-anonymous/004/a2.py\n",
+    );
+
+    #[test]
+    fn test_checkpoint_resume() {
+        let start = || Checkpoint {
+            builder: PpmTableBuilder::new(),
+            offset: 0,
+            lines: 0,
+            first_line: None,
+            skipped: None,
+        };
+        let (single_pass, _) = add_lines(
+            FILE_CONTENTS.as_bytes(),
+            start(),
+            false,
+            KeyOptions::default(),
+            None,
+        )
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "allpairs-loader-test-{}.checkpoint",
+            std::process::id()
+        ));
+        let checkpointing = Checkpointing {
+            every: 2,
+            path: &path,
+        };
+        // The load is cut off partway through the fourth line, after the second checkpoint.
+        let cut_off = &FILE_CONTENTS[..FILE_CONTENTS.len() / 2 + 10];
+        add_lines(
+            cut_off.as_bytes(),
+            start(),
+            false,
+            KeyOptions::default(),
+            Some(checkpointing),
+        )
+        .unwrap_err();
+
+        let checkpoint =
+            ppm_table::io::read_checkpoint::<u32, RandomState>(File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.lines, 2);
+        let rest = &FILE_CONTENTS[checkpoint.offset as usize..];
+        assert!(rest.starts_with("  2232"));
+
+        let (resumed, _) = add_lines(
+            rest.as_bytes(),
+            checkpoint,
+            false,
+            KeyOptions::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(resumed, single_pass);
+        assert_eq!(resumed.build().unwrap(), single_pass.build().unwrap());
+    }
+
+    #[test]
+    fn test_checkpoint_resume_skipped_and_formats() {
+        let path = std::env::temp_dir().join(format!(
+            "allpairs-loader-test-{}-skipped.checkpoint",
+            std::process::id()
+        ));
+        let checkpointing = Checkpointing {
+            every: 2,
+            path: &path,
+        };
+        // The second line is malformed, and the load stops at the checkpoint after it.
+        let start = FILE_CONTENTS
+            .lines()
+            .take(2)
+            .map(|line| line.replace("2155 ", "215x ") + "\n")
+            .collect::<String>();
+        let checkpoint = Checkpoint {
+            builder: PpmTableBuilder::new(),
+            offset: 0,
+            lines: 0,
+            first_line: None,
+            skipped: None,
+        };
+        let (_, skipped) = add_lines(
+            start.as_bytes(),
+            checkpoint,
+            true,
+            KeyOptions::default(),
+            Some(checkpointing),
+        )
+        .unwrap();
+        assert_eq!(skipped, Some((1, 2)));
+
+        let checkpoint =
+            ppm_table::io::read_checkpoint::<u32, RandomState>(File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.first_line, Some((6, 1)));
+        assert_eq!(checkpoint.skipped, Some((1, 2)));
+
+        // The rest of the file leaves out the edit distance and lengths, unlike its first line.
+        let rest = "  2232 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n";
+        let err = add_lines(
+            rest.as_bytes(),
+            checkpoint.clone(),
+            false,
+            KeyOptions::default(),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LoadAllpairsError>(),
+            Some(&LoadAllpairsError::MixedLineFormats {
+                line_number: 3,
+                columns: 3,
+                first_line_number: 1,
+                first_columns: 6,
+            })
+        );
+        let (_, skipped) = add_lines(
+            rest.as_bytes(),
+            checkpoint,
+            true,
+            KeyOptions::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(skipped, Some((2, 2)));
+    }
+
+    #[test]
+    fn test_id_regex() {
+        let start = Checkpoint {
+            builder: PpmTableBuilder::new(),
+            offset: 0,
+            lines: 0,
+            first_line: None,
+            skipped: None,
+        };
+        let handin_name = id_regex(None, Some("a2.py")).unwrap();
+        let (builder, _) = add_lines(
+            FILE_CONTENTS.as_bytes(),
+            start.clone(),
+            false,
+            KeyOptions {
+                id_regex: handin_name.as_ref(),
+                normalize_unicode: false,
+            },
+            None,
+        )
+        .unwrap();
+        let ppm_table = builder.build().unwrap();
+        assert_eq!(ppm_table[("001", "004")], 2520);
+
+        let student = id_regex(Some(r"/(\d+)/"), None).unwrap();
+        let (builder, _) = add_lines(
+            FILE_CONTENTS.as_bytes(),
+            start,
+            false,
+            KeyOptions {
+                id_regex: student.as_ref(),
+                normalize_unicode: false,
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(builder.build().unwrap(), ppm_table);
+
+        assert!(id_regex(None, None).unwrap().is_none());
+        let err = id_regex(Some("(unclosed"), None).unwrap_err();
+        assert!(err.to_string().contains("(unclosed"));
+        let err = id_regex(Some(r"\d+"), None).unwrap_err();
+        assert!(err.to_string().contains("no capture group"));
+    }
+
+    #[test]
+    fn test_key_options_normalize_unicode() {
+        let handin_name = id_regex(None, Some("a2.py")).unwrap();
+        let key_options = KeyOptions {
+            id_regex: handin_name.as_ref(),
+            normalize_unicode: true,
+        };
+        let decomposed = "a2/Rene\u{301}/a2.py".to_string();
+        assert_eq!(key_options.key(decomposed.clone(), 1).unwrap(), "Ren\u{e9}");
+        let key_options = KeyOptions {
+            normalize_unicode: false,
+            ..key_options
+        };
+        assert_eq!(key_options.key(decomposed, 1).unwrap(), "Rene\u{301}");
+    }
+
+    #[test]
+    fn test_write_table_formats() {
+        let ppm_table = allpairs::load(FILE_CONTENTS.to_string()).unwrap();
+
+        let mut postcard = Vec::new();
+        write_table(&mut postcard, &ppm_table, Format::Postcard, false, false).unwrap();
+        let read = ppm_table::io::read_table::<u32, RandomState>(postcard.as_slice()).unwrap();
+        assert_eq!(read, ppm_table);
+
+        let mut json = Vec::new();
+        write_table(&mut json, &ppm_table, Format::Json, false, false).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<PpmTable>(&json).unwrap(),
+            ppm_table
+        );
+
+        let mut csv = Vec::new();
+        write_table(&mut csv, &ppm_table, Format::Csv, false, false).unwrap();
+        assert_eq!(allpairs::load_csv(csv.as_slice()).unwrap(), ppm_table);
+
+        assert_eq!(Format::infer(Path::new("out.json")), Format::Json);
+        assert_eq!(Format::infer(Path::new("out.csv")), Format::Csv);
+        assert_eq!(Format::infer(Path::new("out.ppm-table")), Format::Postcard);
+        assert_eq!(Format::infer(Path::new("-")), Format::Postcard);
+    }
+
+    #[test]
+    fn test_inspect_summary() {
+        let ppm_table = allpairs::load(FILE_CONTENTS.to_string()).unwrap();
+        let mut postcard = Vec::new();
+        write_table(&mut postcard, &ppm_table, Format::Postcard, false, true).unwrap();
+        assert_eq!(InputFormat::detect(&postcard), InputFormat::Table);
+        assert_eq!(
+            InputFormat::detect(FILE_CONTENTS.as_bytes()),
+            InputFormat::Allpairs
+        );
+
+        let mut summary = Vec::new();
+        write_summary(&mut summary, &ppm_table, 2).unwrap();
+        assert_eq!(
+            String::from_utf8(summary).unwrap(),
+            concat!(
+                "keys: 4\n",
+                "edges: 6\n",
+                "min PPM: 2155 (0.21%)\n",
+                "max PPM: 2520 (0.25%)\n",
+                "mean PPM: 2301.5\n",
+                "median PPM: 2266.5\n",
+                "closest pairs:\n",
+                "  0.21%  a2-anonymous/001/a2.py  a2-anonymous/003/a2.py\n",
+                "  0.21%  a2-anonymous/001/a2.py  a2-anonymous/002/a2.py\n",
+            )
+        );
+    }
+}
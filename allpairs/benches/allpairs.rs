@@ -0,0 +1,39 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// An allpairs file for a complete graph on `n` submissions.
+fn synthetic_allpairs(n: usize) -> String {
+    let mut contents = String::new();
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let ppm = (i * 7919 + j * 104729) % 1_000_000;
+            contents.push_str(&format!(
+                "{:6} {:6} {:6} {:6} a2-anonymous/{:04}/a2.py a2-anonymous/{:04}/a2.py\n",
+                ppm,
+                ppm / 200,
+                5000 + i,
+                5000 + j,
+                i,
+                j
+            ));
+        }
+    }
+    contents
+}
+
+fn bench_allpairs(c: &mut Criterion) {
+    // 447 submissions make just under 100,000 lines.
+    let contents = synthetic_allpairs(447);
+
+    c.bench_function("parse", |b| {
+        b.iter(|| black_box(allpairs::parse(&contents).filter(Result::is_ok).count()))
+    });
+
+    c.bench_function("load", |b| {
+        b.iter(|| black_box(allpairs::load(contents.clone()).unwrap().num_edges()))
+    });
+}
+
+criterion_group!(benches, bench_allpairs);
+criterion_main!(benches);
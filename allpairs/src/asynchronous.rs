@@ -0,0 +1,93 @@
+use std::hash::{BuildHasher, RandomState};
+
+use ppm_table::{PpmTable, PpmTableBuilder};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::{
+    add_line, build_table, trim_line, LineFormats, LoadAllpairsError, LoadOptions, SeenPairs,
+};
+
+/// The number of lines to parse between yields to the runtime.
+const LINES_PER_YIELD: usize = 1024;
+
+/// Like [`load_from_reader`](crate::load_from_reader), but reads from an async reader, yielding to
+/// the runtime every so often so that a large file does not hold up other tasks.
+pub async fn load_from_async_reader<R: AsyncBufRead + Unpin>(
+    reader: R,
+) -> Result<PpmTable, LoadAllpairsError> {
+    load_from_async_reader_with_options(reader, LoadOptions::default()).await
+}
+
+pub async fn load_from_async_reader_with_options<R: AsyncBufRead + Unpin>(
+    reader: R,
+    options: LoadOptions,
+) -> Result<PpmTable, LoadAllpairsError> {
+    load_from_async_reader_with_options_and_hasher::<_, RandomState>(reader, options).await
+}
+
+pub async fn load_from_async_reader_with_options_and_hasher<R, S>(
+    mut reader: R,
+    options: LoadOptions,
+) -> Result<PpmTable<u32, S>, LoadAllpairsError>
+where
+    R: AsyncBufRead + Unpin,
+    S: BuildHasher + Default,
+{
+    let mut builder = PpmTableBuilder::new().with_duplicate_policy(options.duplicate_policy);
+    let mut seen = SeenPairs::new(options);
+    let mut formats = LineFormats::default();
+    let mut line = String::new();
+    let mut line_number = 0;
+    loop {
+        line.clear();
+        line_number += 1;
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|err| LoadAllpairsError::Io(err.to_string()))?;
+        if read == 0 {
+            break;
+        }
+        add_line(
+            &mut builder,
+            &mut seen,
+            line_number,
+            trim_line(line_number, &line),
+            options,
+            &mut formats,
+        )?;
+        if line_number % LINES_PER_YIELD == 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+    build_table(builder, seen, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_from_reader;
+
+    #[tokio::test]
+    async fn test_load_from_async_reader() {
+        let contents = (1..=60)
+            .flat_map(|i| (i + 1..=60).map(move |j| (i, j)))
+            .map(|(i, j)| format!("{} 0 10 10 a/{:02}/a.py a/{:02}/a.py\r\n", i * j, i, j))
+            .collect::<String>();
+        assert!(contents.lines().count() > LINES_PER_YIELD);
+
+        let ppm_table = load_from_async_reader(contents.as_bytes())
+            .await
+            .expect("File should be valid.");
+        assert_eq!(
+            ppm_table,
+            load_from_reader(contents.as_bytes()).expect("File should be valid.")
+        );
+
+        let truncated = &contents[..contents.len() - 10];
+        assert_eq!(
+            load_from_async_reader(truncated.as_bytes()).await,
+            load_from_reader(truncated.as_bytes())
+        );
+    }
+}
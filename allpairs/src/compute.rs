@@ -0,0 +1,192 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ppm_table::{PpmTable, PpmTableBuilder};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::ppm_from_edit_distance;
+
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum ComputeError {
+    #[error("The directory {path} could not be read: {error}")]
+    ReadDir { path: PathBuf, error: String },
+    #[error("Found {found} handins named {handin_name}, but at least two are needed.")]
+    TooFewHandins { handin_name: String, found: usize },
+}
+
+/// A file or directory that was skipped by [`compute_from_dir`] because it could not be read.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnreadableHandin {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// The result of [`compute_from_dir`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComputedTable {
+    pub table: PpmTable,
+    pub skipped: Vec<UnreadableHandin>,
+}
+
+/// Compares every pair of files named `handin_name` under `dir` as lexscan would, without needing
+/// an allpairs file: the PPM of a pair is the Levenshtein distance between their bytes per million
+/// bytes of the longer file. Each file is keyed by its path from the parent of `dir`, like
+/// `a2/001/a2.py`, as in an allpairs file run from there. With the `rayon` feature, the pairs are
+/// compared in parallel.
+pub fn compute_from_dir(dir: &Path, handin_name: &str) -> Result<ComputedTable, ComputeError> {
+    let read_dir_error = |error: std::io::Error| ComputeError::ReadDir {
+        path: dir.to_path_buf(),
+        error: error.to_string(),
+    };
+    let dir = dir.canonicalize().map_err(read_dir_error)?;
+    let root = dir.parent().unwrap_or(&dir);
+
+    let mut paths = Vec::new();
+    let mut skipped = Vec::new();
+    find_handins(&dir, handin_name, &mut paths, &mut skipped).map_err(read_dir_error)?;
+    paths.sort_unstable();
+
+    let mut handins = Vec::with_capacity(paths.len());
+    for path in paths {
+        match fs::read(&path) {
+            Ok(contents) => {
+                let key = path.strip_prefix(root).unwrap_or(&path);
+                let key = key
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                handins.push((key, contents));
+            }
+            Err(error) => skipped.push(UnreadableHandin {
+                path,
+                error: error.to_string(),
+            }),
+        }
+    }
+    if handins.len() < 2 {
+        return Err(ComputeError::TooFewHandins {
+            handin_name: handin_name.to_string(),
+            found: handins.len(),
+        });
+    }
+
+    let n = handins.len();
+    let compare = |i: usize, j: usize| {
+        let (l, r) = (&handins[i].1, &handins[j].1);
+        let ppm = ppm_from_edit_distance(edit_distance(l, r), l.len() as u64, r.len() as u64)
+            .expect("The edit distance is at most the length of the longer file.");
+        (i, j, ppm)
+    };
+    #[cfg(feature = "rayon")]
+    let ppms = (0..n)
+        .into_par_iter()
+        .flat_map_iter(|i| (i + 1..n).map(move |j| compare(i, j)))
+        .collect::<Vec<_>>();
+    #[cfg(not(feature = "rayon"))]
+    let ppms = (0..n).flat_map(|i| (i + 1..n).map(move |j| compare(i, j)));
+
+    let mut builder = PpmTableBuilder::with_capacity(n);
+    for (i, j, ppm) in ppms {
+        builder.add_ppm_interned(&handins[i].0, &handins[j].0, ppm);
+    }
+    let table = builder
+        .build()
+        .expect("Every pair of handins was compared.");
+    Ok(ComputedTable { table, skipped })
+}
+
+/// Collects the files named `handin_name` under `dir`. A subdirectory that cannot be read is
+/// added to `skipped`, but `dir` itself must be readable.
+fn find_handins(
+    dir: &Path,
+    handin_name: &str,
+    paths: &mut Vec<PathBuf>,
+    skipped: &mut Vec<UnreadableHandin>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if let Err(error) = find_handins(&path, handin_name, paths, skipped) {
+                skipped.push(UnreadableHandin {
+                    path,
+                    error: error.to_string(),
+                });
+            }
+        } else if entry.file_name() == handin_name {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The Levenshtein distance between `l` and `r`, counting insertions, deletions, and
+/// substitutions of single bytes.
+fn edit_distance(l: &[u8], r: &[u8]) -> u64 {
+    // Keep one row of the table, as long as the shorter input.
+    let (l, r) = if l.len() < r.len() { (r, l) } else { (l, r) };
+    let mut row = (0..=r.len()).collect::<Vec<_>>();
+    for (i, &l_byte) in l.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &r_byte) in r.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if l_byte == r_byte {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[r.len()] as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance(b"kitten", b"sitting"), 3);
+        assert_eq!(edit_distance(b"sitting", b"kitten"), 3);
+        assert_eq!(edit_distance(b"", b"abc"), 3);
+        assert_eq!(edit_distance(b"", b""), 0);
+        assert_eq!(edit_distance(b"flaw", b"lawn"), 2);
+    }
+
+    #[test]
+    fn test_compute_from_dir() {
+        let dir = std::env::temp_dir().join(format!("allpairs-compute-{}", std::process::id()));
+        let a2 = dir.join("a2");
+        for (id, contents) in [("001", "kitten"), ("002", "sitting"), ("003", "kitchen")] {
+            fs::create_dir_all(a2.join(id)).unwrap();
+            fs::write(a2.join(id).join("a2.py"), contents).unwrap();
+        }
+        fs::write(a2.join("001").join("notes.txt"), "not a handin").unwrap();
+
+        let computed = compute_from_dir(&a2, "a2.py");
+        let too_few = compute_from_dir(&a2.join("001"), "a2.py");
+        fs::remove_dir_all(&dir).unwrap();
+
+        let ComputedTable { table, skipped } = computed.expect("Three handins should be found.");
+        assert!(skipped.is_empty());
+        assert_eq!(table.len(), 3);
+        // kitten -> sitting is 3 edits over 7 bytes, kitten -> kitchen is 2 over 7, and
+        // sitting -> kitchen is 5 over 7.
+        assert_eq!(table[("a2/001/a2.py", "a2/002/a2.py")], 428_571);
+        assert_eq!(table[("a2/001/a2.py", "a2/003/a2.py")], 285_714);
+        assert_eq!(table[("a2/002/a2.py", "a2/003/a2.py")], 714_285);
+
+        assert_eq!(
+            too_few,
+            Err(ComputeError::TooFewHandins {
+                handin_name: "a2.py".to_string(),
+                found: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, BufRead, Write};
+
+use csv::{ReaderBuilder, StringRecord, Trim, Writer};
+use ppm_table::ppm::MAX_PPM;
+use ppm_table::{BuildError, BuildErrorKind, MissingPairs, PpmTable, PpmTableBuilder};
+use thiserror::Error;
+
+use crate::MAX_REPORTED_MISSING_PAIRS;
+
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum LoadCsvError {
+    #[error("Line {line_number} of the CSV file does not have exactly 3 fields.")]
+    InvalidRecord { line_number: u64, fields: usize },
+    #[error("The PPM {ppm:?} on line {line_number} of the CSV file is not a valid PPM.")]
+    InvalidPpm { line_number: u64, ppm: String },
+    #[error("The PPM {value} on line {line_number} of the CSV file is more than 1,000,000.")]
+    PpmOutOfRange { value: u32, line_number: u64 },
+    #[error("The provided CSV file does not correspond to a complete similarity graph: {0}.")]
+    IncompleteGraph(MissingPairs),
+    #[error(transparent)]
+    Build(BuildErrorKind),
+    #[error("The CSV file could not be read: {0}")]
+    Csv(String),
+}
+
+impl<S: BuildHasher + Default> From<BuildError<u32, S>> for LoadCsvError {
+    fn from(err: BuildError<u32, S>) -> Self {
+        match err.kind() {
+            BuildErrorKind::IncompleteGraph { .. } => LoadCsvError::IncompleteGraph(
+                err.builder()
+                    .missing_pairs_summary(MAX_REPORTED_MISSING_PAIRS),
+            ),
+            kind => LoadCsvError::Build(kind),
+        }
+    }
+}
+
+/// Loads a CSV file of `left,right,similarity_ppm` records. Fields may be quoted, e.g. to hold
+/// keys that contain commas, and surrounding whitespace is ignored. The first record is treated as
+/// a header if its PPM is not a number.
+pub fn load_csv(reader: impl BufRead) -> Result<PpmTable, LoadCsvError> {
+    load_csv_with_hasher::<RandomState>(reader)
+}
+
+pub fn load_csv_with_hasher<S: BuildHasher + Default>(
+    reader: impl BufRead,
+) -> Result<PpmTable<u32, S>, LoadCsvError> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(reader);
+
+    let mut builder = PpmTableBuilder::new();
+    let mut record = StringRecord::new();
+    let mut first = true;
+    while reader
+        .read_record(&mut record)
+        .map_err(|err| LoadCsvError::Csv(err.to_string()))?
+    {
+        let line_number = record.position().map_or(0, |position| position.line());
+        let [l, r, ppm] = [0, 1, 2].map(|i| record.get(i));
+        let (Some(l), Some(r), Some(ppm), 3) = (l, r, ppm, record.len()) else {
+            return Err(LoadCsvError::InvalidRecord {
+                line_number,
+                fields: record.len(),
+            });
+        };
+        match ppm.parse() {
+            Ok(ppm) if ppm > MAX_PPM => {
+                return Err(LoadCsvError::PpmOutOfRange {
+                    value: ppm,
+                    line_number,
+                })
+            }
+            Ok(ppm) => builder.add_ppm(l.to_string(), r.to_string(), ppm),
+            Err(_) if first => {}
+            Err(_) => {
+                return Err(LoadCsvError::InvalidPpm {
+                    line_number,
+                    ppm: ppm.to_string(),
+                })
+            }
+        }
+        first = false;
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Writes `table` as a CSV file of `left,right,similarity_ppm` records with a header, which
+/// [`load_csv`] reads back.
+pub fn write_csv<S: BuildHasher + Default>(
+    table: &PpmTable<u32, S>,
+    writer: impl Write,
+) -> io::Result<()> {
+    let mut writer = Writer::from_writer(writer);
+    writer.write_record(["left", "right", "similarity_ppm"])?;
+    for (l, r, ppm) in table.edges() {
+        writer.write_record([l, r, &ppm.to_string()])?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_csv() {
+        let contents = concat!(
+            "left,right,similarity_ppm\n",
+            "001,002,2191\n",
+            "\"Smith, John\",001, 2155\n",
+            "002,\"Smith, John\",2232\n",
+        );
+        let ppm_table = load_csv(contents.as_bytes()).expect("File should be valid.");
+        assert_eq!(ppm_table.len(), 3);
+        assert_eq!(ppm_table[("001", "002")], 2191);
+        assert_eq!(ppm_table[("001", "Smith, John")], 2155);
+        assert_eq!(ppm_table[("Smith, John", "002")], 2232);
+
+        // The header is optional.
+        let headerless = contents.split_once('\n').unwrap().1;
+        assert_eq!(
+            load_csv(headerless.as_bytes()).expect("File should be valid."),
+            ppm_table
+        );
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let contents = concat!(
+            "left,right,similarity_ppm\n",
+            "001,002,2191\n",
+            "001,\"Smith, John\",2155\n",
+            "002,\"Smith, John\",2232\n",
+        );
+        let ppm_table = load_csv(contents.as_bytes()).expect("File should be valid.");
+        let mut written = Vec::new();
+        write_csv(&ppm_table, &mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), contents);
+    }
+
+    #[test]
+    fn test_load_csv_max_ppm() {
+        let ppm_table = load_csv("001,002,1000000\n".as_bytes()).expect("File should be valid.");
+        assert_eq!(ppm_table[("001", "002")], MAX_PPM);
+    }
+
+    #[test]
+    fn test_load_csv_errors() {
+        assert_eq!(
+            load_csv("001,002,2191\n001,003,abc\n".as_bytes()),
+            Err(LoadCsvError::InvalidPpm {
+                line_number: 2,
+                ppm: "abc".to_string()
+            })
+        );
+        assert_eq!(
+            load_csv("001,002,2191\n001,003\n".as_bytes()),
+            Err(LoadCsvError::InvalidRecord {
+                line_number: 2,
+                fields: 2
+            })
+        );
+        assert_eq!(
+            load_csv("001,002,2191\n001,003,1000001\n".as_bytes()),
+            Err(LoadCsvError::PpmOutOfRange {
+                value: 1_000_001,
+                line_number: 2
+            })
+        );
+        assert!(matches!(
+            load_csv("001,002,2191\n001,003,2155\n".as_bytes()),
+            Err(LoadCsvError::IncompleteGraph(MissingPairs { total: 1, .. }))
+        ));
+    }
+}
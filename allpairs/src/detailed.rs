@@ -0,0 +1,203 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::io::BufRead;
+
+use ppm_table::{PpmTable, PpmTableBuilder};
+
+use crate::{map_lines, parse_ppm, split_line, LoadAllpairsError};
+
+/// The columns of an allpairs line besides the PPM and the paths.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PairMeta {
+    pub edit_distance: u32,
+    pub l_len: u32,
+    pub r_len: u32,
+}
+
+/// A path whose length on `line_number` differs from the length it had on an earlier line, which
+/// usually means files from different inputs have been mixed together.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InconsistentLength {
+    pub path: String,
+    pub first: u32,
+    pub second: u32,
+    pub line_number: usize,
+}
+
+/// The result of [`load_detailed`]: the table, along with the metadata that [`load`](crate::load)
+/// discards.
+#[derive(Clone, Debug)]
+pub struct DetailedTable<S: BuildHasher + Default = RandomState> {
+    pub table: PpmTable<u32, S>,
+    /// The metadata for each pair, keyed with the smaller path first.
+    pub metadata: HashMap<(String, String), PairMeta, S>,
+    pub warnings: Vec<InconsistentLength>,
+}
+
+impl<S: BuildHasher + Default> DetailedTable<S> {
+    /// The metadata for a pair, with `l_len` and `r_len` in the order the paths were given.
+    pub fn meta(&self, l: &str, r: &str) -> Option<PairMeta> {
+        let (first, second) = if l <= r { (l, r) } else { (r, l) };
+        let meta = *self
+            .metadata
+            .get(&(first.to_string(), second.to_string()))?;
+        if l <= r {
+            Some(meta)
+        } else {
+            Some(PairMeta {
+                l_len: meta.r_len,
+                r_len: meta.l_len,
+                ..meta
+            })
+        }
+    }
+}
+
+/// Like [`load`](crate::load), but keeps the edit distance and lengths of each pair, and reports
+/// any path whose length is inconsistent between lines.
+pub fn load_detailed(file_contents: String) -> Result<DetailedTable, LoadAllpairsError> {
+    load_detailed_from_reader(file_contents.as_bytes())
+}
+
+pub fn load_detailed_from_reader(reader: impl BufRead) -> Result<DetailedTable, LoadAllpairsError> {
+    load_detailed_from_reader_with_hasher::<RandomState>(reader)
+}
+
+pub fn load_detailed_from_reader_with_hasher<S: BuildHasher + Default>(
+    reader: impl BufRead,
+) -> Result<DetailedTable<S>, LoadAllpairsError> {
+    let mut builder = PpmTableBuilder::new();
+    let mut metadata = HashMap::default();
+    let mut lengths = HashMap::<String, u32>::new();
+    let mut warnings = Vec::new();
+    for line in map_lines(reader, parse_detailed_line) {
+        let (line_number, ppm, mut l, mut r, mut meta) = line?;
+        for (path, len) in [(&l, meta.l_len), (&r, meta.r_len)] {
+            match lengths.entry(path.clone()) {
+                Entry::Occupied(first) if *first.get() != len => {
+                    warnings.push(InconsistentLength {
+                        path: path.clone(),
+                        first: *first.get(),
+                        second: len,
+                        line_number,
+                    })
+                }
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) => {
+                    entry.insert(len);
+                }
+            }
+        }
+        if l > r {
+            (l, r) = (r, l);
+            (meta.l_len, meta.r_len) = (meta.r_len, meta.l_len);
+        }
+        metadata.insert((l.clone(), r.clone()), meta);
+        builder.add_ppm(l, r, ppm);
+    }
+
+    Ok(DetailedTable {
+        table: builder.build()?,
+        metadata,
+        warnings,
+    })
+}
+
+fn parse_detailed_line(
+    line_number: usize,
+    line: &str,
+) -> Result<(usize, u32, String, String, PairMeta), LoadAllpairsError> {
+    let (columns, l, r) = split_line(line_number, line)?;
+    let ppm = parse_ppm(line_number, columns.ppm)?;
+    // Only lines with every column have the metadata.
+    let (Some(edit_distance), Some((l_len, r_len))) = (columns.edit_distance, columns.lengths)
+    else {
+        return Err(LoadAllpairsError::InvalidLine {
+            line_number,
+            line: line.to_string(),
+        });
+    };
+    let [edit_distance, l_len, r_len] = [edit_distance, l_len, r_len].map(str::parse);
+    let (Ok(edit_distance), Ok(l_len), Ok(r_len)) = (edit_distance, l_len, r_len) else {
+        return Err(LoadAllpairsError::InvalidLine {
+            line_number,
+            line: line.to_string(),
+        });
+    };
+    let meta = PairMeta {
+        edit_distance,
+        l_len,
+        r_len,
+    };
+    Ok((line_number, ppm, l.to_string(), r.to_string(), meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_CONTENTS: &str = concat!(
+        "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+        "  2155     49   5000   5260 a2-anonymous/003/a2.py a2-anonymous/001/a2.py\n",
+        "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+    );
+
+    #[test]
+    fn test_load_detailed() {
+        let detailed = load_detailed(FILE_CONTENTS.to_string()).expect("File should be valid.");
+        assert_eq!(
+            detailed.table,
+            crate::load(FILE_CONTENTS.to_string()).expect("File should be valid.")
+        );
+        assert!(detailed.warnings.is_empty());
+        assert_eq!(detailed.metadata.len(), 3);
+
+        let meta = PairMeta {
+            edit_distance: 49,
+            l_len: 5260,
+            r_len: 5000,
+        };
+        assert_eq!(
+            detailed.meta("a2-anonymous/001/a2.py", "a2-anonymous/003/a2.py"),
+            Some(meta)
+        );
+        assert_eq!(
+            detailed.meta("a2-anonymous/003/a2.py", "a2-anonymous/001/a2.py"),
+            Some(PairMeta {
+                l_len: 5000,
+                r_len: 5260,
+                ..meta
+            })
+        );
+        assert_eq!(detailed.meta("a2-anonymous/001/a2.py", "missing"), None);
+    }
+
+    #[test]
+    fn test_load_detailed_inconsistent_length() {
+        let contents = FILE_CONTENTS.replace("5000   5260", "5001   5260");
+        let detailed = load_detailed(contents).expect("File should be valid.");
+        assert_eq!(
+            detailed.warnings,
+            [InconsistentLength {
+                path: "a2-anonymous/003/a2.py".to_string(),
+                first: 5001,
+                second: 5000,
+                line_number: 3,
+            }]
+        );
+
+        let contents = FILE_CONTENTS.replace("    23 ", "    xx ");
+        assert_eq!(
+            load_detailed(contents).map(|detailed| detailed.table),
+            Err(LoadAllpairsError::InvalidLine {
+                line_number: 1,
+                line: FILE_CONTENTS
+                    .lines()
+                    .next()
+                    .unwrap()
+                    .replace("    23 ", "    xx "),
+            })
+        );
+    }
+}
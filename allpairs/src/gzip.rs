@@ -0,0 +1,98 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+use flate2::bufread::MultiGzDecoder;
+
+/// The bytes at the start of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Reads from `R`, decompressing it on the fly if it starts with the gzip magic bytes, so that
+/// plain and gzipped allpairs files can be loaded alike.
+#[derive(Debug)]
+pub enum MaybeGzReader<R> {
+    Plain(R),
+    Gzip(BufReader<MultiGzDecoder<R>>),
+}
+
+impl<R: BufRead> MaybeGzReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            Ok(MaybeGzReader::Gzip(BufReader::new(MultiGzDecoder::new(
+                reader,
+            ))))
+        } else {
+            Ok(MaybeGzReader::Plain(reader))
+        }
+    }
+}
+
+impl<R: BufRead> Read for MaybeGzReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            MaybeGzReader::Plain(reader) => reader.read(buf),
+            MaybeGzReader::Gzip(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl<R: BufRead> BufRead for MaybeGzReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            MaybeGzReader::Plain(reader) => reader.fill_buf(),
+            MaybeGzReader::Gzip(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            MaybeGzReader::Plain(reader) => reader.consume(amt),
+            MaybeGzReader::Gzip(reader) => reader.consume(amt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+    use crate::load_from_reader;
+
+    const FILE_CONTENTS: &str = concat!(
+        "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+        "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+        "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+    );
+
+    #[test]
+    fn test_gzip_load() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(FILE_CONTENTS.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let reader = MaybeGzReader::new(gzipped.as_slice()).unwrap();
+        assert!(matches!(reader, MaybeGzReader::Gzip(_)));
+        let ppm_table = load_from_reader(reader).expect("File should be valid.");
+        assert_eq!(
+            ppm_table,
+            load_from_reader(FILE_CONTENTS.as_bytes()).expect("File should be valid.")
+        );
+    }
+
+    #[test]
+    fn test_gzip_plain_passthrough() {
+        let reader = MaybeGzReader::new(FILE_CONTENTS.as_bytes()).unwrap();
+        assert!(matches!(reader, MaybeGzReader::Plain(_)));
+        let ppm_table = load_from_reader(reader).expect("File should be valid.");
+        assert_eq!(ppm_table.num_edges(), 3);
+
+        let mut empty = String::new();
+        MaybeGzReader::new(&b""[..])
+            .unwrap()
+            .read_to_string(&mut empty)
+            .unwrap();
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+
+use ppm_table::{PpmTable, PpmTableBuilder};
+use thiserror::Error;
+
+use crate::{
+    build_table, normalize_unicode, parse_reader_with_source, LoadAllpairsError, LoadOptions,
+    SeenPairs,
+};
+
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum LoadWithIdsError<E> {
+    #[error("Could not extract an ID from {path} on line {line_number}: {error}")]
+    Extract {
+        path: String,
+        line_number: usize,
+        #[source]
+        error: E,
+    },
+    #[error(
+        "{second_path} on line {line_number} has the ID {id}, which was already extracted from \
+         {first_path}."
+    )]
+    IdCollision {
+        id: String,
+        first_path: String,
+        second_path: String,
+        line_number: usize,
+    },
+    #[error(transparent)]
+    Load(#[from] LoadAllpairsError),
+}
+
+/// Like [`load`](crate::load), but keys the table by the IDs that `extract` maps each path to,
+/// e.g. the student number in a submission's path. Two different paths mapping to the same ID
+/// are rejected with [`LoadWithIdsError::IdCollision`] unless
+/// [`LoadOptions::allow_id_collisions`] is set.
+pub fn load_with_id_extractor<F, E>(
+    file_contents: String,
+    extract: F,
+) -> Result<PpmTable, LoadWithIdsError<E>>
+where
+    F: Fn(&str) -> Result<String, E>,
+{
+    load_with_id_extractor_and_hasher::<RandomState, F, E>(file_contents, extract)
+}
+
+pub fn load_with_id_extractor_and_hasher<S, F, E>(
+    file_contents: String,
+    extract: F,
+) -> Result<PpmTable<u32, S>, LoadWithIdsError<E>>
+where
+    S: BuildHasher + Default,
+    F: Fn(&str) -> Result<String, E>,
+{
+    load_with_id_extractor_with_options_and_hasher(file_contents, extract, LoadOptions::default())
+}
+
+pub fn load_with_id_extractor_with_options<F, E>(
+    file_contents: String,
+    extract: F,
+    options: LoadOptions,
+) -> Result<PpmTable, LoadWithIdsError<E>>
+where
+    F: Fn(&str) -> Result<String, E>,
+{
+    load_with_id_extractor_with_options_and_hasher::<RandomState, F, E>(
+        file_contents,
+        extract,
+        options,
+    )
+}
+
+pub fn load_with_id_extractor_with_options_and_hasher<S, F, E>(
+    file_contents: String,
+    extract: F,
+    options: LoadOptions,
+) -> Result<PpmTable<u32, S>, LoadWithIdsError<E>>
+where
+    S: BuildHasher + Default,
+    F: Fn(&str) -> Result<String, E>,
+{
+    load_with_id_extractor_and_paths_and_hasher(file_contents, extract, options)
+        .map(|id_table| id_table.table)
+}
+
+/// The result of [`load_with_id_extractor_and_paths`].
+#[derive(Clone, Debug)]
+pub struct IdTable<S: BuildHasher + Default = RandomState> {
+    pub table: PpmTable<u32, S>,
+    /// The path that each ID in the table was first extracted from.
+    pub paths: HashMap<String, String>,
+}
+
+/// Like [`load_with_id_extractor_with_options`], but also returns the path that each ID in the
+/// table was first extracted from.
+pub fn load_with_id_extractor_and_paths<F, E>(
+    file_contents: String,
+    extract: F,
+    options: LoadOptions,
+) -> Result<IdTable, LoadWithIdsError<E>>
+where
+    F: Fn(&str) -> Result<String, E>,
+{
+    load_with_id_extractor_and_paths_and_hasher::<RandomState, F, E>(
+        file_contents,
+        extract,
+        options,
+    )
+}
+
+pub fn load_with_id_extractor_and_paths_and_hasher<S, F, E>(
+    file_contents: String,
+    extract: F,
+    options: LoadOptions,
+) -> Result<IdTable<S>, LoadWithIdsError<E>>
+where
+    S: BuildHasher + Default,
+    F: Fn(&str) -> Result<String, E>,
+{
+    let mut builder = PpmTableBuilder::new().with_duplicate_policy(options.duplicate_policy);
+    // The path that each ID was first extracted from.
+    let mut paths = HashMap::<String, String>::new();
+    let mut seen = SeenPairs::new(options);
+    let edges = parse_reader_with_source(file_contents.as_bytes(), options.ppm_source);
+    for (i, edge) in edges.enumerate() {
+        let (l, r, ppm) = edge?;
+        let mut extract = |path: String| {
+            let id = extract(&path).map_err(|error| LoadWithIdsError::Extract {
+                path: path.clone(),
+                line_number: i + 1,
+                error,
+            })?;
+            let id = match options.normalize_unicode {
+                true => normalize_unicode(&id).into_owned(),
+                false => id,
+            };
+            match paths.entry(id.clone()) {
+                Entry::Occupied(first) if *first.get() != path && !options.allow_id_collisions => {
+                    return Err(LoadWithIdsError::IdCollision {
+                        id,
+                        first_path: first.get().clone(),
+                        second_path: path,
+                        line_number: i + 1,
+                    })
+                }
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) => {
+                    entry.insert(path);
+                }
+            }
+            Ok(id)
+        };
+        let (l, r) = (extract(l)?, extract(r)?);
+        seen.insert(&l, &r);
+        if options.max_ppm.is_some_and(|max_ppm| ppm > max_ppm) {
+            builder.add_key(&l);
+            builder.add_key(&r);
+            continue;
+        }
+        builder
+            .try_add_ppm(l, r, ppm)
+            .map_err(|err| LoadAllpairsError::conflicting_duplicate(err, i + 1))?;
+    }
+    let table = build_table(builder, seen, options)?;
+    // An ID that only ever paired with itself never made it into the table.
+    paths.retain(|id, _| table.index_of(id).is_some());
+    Ok(IdTable { table, paths })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The submission directory of a path like `a2-anonymous/001/a2.py`.
+    fn submission(path: &str) -> Result<String, String> {
+        path.split('/')
+            .nth(1)
+            .map(str::to_string)
+            .ok_or_else(|| format!("{} has no submission directory", path))
+    }
+
+    #[test]
+    fn test_load_with_id_extractor() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        );
+        let ppm_table = load_with_id_extractor(file_contents.to_string(), submission)
+            .expect("File should be valid.");
+        assert_eq!(ppm_table.len(), 3);
+        assert_eq!(ppm_table[("001", "002")], 2191);
+        assert_eq!(ppm_table[("003", "001")], 2155);
+        assert_eq!(ppm_table[("002", "003")], 2232);
+    }
+
+    #[test]
+    fn test_load_with_id_extractor_and_paths() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+            "     0      0   5236   5236 a2-anonymous/002/a2.py a2-late/002/a2.py\n",
+        );
+        let options = LoadOptions {
+            allow_id_collisions: true,
+            ..LoadOptions::default()
+        };
+        let IdTable { table, paths } =
+            load_with_id_extractor_and_paths(file_contents.to_string(), submission, options)
+                .expect("File should be valid.");
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            paths,
+            HashMap::from(
+                [
+                    ("001", "a2-anonymous/001/a2.py"),
+                    ("002", "a2-anonymous/002/a2.py"),
+                    ("003", "a2-anonymous/003/a2.py"),
+                ]
+                .map(|(id, path)| (id.to_string(), path.to_string()))
+            )
+        );
+
+        // Every path is an ID of its own when they are used as is.
+        let complete = &file_contents[..file_contents.find("     0").unwrap()];
+        let id_table = load_with_id_extractor_and_paths(
+            complete.to_string(),
+            |path| Ok::<_, String>(path.to_string()),
+            LoadOptions::default(),
+        )
+        .expect("File should be valid.");
+        assert_eq!(id_table.paths.len(), 3);
+        assert!(id_table.paths.iter().all(|(id, path)| id == path));
+    }
+
+    #[test]
+    fn test_load_with_id_extractor_errors() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2.py\n",
+        );
+        assert_eq!(
+            load_with_id_extractor(file_contents.to_string(), submission),
+            Err(LoadWithIdsError::Extract {
+                path: "a2.py".to_string(),
+                line_number: 2,
+                error: "a2.py has no submission directory".to_string()
+            })
+        );
+
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-late/002/a2.py\n",
+        );
+        assert_eq!(
+            load_with_id_extractor(file_contents.to_string(), submission),
+            Err(LoadWithIdsError::IdCollision {
+                id: "002".to_string(),
+                first_path: "a2-anonymous/002/a2.py".to_string(),
+                second_path: "a2-late/002/a2.py".to_string(),
+                line_number: 2
+            })
+        );
+        let options = LoadOptions {
+            allow_id_collisions: true,
+            ..LoadOptions::default()
+        };
+        let ppm_table =
+            load_with_id_extractor_with_options(file_contents.to_string(), submission, options)
+                .expect("Collisions should be allowed.");
+        assert_eq!(ppm_table[("001", "002")], 2155);
+
+        let file_contents = "  2191     23   5260   5236 a2-anonymous/001/a2.py\n";
+        assert!(matches!(
+            load_with_id_extractor(file_contents.to_string(), submission),
+            Err(LoadWithIdsError::Load(LoadAllpairsError::InvalidLine {
+                line_number: 1,
+                ..
+            }))
+        ));
+    }
+}
@@ -1,59 +1,859 @@
+#[cfg(feature = "async")]
+mod asynchronous;
+mod compute;
+mod csv;
+mod detailed;
+#[cfg(feature = "gzip")]
+mod gzip;
+mod ids;
+#[cfg(feature = "mmap")]
+mod mmap;
+
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::hash::{BuildHasher, RandomState};
+use std::io::{self, BufRead, Write};
+
+#[cfg(feature = "async")]
+pub use asynchronous::{
+    load_from_async_reader, load_from_async_reader_with_options,
+    load_from_async_reader_with_options_and_hasher,
+};
+pub use compute::{compute_from_dir, ComputeError, ComputedTable, UnreadableHandin};
+pub use csv::{load_csv, load_csv_with_hasher, write_csv, LoadCsvError};
+pub use detailed::{
+    load_detailed, load_detailed_from_reader, load_detailed_from_reader_with_hasher, DetailedTable,
+    InconsistentLength, PairMeta,
+};
+#[cfg(feature = "gzip")]
+pub use gzip::MaybeGzReader;
+pub use ids::{
+    load_with_id_extractor, load_with_id_extractor_and_hasher, load_with_id_extractor_and_paths,
+    load_with_id_extractor_and_paths_and_hasher, load_with_id_extractor_with_options,
+    load_with_id_extractor_with_options_and_hasher, IdTable, LoadWithIdsError,
+};
+#[cfg(feature = "mmap")]
+pub use mmap::{
+    load_from_path, load_from_path_with_options, load_from_path_with_options_and_hasher,
+    load_lenient_from_path, MappedFile,
+};
 
-use ppm_table::{PpmTable, PpmTableBuilder};
+use ppm_table::ppm::MAX_PPM;
+use ppm_table::{
+    BuildError, BuildErrorKind, DuplicateError, DuplicatePolicy, MissingPairs, PpmTable,
+    PpmTableBuilder,
+};
 use thiserror::Error;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 
 #[derive(Clone, Debug, Error, Eq, PartialEq)]
 pub enum LoadAllpairsError {
-    #[error("A line in the file was not a valid allpairs entry.")]
-    InvalidLine(String),
-    #[error("The PPM in the file was missing or invalid.")]
-    PpmCaptureFail(String),
-    #[error("The provided allpairs file does not correspond to a complete similarity graph.")]
-    IncompleteGraph,
+    #[error("Line {line_number} of the file was not a valid allpairs entry.")]
+    InvalidLine { line_number: usize, line: String },
+    #[error("The PPM on line {line_number} of the file was missing or invalid.")]
+    PpmCaptureFail { line_number: usize, ppm: String },
+    #[error("The provided allpairs file does not correspond to a complete similarity graph: {0}.")]
+    IncompleteGraph(MissingPairs),
+    #[error(transparent)]
+    Build(BuildErrorKind),
+    #[error("The allpairs file could not be read: {0}")]
+    Io(String),
+    #[error(
+        "The pair ({l}, {r}) on line {line_number} has PPM {second}, but appeared earlier with \
+         PPM {first}."
+    )]
+    ConflictingDuplicate {
+        l: String,
+        r: String,
+        first: u32,
+        second: u32,
+        line_number: usize,
+    },
+    #[error(
+        "The PPM on line {line_number} of the file is {ppm}, but its edit distance and lengths \
+         give {recomputed}."
+    )]
+    PpmMismatch {
+        line_number: usize,
+        ppm: u32,
+        recomputed: u32,
+    },
+    #[error("The PPM {value} on line {line_number} of the file is more than 1,000,000.")]
+    PpmOutOfRange { value: u32, line_number: usize },
+    #[error("Line {line_number} of the file compares {path} against itself.")]
+    SelfComparison { line_number: usize, path: String },
+    #[error(
+        "Line {line_number} of the file has {columns} columns, but line {first_line_number} has \
+         {first_columns}."
+    )]
+    MixedLineFormats {
+        line_number: usize,
+        columns: usize,
+        first_line_number: usize,
+        first_columns: usize,
+    },
+    #[error("Line {line_number} of the file is not valid UTF-8 at byte {offset} of the file.")]
+    InvalidUtf8 { line_number: usize, offset: usize },
+}
+
+impl LoadAllpairsError {
+    /// The line that caused the error, if the error is about a single malformed line.
+    pub fn line_number(&self) -> Option<usize> {
+        match self {
+            LoadAllpairsError::InvalidLine { line_number, .. }
+            | LoadAllpairsError::PpmCaptureFail { line_number, .. }
+            | LoadAllpairsError::PpmMismatch { line_number, .. }
+            | LoadAllpairsError::PpmOutOfRange { line_number, .. }
+            | LoadAllpairsError::SelfComparison { line_number, .. }
+            | LoadAllpairsError::MixedLineFormats { line_number, .. }
+            | LoadAllpairsError::InvalidUtf8 { line_number, .. } => Some(*line_number),
+            _ => None,
+        }
+    }
+
+    /// Attaches the line on which the second PPM for a pair appeared to a [`DuplicateError`].
+    pub fn conflicting_duplicate(err: DuplicateError, line_number: usize) -> Self {
+        LoadAllpairsError::ConflictingDuplicate {
+            l: err.l,
+            r: err.r,
+            first: err.first,
+            second: err.second,
+            line_number,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum LoadManyError {
+    #[error("{name}: {error}")]
+    InFile {
+        name: String,
+        #[source]
+        error: LoadAllpairsError,
+    },
+    #[error(
+        "The pair ({l}, {r}) has PPM {first} on line {first_line_number} of {first_name}, but \
+         PPM {second} on line {second_line_number} of {second_name}."
+    )]
+    ConflictingDuplicate {
+        l: String,
+        r: String,
+        first: u32,
+        first_name: String,
+        first_line_number: usize,
+        second: u32,
+        second_name: String,
+        second_line_number: usize,
+    },
+    #[error(transparent)]
+    Combined(LoadAllpairsError),
 }
 
-pub fn load(file_contents: String) -> Result<PpmTable<RandomState>, LoadAllpairsError> {
+/// The most missing pairs listed in a [`LoadAllpairsError::IncompleteGraph`], which is enough to
+/// rerun the comparisons for a handful of missing submissions.
+pub const MAX_REPORTED_MISSING_PAIRS: usize = 50;
+
+impl<S: BuildHasher + Default> From<BuildError<u32, S>> for LoadAllpairsError {
+    fn from(err: BuildError<u32, S>) -> Self {
+        match err.kind() {
+            BuildErrorKind::IncompleteGraph { .. } => LoadAllpairsError::IncompleteGraph(
+                err.builder()
+                    .missing_pairs_summary(MAX_REPORTED_MISSING_PAIRS),
+            ),
+            kind => LoadAllpairsError::Build(kind),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LoadOptions {
+    /// The PPM given to pairs missing from the file. If `None`, a file that is missing pairs is
+    /// rejected with [`LoadAllpairsError::IncompleteGraph`].
+    pub default_ppm: Option<u32>,
+    /// How to handle a pair that appears more than once. Under [`DuplicatePolicy::Error`], a pair
+    /// that recurs with a different PPM is rejected with
+    /// [`LoadAllpairsError::ConflictingDuplicate`].
+    pub duplicate_policy: DuplicatePolicy,
+    /// Where each pair's PPM comes from.
+    pub ppm_source: PpmSource,
+    /// If set, only pairs with a PPM at or below `max_ppm` are kept, to save memory when only the
+    /// most similar pairs matter. Every dropped pair gets `default_ppm`, or [`MAX_PPM`] if that is
+    /// `None`, so only the PPMs at or below `max_ppm` reflect the file. Dropped pairs still count
+    /// as present, so unless `default_ppm` is set, a file that is missing pairs is rejected with
+    /// [`LoadAllpairsError::IncompleteGraph`] as usual.
+    pub max_ppm: Option<u32>,
+    /// Whether [`load_with_id_extractor`] may map different paths to the same ID, e.g. for
+    /// students with several handins, whose pairs are then treated like duplicate lines.
+    pub allow_id_collisions: bool,
+    /// Whether to put paths, or the IDs extracted from them, into Unicode Normalization Form C,
+    /// so that a name written with precomposed characters on some lines and combining characters
+    /// on others is one key. Errors for malformed lines still quote the lines as written.
+    pub normalize_unicode: bool,
+}
+
+/// How to get the PPM of a line. A PPM can be recomputed from the line's other columns as
+/// `edit_distance * 1_000_000 / max(l_len, r_len)`, rounded down, or 0 if both files are empty.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum PpmSource {
+    /// Use the PPM column as is.
+    #[default]
+    Column,
+    /// Ignore the PPM column and recompute the PPM, for files whose PPM column is known to be
+    /// wrong.
+    Recompute,
+    /// Use the PPM column, but reject a line with [`LoadAllpairsError::PpmMismatch`] if it differs
+    /// from the recomputed PPM by more than `tolerance`.
+    Verify { tolerance: u32 },
+}
+
+pub fn load(file_contents: String) -> Result<PpmTable, LoadAllpairsError> {
     load_with_hasher::<RandomState>(file_contents)
 }
 
 pub fn load_with_hasher<S: BuildHasher + Default>(
     file_contents: String,
-) -> Result<PpmTable<S>, LoadAllpairsError> {
-    let mut ppm_table_builder = PpmTableBuilder::<S>::new();
+) -> Result<PpmTable<u32, S>, LoadAllpairsError> {
+    load_with_options_and_hasher::<S>(file_contents, LoadOptions::default())
+}
+
+pub fn load_with_options(
+    file_contents: String,
+    options: LoadOptions,
+) -> Result<PpmTable, LoadAllpairsError> {
+    load_with_options_and_hasher::<RandomState>(file_contents, options)
+}
+
+pub fn load_with_options_and_hasher<S: BuildHasher + Default>(
+    file_contents: String,
+    options: LoadOptions,
+) -> Result<PpmTable<u32, S>, LoadAllpairsError> {
+    load_from_reader_with_options_and_hasher(file_contents.as_bytes(), options)
+}
+
+/// Like [`load`], but reads the file a line at a time rather than holding all of it in memory.
+pub fn load_from_reader(reader: impl BufRead) -> Result<PpmTable, LoadAllpairsError> {
+    load_from_reader_with_hasher::<RandomState>(reader)
+}
+
+pub fn load_from_reader_with_hasher<S: BuildHasher + Default>(
+    reader: impl BufRead,
+) -> Result<PpmTable<u32, S>, LoadAllpairsError> {
+    load_from_reader_with_options_and_hasher::<S>(reader, LoadOptions::default())
+}
+
+pub fn load_from_reader_with_options(
+    reader: impl BufRead,
+    options: LoadOptions,
+) -> Result<PpmTable, LoadAllpairsError> {
+    load_from_reader_with_options_and_hasher::<RandomState>(reader, options)
+}
+
+pub fn load_from_reader_with_options_and_hasher<S: BuildHasher + Default>(
+    reader: impl BufRead,
+    options: LoadOptions,
+) -> Result<PpmTable<u32, S>, LoadAllpairsError> {
+    let (builder, seen) = collect_builder::<S>(reader, options, None)?;
+    build_table(builder, seen, options)
+}
 
-    for edge in file_contents.lines().map(parse_line) {
-        match edge {
-            Ok((ppm, l, r)) => ppm_table_builder.add_ppm(l, r, ppm),
-            Err(e) => return Err(e),
+/// Like [`load`], but skips malformed lines instead of failing on them, returning the errors for
+/// the skipped lines alongside the result. Other errors, including an incomplete graph left by
+/// the skipped lines, are still fatal.
+pub fn load_lenient(
+    file_contents: String,
+) -> (Result<PpmTable, LoadAllpairsError>, Vec<LoadAllpairsError>) {
+    load_lenient_from_reader(file_contents.as_bytes(), LoadOptions::default())
+}
+
+pub fn load_lenient_from_reader(
+    reader: impl BufRead,
+    options: LoadOptions,
+) -> (Result<PpmTable, LoadAllpairsError>, Vec<LoadAllpairsError>) {
+    load_lenient_from_reader_with_hasher::<RandomState>(reader, options)
+}
+
+pub fn load_lenient_from_reader_with_hasher<S: BuildHasher + Default>(
+    reader: impl BufRead,
+    options: LoadOptions,
+) -> (
+    Result<PpmTable<u32, S>, LoadAllpairsError>,
+    Vec<LoadAllpairsError>,
+) {
+    let mut skipped = Vec::new();
+    let builder = collect_builder::<S>(reader, options, Some(&mut skipped));
+    (
+        builder.and_then(|(builder, seen)| build_table(builder, seen, options)),
+        skipped,
+    )
+}
+
+/// Adds the lines of an allpairs file to a builder, along with the pairs seen. If `skipped` is
+/// given, the errors for malformed lines are moved into it rather than returned.
+fn collect_builder<S: BuildHasher + Default>(
+    reader: impl BufRead,
+    options: LoadOptions,
+    skipped: Option<&mut Vec<LoadAllpairsError>>,
+) -> Result<(PpmTableBuilder<u32, S>, SeenPairs), LoadAllpairsError> {
+    let mut builder = PpmTableBuilder::new().with_duplicate_policy(options.duplicate_policy);
+    let mut seen = SeenPairs::new(options);
+    let mut formats = LineFormats::default();
+    let lines = map_lines(reader, |line_number, line| {
+        add_line(
+            &mut builder,
+            &mut seen,
+            line_number,
+            line,
+            options,
+            &mut formats,
+        )
+    });
+    drain_lines(lines, skipped)?;
+    Ok((builder, seen))
+}
+
+/// Runs `lines` to completion, stopping at the first error unless `skipped` is given, in which
+/// case the errors for malformed lines are moved into it.
+fn drain_lines(
+    lines: impl Iterator<Item = Result<(), LoadAllpairsError>>,
+    mut skipped: Option<&mut Vec<LoadAllpairsError>>,
+) -> Result<(), LoadAllpairsError> {
+    for line in lines {
+        match (line, skipped.as_deref_mut()) {
+            (Err(err), Some(skipped)) if err.line_number().is_some() => skipped.push(err),
+            (line, _) => line?,
         }
     }
+    Ok(())
+}
 
-    ppm_table_builder
+/// Parses one line, with its line ending already removed, and adds it to `builder`. This is the
+/// core of every loader that produces a table.
+fn add_line<S: BuildHasher + Default>(
+    builder: &mut PpmTableBuilder<u32, S>,
+    seen: &mut SeenPairs,
+    line_number: usize,
+    line: &str,
+    options: LoadOptions,
+    formats: &mut LineFormats,
+) -> Result<(), LoadAllpairsError> {
+    // The paths are added straight from the line buffer, so that each path is only allocated the
+    // first time it appears.
+    let (ppm, l, r) = parse_line(line_number, line, options.ppm_source, formats)?;
+    let (l, r) = match options.normalize_unicode {
+        true => (normalize_unicode(l), normalize_unicode(r)),
+        false => (Cow::Borrowed(l), Cow::Borrowed(r)),
+    };
+    let (l, r) = (l.as_ref(), r.as_ref());
+    seen.insert(l, r);
+    if options.max_ppm.is_some_and(|max_ppm| ppm > max_ppm) {
+        builder.add_key(l);
+        builder.add_key(r);
+        return Ok(());
+    }
+    builder
+        .try_add_ppm_interned(l, r, ppm)
+        .map_err(|err| LoadAllpairsError::conflicting_duplicate(err, line_number))
+}
+
+fn build_table<S: BuildHasher + Default>(
+    builder: PpmTableBuilder<u32, S>,
+    seen: SeenPairs,
+    options: LoadOptions,
+) -> Result<PpmTable<u32, S>, LoadAllpairsError> {
+    match (options.default_ppm, options.max_ppm) {
+        (Some(default_ppm), _) => Ok(builder.build_with_default(default_ppm)),
+        (None, Some(_)) => {
+            let missing = seen.missing_pairs_summary(&builder, MAX_REPORTED_MISSING_PAIRS);
+            if missing.total > 0 {
+                return Err(LoadAllpairsError::IncompleteGraph(missing));
+            }
+            Ok(builder.build_with_default(MAX_PPM))
+        }
+        (None, None) => Ok(builder.build()?),
+    }
+}
+
+/// Loads several allpairs files, e.g. from different terms, into one table. Each file is given as
+/// a name for errors to refer to and the file's contents. The files only need to be complete
+/// together, but a pair that appears more than once must always have the same PPM.
+pub fn load_many(
+    files: impl IntoIterator<Item = (String, String)>,
+) -> Result<PpmTable, LoadManyError> {
+    load_many_with_hasher::<RandomState>(files)
+}
+
+pub fn load_many_with_hasher<S: BuildHasher + Default>(
+    files: impl IntoIterator<Item = (String, String)>,
+) -> Result<PpmTable<u32, S>, LoadManyError> {
+    let files = files.into_iter().collect::<Vec<_>>();
+    let mut builder = PpmTableBuilder::new().with_duplicate_policy(DuplicatePolicy::Error);
+    for (k, (name, contents)) in files.iter().enumerate() {
+        for (i, edge) in parse(contents).enumerate() {
+            let (l, r, ppm) = edge.map_err(|error| LoadManyError::InFile {
+                name: name.clone(),
+                error,
+            })?;
+            if let Err(err) = builder.try_add_ppm(l, r, ppm) {
+                // Only look for where the first PPM came from once there is a conflict.
+                let (first_name, first_line_number) = files[..=k]
+                    .iter()
+                    .find_map(|(name, contents)| {
+                        let i = parse(contents).position(|edge| {
+                            edge.is_ok_and(|(l, r, _)| {
+                                (l == err.l && r == err.r) || (l == err.r && r == err.l)
+                            })
+                        })?;
+                        Some((name.clone(), i + 1))
+                    })
+                    .expect("The first PPM for the pair came from one of the files.");
+                return Err(LoadManyError::ConflictingDuplicate {
+                    l: err.l,
+                    r: err.r,
+                    first: err.first,
+                    first_name,
+                    first_line_number,
+                    second: err.second,
+                    second_name: name.clone(),
+                    second_line_number: i + 1,
+                });
+            }
+        }
+    }
+    builder
         .build()
-        .map_err(|_| LoadAllpairsError::IncompleteGraph)
+        .map_err(|err| LoadManyError::Combined(err.into()))
+}
+
+/// Parses an allpairs file without requiring it to describe a complete similarity graph, e.g.
+/// so that the results of several partial allpairs runs can be merged.
+pub fn load_builder(file_contents: String) -> Result<PpmTableBuilder, LoadAllpairsError> {
+    load_builder_with_hasher::<RandomState>(file_contents)
+}
+
+pub fn load_builder_with_hasher<S: BuildHasher + Default>(
+    file_contents: String,
+) -> Result<PpmTableBuilder<u32, S>, LoadAllpairsError> {
+    load_builder_from_reader_with_hasher(file_contents.as_bytes())
+}
+
+pub fn load_builder_from_reader_with_hasher<S: BuildHasher + Default>(
+    reader: impl BufRead,
+) -> Result<PpmTableBuilder<u32, S>, LoadAllpairsError> {
+    parse_reader(reader).collect()
+}
+
+/// Parses each line of an allpairs file into a `(l, r, ppm)` triple, without building anything
+/// from them.
+pub fn parse(
+    file_contents: &str,
+) -> impl Iterator<Item = Result<(String, String, u32), LoadAllpairsError>> + '_ {
+    let file_contents = file_contents.strip_prefix(BOM).unwrap_or(file_contents);
+    let mut formats = LineFormats::default();
+    file_contents.lines().enumerate().map(move |(i, line)| {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        parse_line(i + 1, line, PpmSource::Column, &mut formats).map(owned_edge)
+    })
+}
+
+/// Like [`parse`], but reads the lines from `reader` as they are needed, reusing one buffer.
+pub fn parse_reader(
+    reader: impl BufRead,
+) -> impl Iterator<Item = Result<(String, String, u32), LoadAllpairsError>> {
+    parse_reader_with_source(reader, PpmSource::Column)
+}
+
+/// Like [`parse_reader`], but numbers the first line read `first_line_number`, e.g. when resuming
+/// partway through a file. `first_line` holds the number of columns in the first line of the file
+/// and that line's number, which every line must match. It is set once the first line is read, so
+/// that it can be saved along with the position reached and given back on resuming.
+pub fn parse_reader_from_line<'a, R: BufRead + 'a>(
+    reader: R,
+    first_line_number: usize,
+    first_line: &'a Cell<Option<(usize, usize)>>,
+) -> impl Iterator<Item = Result<(String, String, u32), LoadAllpairsError>> + 'a {
+    map_lines_from(reader, first_line_number, move |line_number, line| {
+        let mut formats = LineFormats(first_line.get());
+        let edge = parse_line(line_number, line, PpmSource::Column, &mut formats);
+        first_line.set(formats.0);
+        edge.map(owned_edge)
+    })
+}
+
+fn parse_reader_with_source(
+    reader: impl BufRead,
+    ppm_source: PpmSource,
+) -> impl Iterator<Item = Result<(String, String, u32), LoadAllpairsError>> {
+    let mut formats = LineFormats::default();
+    map_lines(reader, move |line_number, line| {
+        parse_line(line_number, line, ppm_source, &mut formats).map(owned_edge)
+    })
+}
+
+fn owned_edge((ppm, l, r): (u32, &str, &str)) -> (String, String, u32) {
+    (l.to_string(), r.to_string(), ppm)
+}
+
+/// The byte order mark that editors on Windows may put at the start of a UTF-8 file.
+const BOM: char = '\u{feff}';
+
+/// Applies `f` to each line of `reader` and its line number, with the line ending and any byte
+/// order mark removed.
+fn map_lines<T>(
+    reader: impl BufRead,
+    f: impl FnMut(usize, &str) -> Result<T, LoadAllpairsError>,
+) -> impl Iterator<Item = Result<T, LoadAllpairsError>> {
+    map_lines_from(reader, 1, f)
+}
+
+fn map_lines_from<T>(
+    mut reader: impl BufRead,
+    first_line_number: usize,
+    mut f: impl FnMut(usize, &str) -> Result<T, LoadAllpairsError>,
+) -> impl Iterator<Item = Result<T, LoadAllpairsError>> {
+    let mut line = String::new();
+    let mut line_number = first_line_number.saturating_sub(1);
+    std::iter::from_fn(move || {
+        line.clear();
+        line_number += 1;
+        match reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(f(line_number, trim_line(line_number, &line))),
+            Err(err) => Some(Err(LoadAllpairsError::Io(err.to_string()))),
+        }
+    })
+}
+
+/// Puts `key` into Unicode Normalization Form C, only allocating if it is not already normalized.
+pub fn normalize_unicode(key: &str) -> Cow<'_, str> {
+    if is_nfc(key) {
+        Cow::Borrowed(key)
+    } else {
+        Cow::Owned(key.nfc().collect())
+    }
+}
+
+/// Removes the line ending from a line read with its line ending, and the byte order mark if it is
+/// the first line.
+fn trim_line(line_number: usize, line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    match line_number {
+        1 => line.strip_prefix(BOM).unwrap_or(line),
+        _ => line,
+    }
+}
+
+/// Splits the two paths at the end of a line. If they are the only two words, they are the paths.
+/// Otherwise, a path contains a space, and they are split at the one space where both halves end
+/// in the same file name at the same depth, e.g. `a2/Smith, John/a2.py a2/002/a2.py`. If no space
+/// or more than one space qualifies, the line is ambiguous.
+fn split_paths(paths: &str) -> Option<(&str, &str)> {
+    let mut words = paths.split_whitespace();
+    if let (Some(l), Some(r), None) = (words.next(), words.next(), words.next()) {
+        return Some((l, r));
+    }
+
+    fn shape(path: &str) -> (Option<&str>, usize) {
+        (path.rsplit('/').next(), path.matches('/').count())
+    }
+    let mut splits = paths
+        .match_indices(' ')
+        .map(|(i, _)| (&paths[..i], &paths[i + 1..]))
+        .filter(|&(l, r)| shape(l) == shape(r));
+    match (splits.next(), splits.next()) {
+        (Some(split), None) => Some(split),
+        _ => None,
+    }
+}
+
+/// Parses the line at the 1-based `line_number`, which is only used for errors. `formats` holds
+/// the number of columns in the first line of the file, which every other line must match.
+fn parse_line<'a>(
+    line_number: usize,
+    line: &'a str,
+    ppm_source: PpmSource,
+    formats: &mut LineFormats,
+) -> Result<(u32, &'a str, &'a str), LoadAllpairsError> {
+    let (columns, l, r) = split_line(line_number, line)?;
+    formats.check(line_number, columns.count())?;
+    let recompute = || {
+        let invalid_line = || LoadAllpairsError::InvalidLine {
+            line_number,
+            line: line.to_string(),
+        };
+        let (edit_distance, (l_len, r_len)) = columns
+            .edit_distance
+            .zip(columns.lengths)
+            .ok_or_else(invalid_line)?;
+        recompute_ppm(edit_distance, l_len, r_len).ok_or_else(invalid_line)
+    };
+    let ppm = match ppm_source {
+        PpmSource::Column => parse_ppm(line_number, columns.ppm)?,
+        PpmSource::Recompute => recompute()?,
+        PpmSource::Verify { tolerance } => {
+            let ppm = parse_ppm(line_number, columns.ppm)?;
+            let recomputed = recompute()?;
+            if ppm.abs_diff(recomputed) > tolerance {
+                return Err(LoadAllpairsError::PpmMismatch {
+                    line_number,
+                    ppm,
+                    recomputed,
+                });
+            }
+            ppm
+        }
+    };
+    Ok((ppm, l, r))
+}
+
+/// Recomputes a PPM as described on [`PpmSource`], or returns `None` if a column is not a number.
+fn recompute_ppm(edit_distance: &str, l_len: &str, r_len: &str) -> Option<u32> {
+    ppm_from_edit_distance(
+        edit_distance.parse().ok()?,
+        l_len.parse().ok()?,
+        r_len.parse().ok()?,
+    )
+}
+
+/// The PPM of a pair of files, as lexscan computes it: the edit distance per million bytes of
+/// the longer file, rounded down, or 0 if both are empty. Returns `None` if the PPM does not fit
+/// in a `u32`.
+fn ppm_from_edit_distance(edit_distance: u64, l_len: u64, r_len: u64) -> Option<u32> {
+    let max_len = l_len.max(r_len);
+    if max_len == 0 {
+        return Some(0);
+    }
+    (edit_distance * 1_000_000 / max_len).try_into().ok()
+}
+
+/// The unparsed columns before the paths of a line. Lines from lexscan have all of them, but some
+/// older builds leave out the lengths, and others the edit distance as well.
+#[derive(Clone, Copy, Debug)]
+struct Columns<'a> {
+    ppm: &'a str,
+    edit_distance: Option<&'a str>,
+    lengths: Option<(&'a str, &'a str)>,
+}
+
+impl Columns<'_> {
+    /// The number of columns in the line, counting the two paths.
+    fn count(&self) -> usize {
+        3 + self.edit_distance.map_or(0, |_| 1) + self.lengths.map_or(0, |_| 2)
+    }
+}
+
+/// The number of columns in the first line of a file, and that line's number.
+#[derive(Clone, Copy, Debug, Default)]
+struct LineFormats(Option<(usize, usize)>);
+
+impl LineFormats {
+    fn check(&mut self, line_number: usize, columns: usize) -> Result<(), LoadAllpairsError> {
+        match self.0 {
+            Some((first_columns, first_line_number)) if first_columns != columns => {
+                Err(LoadAllpairsError::MixedLineFormats {
+                    line_number,
+                    columns,
+                    first_line_number,
+                    first_columns,
+                })
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.0 = Some((columns, line_number));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The pairs seen while loading with [`LoadOptions::max_ppm`] but no `default_ppm`, whether they
+/// were kept or dropped, so that the builder, which only has the kept pairs, can still be checked
+/// for completeness. Keys are numbered as they are first seen, and each pair is one bit, which is
+/// far less than the PPMs that were dropped.
+#[derive(Clone, Debug, Default)]
+struct SeenPairs {
+    tracked: bool,
+    indices: HashMap<String, usize>,
+    bits: Vec<u64>,
+    len: usize,
 }
 
-fn parse_line(line: &str) -> Result<(u32, String, String), LoadAllpairsError> {
-    let generate_error = || LoadAllpairsError::InvalidLine(line.to_string());
+impl SeenPairs {
+    fn new(options: LoadOptions) -> Self {
+        SeenPairs {
+            tracked: options.max_ppm.is_some() && options.default_ppm.is_none(),
+            ..SeenPairs::default()
+        }
+    }
+
+    fn insert(&mut self, l: &str, r: &str) {
+        if !self.tracked || l == r {
+            return;
+        }
+        let bit = Self::bit(self.index(l), self.index(r));
+        if bit / 64 >= self.bits.len() {
+            self.bits.resize(bit / 64 + 1, 0);
+        }
+        let word = &mut self.bits[bit / 64];
+        if *word & 1 << (bit % 64) == 0 {
+            *word |= 1 << (bit % 64);
+            self.len += 1;
+        }
+    }
+
+    fn contains(&self, l: &str, r: &str) -> bool {
+        match (self.indices.get(l), self.indices.get(r)) {
+            (Some(&l), Some(&r)) => {
+                let bit = Self::bit(l, r);
+                self.bits
+                    .get(bit / 64)
+                    .is_some_and(|word| word & 1 << (bit % 64) != 0)
+            }
+            _ => false,
+        }
+    }
+
+    fn index(&mut self, key: &str) -> usize {
+        let next = self.indices.len();
+        match self.indices.get(key) {
+            Some(&index) => index,
+            None => *self.indices.entry(key.to_string()).or_insert(next),
+        }
+    }
+
+    /// The position of the pair in the lower triangle of the matrix of key numbers.
+    fn bit(l: usize, r: usize) -> usize {
+        let (l, r) = if l < r { (l, r) } else { (r, l) };
+        r * (r - 1) / 2 + l
+    }
+
+    /// Counts the pairs of the builder's keys that were never seen, like
+    /// [`PpmTableBuilder::missing_pairs_summary`].
+    fn missing_pairs_summary<S: BuildHasher + Default>(
+        &self,
+        builder: &PpmTableBuilder<u32, S>,
+        limit: usize,
+    ) -> MissingPairs {
+        let n = builder.num_keys();
+        let mut summary = MissingPairs::default();
+        if self.len == n * n.saturating_sub(1) / 2 {
+            return summary;
+        }
+        summary.total = n * n.saturating_sub(1) / 2 - self.len;
+        let mut keys = builder.keys().collect::<Vec<_>>();
+        keys.sort_unstable();
+        for (i, &l) in keys.iter().enumerate() {
+            for &r in &keys[i + 1..] {
+                if summary.sample.len() == limit {
+                    return summary;
+                }
+                if !self.contains(l, r) {
+                    summary.sample.push((l.to_string(), r.to_string()));
+                }
+            }
+        }
+        summary
+    }
+}
 
-    let mut columns = line.split_whitespace();
+/// Splits a line into its columns, unparsed, and its two paths. The PPM always comes first, and
+/// the numbers after it are the other columns, as long as two words remain for the paths.
+fn split_line(
+    line_number: usize,
+    line: &str,
+) -> Result<(Columns<'_>, &str, &str), LoadAllpairsError> {
+    let generate_error = || LoadAllpairsError::InvalidLine {
+        line_number,
+        line: line.to_string(),
+    };
 
-    let ppm_str = columns.next().ok_or_else(generate_error)?;
-    let _edit_distance = columns.next().ok_or_else(generate_error)?;
-    let _l_len = columns.next().ok_or_else(generate_error)?;
-    let _r_len = columns.next().ok_or_else(generate_error)?;
-    let l = columns.next().ok_or_else(generate_error)?;
-    let r = columns.next().ok_or_else(generate_error)?;
-    if columns.next().is_some() {
-        return Err(LoadAllpairsError::InvalidLine(line.to_string()));
+    // The numeric columns cannot contain spaces, but the paths after them can.
+    fn next_column(rest: &str) -> Option<(&str, &str)> {
+        let rest = rest.trim_start();
+        rest.find(char::is_whitespace).map(|end| rest.split_at(end))
+    }
+    let (ppm, mut rest) = next_column(line).ok_or_else(generate_error)?;
+    let mut numbers = [""; 3];
+    let mut count = 0;
+    while count < numbers.len() && rest.split_whitespace().nth(2).is_some() {
+        let (column, after) = next_column(rest).ok_or_else(generate_error)?;
+        if !column.bytes().all(|byte| byte.is_ascii_digit()) {
+            break;
+        }
+        numbers[count] = column;
+        count += 1;
+        rest = after;
+    }
+    let columns = match numbers[..count] {
+        [] => Columns {
+            ppm,
+            edit_distance: None,
+            lengths: None,
+        },
+        [edit_distance] => Columns {
+            ppm,
+            edit_distance: Some(edit_distance),
+            lengths: None,
+        },
+        [edit_distance, l_len, r_len] => Columns {
+            ppm,
+            edit_distance: Some(edit_distance),
+            lengths: Some((l_len, r_len)),
+        },
+        _ => return Err(generate_error()),
+    };
+    let (l, r) = split_paths(rest.trim()).ok_or_else(generate_error)?;
+    if l == r {
+        return Err(LoadAllpairsError::SelfComparison {
+            line_number,
+            path: l.to_string(),
+        });
     }
+    Ok((columns, l, r))
+}
 
+fn parse_ppm(line_number: usize, ppm_str: &str) -> Result<u32, LoadAllpairsError> {
     let ppm = ppm_str
         .parse()
-        .map_err(|_| LoadAllpairsError::PpmCaptureFail(ppm_str.to_string()))?;
+        .map_err(|_| LoadAllpairsError::PpmCaptureFail {
+            line_number,
+            ppm: ppm_str.to_string(),
+        })?;
+    if ppm > MAX_PPM {
+        return Err(LoadAllpairsError::PpmOutOfRange {
+            value: ppm,
+            line_number,
+        });
+    }
+    Ok(ppm)
+}
+
+/// Writes `table` in the allpairs format, with the numeric columns right-aligned as lexscan does.
+/// The table does not record edit distances or file lengths, so they are written as zeros.
+pub fn write<S: BuildHasher + Default>(
+    table: &PpmTable<u32, S>,
+    writer: impl Write,
+) -> io::Result<()> {
+    write_with_metadata(table, writer, |_, _| (0, 0, 0))
+}
 
-    Ok((ppm, l.to_string(), r.to_string()))
+/// Like [`write`], but takes the edit distance and the lengths of the two files for each pair
+/// from `metadata`.
+pub fn write_with_metadata<S: BuildHasher + Default>(
+    table: &PpmTable<u32, S>,
+    mut writer: impl Write,
+    metadata: impl Fn(&str, &str) -> (u32, u32, u32),
+) -> io::Result<()> {
+    for (l, r, ppm) in table.edges() {
+        let (edit_distance, l_len, r_len) = metadata(l, r);
+        writeln!(
+            writer,
+            "{:6} {:6} {:6} {:6} {} {}",
+            ppm, edit_distance, l_len, r_len, l, r
+        )?;
+    }
+    writer.flush()
 }
 
 #[cfg(test)]
@@ -85,7 +885,7 @@ mod tests {
             "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
         )
         .to_string();
-        let ppm_table = load(file_contents).expect("File should be valid.");
+        let ppm_table = load(file_contents.clone()).expect("File should be valid.");
         assert_eq!(
             ppm_table[("a2-anonymous/001/a2.py", "a2-anonymous/002/a2.py")],
             2191
@@ -98,6 +898,147 @@ mod tests {
             ppm_table[("a2-anonymous/002/a2.py", "a2-anonymous/003/a2.py")],
             2232
         );
+
+        // Loading shares each path between its pairs, but builds the same table as adding the
+        // parsed pairs one by one.
+        let builder: Result<PpmTableBuilder, _> = parse(&file_contents).collect();
+        assert_eq!(builder.unwrap().build().unwrap(), ppm_table);
+    }
+
+    #[test]
+    fn test_load_allpairs_from_reader() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\r\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py",
+        );
+        let ppm_table = load_from_reader(file_contents.as_bytes()).expect("File should be valid.");
+        assert_eq!(
+            ppm_table,
+            load(file_contents.to_string()).expect("File should be valid.")
+        );
+        assert_eq!(
+            ppm_table[("a2-anonymous/002/a2.py", "a2-anonymous/003/a2.py")],
+            2232
+        );
+
+        let err = load_from_reader(&[0xff, b'\n'][..]).expect_err("File should not be UTF-8.");
+        assert!(matches!(err, LoadAllpairsError::Io(_)));
+    }
+
+    #[test]
+    fn test_load_allpairs_crlf_and_bom() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        );
+        let ppm_table = load(file_contents.to_string()).expect("File should be valid.");
+
+        let windows = format!("{}{}", BOM, file_contents.replace('\n', "\r\n"));
+        assert_eq!(load(windows.clone()), Ok(ppm_table.clone()));
+        assert_eq!(load_from_reader(windows.as_bytes()), Ok(ppm_table.clone()));
+        let edges = parse(&windows).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(edges[1].1, "a2-anonymous/003/a2.py");
+
+        // Unterminated carriage returns are line endings too.
+        let last_cr = windows.trim_end_matches('\n');
+        assert_eq!(load(last_cr.to_string()), Ok(ppm_table.clone()));
+        assert_eq!(load_from_reader(last_cr.as_bytes()), Ok(ppm_table));
+    }
+
+    #[test]
+    fn test_write_allpairs() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        );
+        let ppm_table = load(file_contents.to_string()).expect("File should be valid.");
+
+        let mut written = Vec::new();
+        write(&ppm_table, &mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(
+            written.lines().next(),
+            Some("  2191      0      0      0 a2-anonymous/001/a2.py a2-anonymous/002/a2.py")
+        );
+        assert_eq!(load(written), Ok(ppm_table.clone()));
+
+        let mut written = Vec::new();
+        write_with_metadata(&ppm_table, &mut written, |l, r| {
+            let len = |path: &str| if path.contains("001") { 5260 } else { 5236 };
+            (23, len(l), len(r))
+        })
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap().lines().next(),
+            file_contents.lines().next()
+        );
+    }
+
+    #[test]
+    fn test_load_many() {
+        let this_term = (
+            "this".to_string(),
+            "  2191     23   5260   5236 a2/001/a2.py a2/002/a2.py\n".to_string(),
+        );
+        let across_terms = (
+            "across".to_string(),
+            concat!(
+                "  2155     49   5260   5000 a2/001/a2.py old/003/a2.py\n",
+                "  2232     12   5236   5000 a2/002/a2.py old/003/a2.py\n",
+            )
+            .to_string(),
+        );
+        assert!(load(this_term.1.clone()).is_ok());
+        assert!(load(across_terms.1.clone()).is_err());
+
+        let ppm_table = load_many([this_term.clone(), across_terms.clone()])
+            .expect("Files should be complete together.");
+        assert_eq!(ppm_table.len(), 3);
+        assert_eq!(ppm_table[("a2/001/a2.py", "a2/002/a2.py")], 2191);
+        assert_eq!(ppm_table[("a2/002/a2.py", "old/003/a2.py")], 2232);
+
+        assert!(matches!(
+            load_many([this_term.clone()]),
+            Ok(ppm_table) if ppm_table.len() == 2
+        ));
+        assert!(matches!(
+            load_many([across_terms.clone()]),
+            Err(LoadManyError::Combined(LoadAllpairsError::IncompleteGraph(
+                _
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_load_many_errors() {
+        let first = (
+            "first".to_string(),
+            "  2191     23   5260   5236 a2/001/a2.py a2/002/a2.py\n".to_string(),
+        );
+        let conflicting = (
+            "second".to_string(),
+            concat!(
+                "  2155     49   5260   5000 a2/001/a2.py a2/003/a2.py\n",
+                "  2192     23   5236   5260 a2/002/a2.py a2/001/a2.py\n",
+            )
+            .to_string(),
+        );
+        let err = load_many([first.clone(), conflicting]).expect_err("PPMs should conflict.");
+        assert_eq!(
+            err.to_string(),
+            "The pair (a2/001/a2.py, a2/002/a2.py) has PPM 2191 on line 1 of first, but PPM 2192 \
+             on line 2 of second."
+        );
+
+        let malformed = ("malformed".to_string(), "\n  2191\n".to_string());
+        let err = load_many([first, malformed]).expect_err("Line 1 should be malformed.");
+        assert_eq!(
+            err.to_string(),
+            "malformed: Line 1 of the file was not a valid allpairs entry."
+        );
     }
 
     #[test]
@@ -110,13 +1051,173 @@ mod tests {
         let err = load(file_contents).expect_err("Line 2 should be malformed.");
         assert_eq!(
             err,
-            LoadAllpairsError::InvalidLine(
-                "  2191     23   5260   abcda2-anonymous/003/a2.py a2-anonymous/002/a2.py"
+            LoadAllpairsError::InvalidLine {
+                line_number: 2,
+                line: "  2191     23   5260   abcda2-anonymous/003/a2.py a2-anonymous/002/a2.py"
                     .to_string()
-            )
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_reader_from_line() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2191     23   5260   abcda2-anonymous/003/a2.py a2-anonymous/002/a2.py\n",
+        );
+        let first_line = Cell::new(None);
+        let errors = parse_reader_from_line(file_contents.as_bytes(), 41, &first_line)
+            .filter_map(|edge| edge.err()?.line_number())
+            .collect::<Vec<_>>();
+        assert_eq!(errors, [42]);
+        assert_eq!(first_line.get(), Some((6, 41)));
+
+        // Resuming holds the lines to the format of the first line, which was read before.
+        let short_line = "  2191 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n";
+        let first_line = Cell::new(Some((6, 1)));
+        assert_eq!(
+            parse_reader_from_line(short_line.as_bytes(), 43, &first_line).collect::<Vec<_>>(),
+            [Err(LoadAllpairsError::MixedLineFormats {
+                line_number: 43,
+                columns: 3,
+                first_line_number: 1,
+                first_columns: 6,
+            })]
+        );
+    }
+
+    #[test]
+    fn test_load_allpairs_invalid_last_line() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "  2232     12   5236   5000 a2-anonymous/002/a2.py\n",
+        );
+        let err = load(file_contents.to_string()).expect_err("Line 3 should be malformed.");
+        assert_eq!(
+            err,
+            LoadAllpairsError::InvalidLine {
+                line_number: 3,
+                line: "  2232     12   5236   5000 a2-anonymous/002/a2.py".to_string()
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Line 3 of the file was not a valid allpairs entry."
+        );
+        assert_eq!(load_from_reader(file_contents.as_bytes()), Err(err));
+    }
+
+    #[test]
+    fn test_load_allpairs_lenient() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+            "  2232     12   5236   5000 a2-anonym",
+        );
+        assert!(load(file_contents.to_string()).is_err());
+
+        let (ppm_table, skipped) = load_lenient(file_contents.to_string());
+        let ppm_table = ppm_table.expect("Malformed lines should be skipped.");
+        assert_eq!(ppm_table.num_edges(), 3);
+        assert_eq!(
+            skipped,
+            [LoadAllpairsError::InvalidLine {
+                line_number: 4,
+                line: "  2232     12   5236   5000 a2-anonym".to_string()
+            }]
+        );
+
+        // Skipping a line can leave the graph incomplete, which is still an error.
+        let (ppm_table, skipped) = load_lenient(file_contents.replacen("2155", "x", 1));
+        assert!(matches!(
+            ppm_table,
+            Err(LoadAllpairsError::IncompleteGraph(_))
+        ));
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|err| err.line_number())
+                .collect::<Vec<_>>(),
+            [Some(2), Some(4)]
         );
     }
 
+    #[test]
+    fn test_load_allpairs_conflicting_duplicate() {
+        let run = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        );
+        let options = LoadOptions {
+            duplicate_policy: DuplicatePolicy::Error,
+            ..LoadOptions::default()
+        };
+
+        // Two copies of the same run agree on every pair.
+        let concatenated = run.repeat(2);
+        let ppm_table = load_with_options(concatenated.clone(), options)
+            .expect("Exact duplicates should be accepted.");
+        assert_eq!(ppm_table, load(run.to_string()).unwrap());
+
+        let conflicting = concatenated
+            .replacen("2155", "2156", 2)
+            .replacen("2156", "2155", 1);
+        let err = load_with_options(conflicting.clone(), options)
+            .expect_err("Line 5 should conflict with line 2.");
+        assert_eq!(
+            err,
+            LoadAllpairsError::ConflictingDuplicate {
+                l: "a2-anonymous/001/a2.py".to_string(),
+                r: "a2-anonymous/003/a2.py".to_string(),
+                first: 2155,
+                second: 2156,
+                line_number: 5,
+            }
+        );
+
+        // By default, the last PPM wins.
+        let ppm_table = load(conflicting).expect("Duplicates should be overwritten.");
+        assert_eq!(
+            ppm_table[("a2-anonymous/001/a2.py", "a2-anonymous/003/a2.py")],
+            2156
+        );
+    }
+
+    #[test]
+    fn test_parse_paths_with_spaces() {
+        let parse_paths = |paths: &str| {
+            let line = format!("  2191     23   5260   5236 {}", paths);
+            let edge = parse(&line).next().unwrap();
+            edge.map(|(l, r, _)| (l, r))
+                .map_err(|err| err.line_number())
+        };
+        let pair = |l: &str, r: &str| Ok((l.to_string(), r.to_string()));
+
+        assert_eq!(
+            parse_paths("a2/Smith, John/a2.py a2/002/a2.py"),
+            pair("a2/Smith, John/a2.py", "a2/002/a2.py")
+        );
+        assert_eq!(
+            parse_paths("a2/001/a2.py a2/Smith, John/a2.py"),
+            pair("a2/001/a2.py", "a2/Smith, John/a2.py")
+        );
+        assert_eq!(
+            parse_paths("a2/Smith, John/a2.py a2/Doe, Jane Q/a2.py"),
+            pair("a2/Smith, John/a2.py", "a2/Doe, Jane Q/a2.py")
+        );
+        // Without a space in either path, the file names may differ.
+        assert_eq!(
+            parse_paths("a2/001/a2.py a2/002/main.py"),
+            pair("a2/001/a2.py", "a2/002/main.py")
+        );
+        // Either space could separate the paths.
+        assert_eq!(parse_paths("a2/x a2/x a2/x"), Err(Some(1)));
+        assert_eq!(parse_paths("a2/001/a2.py"), Err(Some(1)));
+    }
+
     #[test]
     fn test_load_allpairs_ppm_parse_error() {
         let usize_max_plus_one = "18446744073709551616";
@@ -127,10 +1228,244 @@ mod tests {
         let err = load(file_contents).expect_err("Parsing of overly long usize should fail.");
         assert_eq!(
             err,
-            LoadAllpairsError::PpmCaptureFail(usize_max_plus_one.to_string())
+            LoadAllpairsError::PpmCaptureFail {
+                line_number: 1,
+                ppm: usize_max_plus_one.to_string()
+            }
         );
     }
 
+    #[test]
+    fn test_load_allpairs_ppm_source() {
+        let file_contents = concat!(
+            "  4372     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  9315     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "  1000     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        )
+        .to_string();
+        let load_from = |ppm_source| {
+            let options = LoadOptions {
+                ppm_source,
+                ..LoadOptions::default()
+            };
+            load_with_options(file_contents.clone(), options)
+        };
+
+        let column = load_from(PpmSource::Column).expect("File should be valid.");
+        assert_eq!(
+            column[("a2-anonymous/002/a2.py", "a2-anonymous/003/a2.py")],
+            1000
+        );
+
+        let recomputed = load_from(PpmSource::Recompute).expect("File should be valid.");
+        assert_eq!(
+            recomputed[("a2-anonymous/001/a2.py", "a2-anonymous/002/a2.py")],
+            4372
+        );
+        assert_eq!(
+            recomputed[("a2-anonymous/002/a2.py", "a2-anonymous/003/a2.py")],
+            2291
+        );
+
+        assert_eq!(
+            load_from(PpmSource::Verify { tolerance: 1 }),
+            Err(LoadAllpairsError::PpmMismatch {
+                line_number: 3,
+                ppm: 1000,
+                recomputed: 2291,
+            })
+        );
+        assert_eq!(load_from(PpmSource::Verify { tolerance: 1291 }), Ok(column));
+    }
+
+    #[test]
+    fn test_load_allpairs_ppm_out_of_range() {
+        let file_contents =
+            "1000000     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n";
+        let ppm_table = load(file_contents.to_string()).expect("File should be valid.");
+        assert_eq!(
+            ppm_table[("a2-anonymous/001/a2.py", "a2-anonymous/002/a2.py")],
+            MAX_PPM
+        );
+
+        let file_contents = file_contents.replace("1000000", "1000001");
+        assert_eq!(
+            load(file_contents),
+            Err(LoadAllpairsError::PpmOutOfRange {
+                value: 1_000_001,
+                line_number: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_allpairs_self_comparison() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "     0      0   5260   5260 a2-anonymous/001/a2.py a2-anonymous/001/a2.py\n",
+        );
+        assert_eq!(
+            load(file_contents.to_string()),
+            Err(LoadAllpairsError::SelfComparison {
+                line_number: 2,
+                path: "a2-anonymous/001/a2.py".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_allpairs_max_ppm() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            " 92232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+            " 91000     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        );
+        let options = LoadOptions {
+            max_ppm: Some(10_000),
+            ..LoadOptions::default()
+        };
+        let ppm_table = load_with_options(file_contents.to_string(), options)
+            .expect("Dropped pairs should count towards completeness.");
+        assert_eq!(ppm_table.len(), 3);
+        assert_eq!(
+            ppm_table.edges_below(10_000).collect::<Vec<_>>(),
+            vec![
+                ("a2-anonymous/001/a2.py", "a2-anonymous/002/a2.py", 2191),
+                ("a2-anonymous/001/a2.py", "a2-anonymous/003/a2.py", 2155),
+            ]
+        );
+        assert_eq!(
+            ppm_table[("a2-anonymous/002/a2.py", "a2-anonymous/003/a2.py")],
+            MAX_PPM
+        );
+
+        // The dropped pair appears twice, which must not make up for the missing pair.
+        let truncated = file_contents
+            .lines()
+            .filter(|line| !line.contains("2155"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            load_with_options(truncated.clone(), options),
+            Err(LoadAllpairsError::IncompleteGraph(MissingPairs {
+                sample: vec![(
+                    "a2-anonymous/001/a2.py".to_string(),
+                    "a2-anonymous/003/a2.py".to_string()
+                )],
+                total: 1,
+            }))
+        );
+
+        let options = LoadOptions {
+            default_ppm: Some(500_000),
+            ..options
+        };
+        let ppm_table = load_with_options(truncated, options)
+            .expect("Incomplete files should be accepted with a default PPM.");
+        assert_eq!(
+            ppm_table[("a2-anonymous/001/a2.py", "a2-anonymous/003/a2.py")],
+            500_000
+        );
+    }
+
+    #[test]
+    fn test_load_allpairs_short_formats() {
+        let full = load(
+            concat!(
+                "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+                "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+                "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+            )
+            .to_string(),
+        )
+        .expect("File should be valid.");
+        let edit_distance = concat!(
+            "2191 23 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "2155 49 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "2232 12 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        );
+        assert_eq!(load(edit_distance.to_string()), Ok(full.clone()));
+        let short = concat!(
+            "2191 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "2155 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "2232 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        );
+        assert_eq!(load(short.to_string()), Ok(full));
+
+        // Paths that are numbers are still paths when they are the last two words.
+        let ppm_table = load("2191 001 002\n".to_string()).expect("File should be valid.");
+        assert_eq!(ppm_table[("001", "002")], 2191);
+
+        let five_columns = "2191 23 5260 a2-anonymous/001/a2.py a2-anonymous/002/a2.py";
+        assert_eq!(
+            load(five_columns.to_string()),
+            Err(LoadAllpairsError::InvalidLine {
+                line_number: 1,
+                line: five_columns.to_string()
+            })
+        );
+
+        let mixed = concat!(
+            "2191 23 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "2155 49 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\n",
+            "2232 a2-anonymous/002/a2.py a2-anonymous/003/a2.py\n",
+        );
+        let err = load(mixed.to_string()).expect_err("Mixed formats should be rejected.");
+        assert_eq!(
+            err,
+            LoadAllpairsError::MixedLineFormats {
+                line_number: 3,
+                columns: 3,
+                first_line_number: 1,
+                first_columns: 4
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Line 3 of the file has 3 columns, but line 1 has 4."
+        );
+
+        // The PPM can only be recomputed from lines with every column.
+        let options = LoadOptions {
+            ppm_source: PpmSource::Recompute,
+            ..LoadOptions::default()
+        };
+        assert!(matches!(
+            load_with_options(short.to_string(), options),
+            Err(LoadAllpairsError::InvalidLine { line_number: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_allpairs_normalize_unicode() {
+        // The same accented name, precomposed and with a combining accent.
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2/Ren\u{e9}/a2.py a2/002/a2.py\n",
+            "  2155     49   5260   5000 a2/Rene\u{301}/a2.py a2/003/a2.py\n",
+            "  2232     12   5236   5000 a2/002/a2.py a2/003/a2.py\n",
+        );
+        let err = load(file_contents.to_string()).expect_err("The names should differ.");
+        assert!(matches!(err, LoadAllpairsError::IncompleteGraph(_)));
+
+        let options = LoadOptions {
+            normalize_unicode: true,
+            ..LoadOptions::default()
+        };
+        let ppm_table = load_with_options(file_contents.to_string(), options)
+            .expect("The names should be normalized to one key.");
+        assert_eq!(ppm_table.len(), 3);
+        assert_eq!(ppm_table[("a2/Ren\u{e9}/a2.py", "a2/003/a2.py")], 2155);
+        assert_eq!(
+            normalize_unicode("a2/Rene\u{301}/a2.py"),
+            "a2/Ren\u{e9}/a2.py"
+        );
+        assert!(matches!(
+            normalize_unicode("a2/001/a2.py"),
+            Cow::Borrowed(_)
+        ));
+    }
+
     #[test]
     fn test_load_allpairs_incomplete_graph() {
         let file_contents = concat!(
@@ -139,6 +1474,72 @@ mod tests {
         )
         .to_string();
         let err = load(file_contents).expect_err("Parsing of incomplete graph should fail.");
-        assert_eq!(err, LoadAllpairsError::IncompleteGraph);
+        assert_eq!(
+            err,
+            LoadAllpairsError::IncompleteGraph(MissingPairs {
+                sample: vec![(
+                    "a2-anonymous/001/a2.py".to_string(),
+                    "a2-anonymous/003/a2.py".to_string()
+                )],
+                total: 1
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "The provided allpairs file does not correspond to a complete similarity graph: \
+             missing 1 pairs, e.g. (a2-anonymous/001/a2.py, a2-anonymous/003/a2.py)."
+        );
+
+        // Only pairs with the first key are present, so every other pair is missing.
+        let file_contents = (1..20)
+            .map(|i| format!("1 0 1 1 a/00/a.py a/{:02}/a.py\n", i))
+            .collect::<String>();
+        let Err(LoadAllpairsError::IncompleteGraph(missing)) = load(file_contents) else {
+            panic!("Parsing of incomplete graph should fail.");
+        };
+        assert_eq!(missing.total, 19 * 18 / 2);
+        assert_eq!(missing.sample.len(), MAX_REPORTED_MISSING_PAIRS);
+    }
+
+    #[test]
+    fn test_load_allpairs_sparse() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2191     23   5260   5236 a2-anonymous/003/a2.py a2-anonymous/002/a2.py\n",
+        )
+        .to_string();
+        let options = LoadOptions {
+            default_ppm: Some(1_000_000),
+            ..LoadOptions::default()
+        };
+        let ppm_table = load_with_options(file_contents, options).expect("File should be valid.");
+        assert_eq!(
+            ppm_table[("a2-anonymous/001/a2.py", "a2-anonymous/003/a2.py")],
+            1_000_000
+        );
+        assert_eq!(
+            ppm_table[("a2-anonymous/002/a2.py", "a2-anonymous/003/a2.py")],
+            2191
+        );
+    }
+
+    #[test]
+    fn test_load_builder_incomplete_graph() {
+        let file_contents = concat!(
+            "  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+            "  2191     23   5260   5236 a2-anonymous/003/a2.py a2-anonymous/002/a2.py\n",
+        )
+        .to_string();
+        let mut builder = load_builder(file_contents).expect("File should be valid.");
+        builder.add_ppm(
+            "a2-anonymous/001/a2.py".to_string(),
+            "a2-anonymous/003/a2.py".to_string(),
+            2155,
+        );
+        let ppm_table = builder.build().expect("Graph should now be complete.");
+        assert_eq!(
+            ppm_table[("a2-anonymous/001/a2.py", "a2-anonymous/003/a2.py")],
+            2155
+        );
     }
 }
@@ -0,0 +1,215 @@
+use std::fs::File;
+use std::hash::{BuildHasher, RandomState};
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+use ppm_table::{PpmTable, PpmTableBuilder};
+
+use crate::{
+    add_line, build_table, drain_lines, trim_line, LineFormats, LoadAllpairsError, LoadOptions,
+    SeenPairs,
+};
+
+/// A file mapped into memory, which reads as a byte slice without copying it through a buffer.
+#[derive(Debug)]
+pub struct MappedFile(Mmap);
+
+impl MappedFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: The map is only read, and allpairs files are not modified while being loaded.
+        // If one is, the loaded table may be wrong, but reads never go out of bounds.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(MappedFile(map))
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Like [`load_from_reader`](crate::load_from_reader), but maps the file at `path` into memory
+/// and splits it into lines in place, which is faster for large files. Gzipped files are read
+/// through `MaybeGzReader` if the `gzip` feature is enabled.
+pub fn load_from_path(path: &Path) -> Result<PpmTable, LoadAllpairsError> {
+    load_from_path_with_options(path, LoadOptions::default())
+}
+
+pub fn load_from_path_with_options(
+    path: &Path,
+    options: LoadOptions,
+) -> Result<PpmTable, LoadAllpairsError> {
+    load_from_path_with_options_and_hasher::<RandomState>(path, options)
+}
+
+pub fn load_from_path_with_options_and_hasher<S: BuildHasher + Default>(
+    path: &Path,
+    options: LoadOptions,
+) -> Result<PpmTable<u32, S>, LoadAllpairsError> {
+    let (builder, seen) = collect_builder_from_path::<S>(path, options, None)?;
+    build_table(builder, seen, options)
+}
+
+/// Like [`load_from_path_with_options`], but skips malformed lines as
+/// [`load_lenient`](crate::load_lenient) does.
+pub fn load_lenient_from_path(
+    path: &Path,
+    options: LoadOptions,
+) -> (Result<PpmTable, LoadAllpairsError>, Vec<LoadAllpairsError>) {
+    let mut skipped = Vec::new();
+    let builder = collect_builder_from_path::<RandomState>(path, options, Some(&mut skipped));
+    (
+        builder.and_then(|(builder, seen)| build_table(builder, seen, options)),
+        skipped,
+    )
+}
+
+fn collect_builder_from_path<S: BuildHasher + Default>(
+    path: &Path,
+    options: LoadOptions,
+    skipped: Option<&mut Vec<LoadAllpairsError>>,
+) -> Result<(PpmTableBuilder<u32, S>, SeenPairs), LoadAllpairsError> {
+    let io_error = |err: io::Error| LoadAllpairsError::Io(err.to_string());
+    let file = MappedFile::open(path).map_err(io_error)?;
+    #[cfg(feature = "gzip")]
+    if let reader @ crate::MaybeGzReader::Gzip(_) =
+        crate::MaybeGzReader::new(&file[..]).map_err(io_error)?
+    {
+        return crate::collect_builder(reader, options, skipped);
+    }
+
+    let mut builder = PpmTableBuilder::new().with_duplicate_policy(options.duplicate_policy);
+    let mut seen = SeenPairs::new(options);
+    let mut formats = LineFormats::default();
+    let lines = map_byte_lines(&file, |line_number, line| {
+        add_line(
+            &mut builder,
+            &mut seen,
+            line_number,
+            line,
+            options,
+            &mut formats,
+        )
+    });
+    drain_lines(lines, skipped)?;
+    Ok((builder, seen))
+}
+
+/// Like `map_lines`, but splits `bytes` into lines in place. A line that is
+/// not valid UTF-8 gives [`LoadAllpairsError::InvalidUtf8`].
+fn map_byte_lines<'a, T: 'a>(
+    bytes: &'a [u8],
+    mut f: impl FnMut(usize, &str) -> Result<T, LoadAllpairsError> + 'a,
+) -> impl Iterator<Item = Result<T, LoadAllpairsError>> + 'a {
+    let mut start = 0;
+    let mut line_number = 0;
+    std::iter::from_fn(move || {
+        if start == bytes.len() {
+            return None;
+        }
+        let end = memchr::memchr(b'\n', &bytes[start..]).map_or(bytes.len(), |i| start + i + 1);
+        let line = &bytes[start..end];
+        let offset = start;
+        start = end;
+        line_number += 1;
+        Some(match std::str::from_utf8(line) {
+            Ok(line) => f(line_number, trim_line(line_number, line)),
+            Err(err) => Err(LoadAllpairsError::InvalidUtf8 {
+                line_number,
+                offset: offset + err.valid_up_to(),
+            }),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::load;
+
+    const FILE_CONTENTS: &str = concat!(
+        "\u{feff}  2191     23   5260   5236 a2-anonymous/001/a2.py a2-anonymous/002/a2.py\n",
+        "  2155     49   5260   5000 a2-anonymous/001/a2.py a2-anonymous/003/a2.py\r\n",
+        "  2232     12   5236   5000 a2-anonymous/002/a2.py a2-anonymous/003/a2.py",
+    );
+
+    /// A file in the temporary directory that is removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "allpairs-mmap-test-{}-{}",
+                std::process::id(),
+                name
+            ));
+            fs::write(&path, contents).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_load_from_path() {
+        let file = TempFile::new("valid", FILE_CONTENTS.as_bytes());
+        assert_eq!(
+            load_from_path(&file.0),
+            Ok(load(FILE_CONTENTS.to_string()).expect("File should be valid."))
+        );
+
+        let file = TempFile::new("empty", b"");
+        assert_eq!(
+            load_from_path(&file.0).expect("An empty file should be valid."),
+            load(String::new()).unwrap()
+        );
+
+        let mut invalid = FILE_CONTENTS.as_bytes().to_vec();
+        let second_line = FILE_CONTENTS.find('\n').unwrap() + 1;
+        invalid[second_line + 4] = 0xff;
+        let file = TempFile::new("invalid", &invalid);
+        assert_eq!(
+            load_from_path(&file.0),
+            Err(LoadAllpairsError::InvalidUtf8 {
+                line_number: 2,
+                offset: second_line + 4
+            })
+        );
+        let (ppm_table, skipped) = load_lenient_from_path(&file.0, LoadOptions::default());
+        assert!(matches!(
+            ppm_table,
+            Err(LoadAllpairsError::IncompleteGraph(_))
+        ));
+        assert_eq!(skipped.len(), 1);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_load_from_path_gzip() {
+        use std::io::Write;
+
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(FILE_CONTENTS.as_bytes()).unwrap();
+        let file = TempFile::new("gzip", &encoder.finish().unwrap());
+        assert_eq!(
+            load_from_path(&file.0),
+            Ok(load(FILE_CONTENTS.to_string()).expect("File should be valid."))
+        );
+    }
+}
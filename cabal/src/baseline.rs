@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use ppm_table::io::ReadTableError;
+use ppm_table::ppm::MAX_PPM;
+use ppm_table::{PpmTable, PpmTableBuilder};
+use serde::Serialize;
+
+use crate::report::PathIds;
+
+/// The PPM between each submission and the starter code they were all given.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Baseline(HashMap<String, u32>);
+
+/// A pair of IDs whose PPM was corrected for the baseline. `adjusted` is `None` if the pair was
+/// dropped.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct AdjustedPair {
+    pub left: String,
+    pub right: String,
+    pub ppm: u32,
+    pub adjusted: Option<u32>,
+}
+
+impl Baseline {
+    /// Loads a baseline from a PPM table or allpairs file with the starter code as one of its
+    /// keys, or from a file of `ID PPM` lines.
+    pub fn load(path: &Path, starter: Option<&str>, ids: &PathIds) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+        let table = match ppm_table::io::read_table(bytes.as_slice()) {
+            Err(ReadTableError::NotPpmTable) => {
+                let contents = String::from_utf8(bytes)?;
+                let first_line = contents.lines().find(|line| !line.trim().is_empty());
+                if first_line.is_some_and(|line| line.split_whitespace().count() == 2) {
+                    return Self::from_two_columns(&contents);
+                }
+                allpairs::load(contents)?
+            }
+            table => table?,
+        };
+        let Some(starter) = starter else {
+            bail!(
+                "--starter is needed to find the starter code in the baseline table {}",
+                path.display()
+            );
+        };
+        Self::from_table(&table, starter, ids)
+    }
+
+    /// Reads the PPM between the key whose ID is `starter` and every other key.
+    pub fn from_table(table: &PpmTable, starter: &str, ids: &PathIds) -> Result<Self> {
+        let keys = (0..table.len()).filter_map(|i| table.key_at(i));
+        let Some(starter_key) = keys.clone().find(|&key| ids.id(key).ok() == Some(starter)) else {
+            bail!("the starter {} is not in the baseline table", starter);
+        };
+        let mut baseline = HashMap::new();
+        for key in keys.filter(|&key| key != starter_key) {
+            baseline.insert(ids.id(key)?.to_string(), table[(starter_key, key)]);
+        }
+        Ok(Baseline(baseline))
+    }
+
+    /// Reads lines of an ID followed by its PPM from the starter code.
+    pub fn from_two_columns(contents: &str) -> Result<Self> {
+        let mut baseline = HashMap::new();
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some((id, ppm)) = line.trim().rsplit_once(char::is_whitespace) else {
+                bail!("line {} of the baseline has no PPM", i + 1);
+            };
+            let ppm = ppm.parse().with_context(|| {
+                format!("invalid PPM {:?} on line {} of the baseline", ppm, i + 1)
+            })?;
+            baseline.insert(id.trim().to_string(), ppm);
+        }
+        Ok(Baseline(baseline))
+    }
+
+    /// Corrects the PPM of every pair whose IDs are both in the baseline with [`adjust_ppm`].
+    /// Dropped pairs are given [`MAX_PPM`]. Returns the corrected table and the pairs that changed,
+    /// sorted by their original PPM.
+    pub fn apply(
+        &self,
+        table: PpmTable,
+        ids: &PathIds,
+        drop_within: u32,
+    ) -> Result<(PpmTable, Vec<AdjustedPair>)> {
+        let mut builder = PpmTableBuilder::with_capacity(table.len());
+        let mut adjusted_pairs = Vec::new();
+        for (l, r, ppm) in table.into_edges() {
+            let (l_id, r_id) = (ids.id(&l)?, ids.id(&r)?);
+            let adjusted = match (self.0.get(l_id), self.0.get(r_id)) {
+                (Some(&l_baseline), Some(&r_baseline)) => {
+                    adjust_ppm(ppm, (l_baseline, r_baseline), drop_within)
+                }
+                _ => Some(ppm),
+            };
+            if adjusted != Some(ppm) {
+                adjusted_pairs.push(AdjustedPair {
+                    left: l_id.to_string(),
+                    right: r_id.to_string(),
+                    ppm,
+                    adjusted,
+                });
+            }
+            builder.add_ppm(l, r, adjusted.unwrap_or(MAX_PPM));
+        }
+        adjusted_pairs.sort_unstable_by_key(|pair| pair.ppm);
+        Ok((builder.build()?, adjusted_pairs))
+    }
+}
+
+/// Corrects the PPM of a pair for how much of it both members owe to the starter code, given each
+/// member's PPM from the starter. Returns `None` if both members are within `drop_within` of the
+/// starter, so that their similarity says nothing.
+///
+/// Otherwise, the PPM is scaled up by how far the member further from the starter is from 100%.
+/// Two submissions that are 2% apart but 40% from the starter become 5% apart, while the PPM of
+/// submissions that share nothing with the starter is unchanged.
+pub fn adjust_ppm(ppm: u32, baselines: (u32, u32), drop_within: u32) -> Option<u32> {
+    let owed = baselines.0.max(baselines.1);
+    if owed <= drop_within {
+        return None;
+    }
+    let scaled = ppm as u64 * MAX_PPM as u64 / owed.min(MAX_PPM) as u64;
+    Some((scaled.min(MAX_PPM as u64) as u32).max(ppm))
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    #[test]
+    fn test_adjust_ppm() {
+        assert_eq!(adjust_ppm(20_000, (400_000, 100_000), 10_000), Some(50_000));
+        assert_eq!(adjust_ppm(20_000, (MAX_PPM, 0), 10_000), Some(20_000));
+        assert_eq!(adjust_ppm(20_000, (15_000, 5_000), 10_000), Some(MAX_PPM));
+        assert_eq!(adjust_ppm(20_000, (10_000, 5_000), 10_000), None);
+        assert_eq!(adjust_ppm(0, (0, 0), 0), None);
+        assert_eq!(adjust_ppm(0, (1, 0), 0), Some(0));
+    }
+
+    #[test]
+    fn test_baseline_apply() {
+        let table = allpairs::load(
+            concat!(
+                "  20000    23   5260   5236 a2/001/a2.py a2/002/a2.py\n",
+                "  15000    49   5260   5000 a2/001/a2.py a2/003/a2.py\n",
+                "  22320    12   5236   5000 a2/002/a2.py a2/003/a2.py\n",
+                "  400000   12   5236   5000 a2/starter/a2.py a2/001/a2.py\n",
+                "  100000   12   5236   5000 a2/starter/a2.py a2/002/a2.py\n",
+                "  5000     12   5236   5000 a2/starter/a2.py a2/003/a2.py\n",
+            )
+            .to_string(),
+        )
+        .unwrap();
+        let ids = PathIds::new(Regex::new(r"^[^/]+/(.+)/a2.py").unwrap(), false);
+        let baseline = Baseline::from_table(&table, "starter", &ids).unwrap();
+        assert_eq!(
+            baseline,
+            Baseline::from_two_columns("001 400000\n\n002   100000\n003 5000\n").unwrap()
+        );
+
+        let (adjusted, pairs) = baseline.apply(table, &ids, 10_000).unwrap();
+        assert_eq!(adjusted[("a2/001/a2.py", "a2/002/a2.py")], 50_000);
+        assert_eq!(adjusted[("a2/001/a2.py", "a2/003/a2.py")], 37_500);
+        assert_eq!(adjusted[("a2/002/a2.py", "a2/003/a2.py")], 223_200);
+        assert_eq!(adjusted[("a2/starter/a2.py", "a2/001/a2.py")], 400_000);
+        assert_eq!(
+            pairs.iter().map(|pair| pair.ppm).collect::<Vec<_>>(),
+            [15_000, 20_000, 22_320]
+        );
+
+        let (_, pairs) = baseline.apply(adjusted, &ids, 100_000).unwrap();
+        assert_eq!(pairs[2].adjusted, None);
+    }
+}
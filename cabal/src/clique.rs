@@ -1,7 +1,11 @@
 use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
+use std::mem;
 
 use petgraph::prelude::*;
+use ppm_table::ppm::Ppm;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 
 #[derive(Clone, Debug)]
 pub struct Clique<'a> {
@@ -17,14 +21,6 @@ impl<'a> Clique<'a> {
         new_clique
     }
 
-    pub fn id(&self) -> usize {
-        self.id
-    }
-
-    pub fn contains(&self, l: &'a str) -> bool {
-        self.members.contains_node(l)
-    }
-
     pub fn add(&mut self, l: &'a str, r: &'a str, ppm: u32) {
         self.members.add_edge(l, r, ppm);
     }
@@ -33,7 +29,13 @@ impl<'a> Clique<'a> {
         self.members.nodes().collect::<Vec<_>>().into_iter()
     }
 
-    pub fn merge(&mut self, o: Clique<'a>) {
+    /// Adds the edges of `o` to the clique, which keeps its ID. The edges of the smaller of the
+    /// two are copied into the larger, so however cliques merge, each edge is only copied a
+    /// logarithmic number of times.
+    pub fn merge(&mut self, mut o: Clique<'a>) {
+        if o.members.edge_count() > self.members.edge_count() {
+            mem::swap(&mut self.members, &mut o.members);
+        }
         for (l, r, ppm) in o.members.all_edges() {
             self.add(l, r, *ppm)
         }
@@ -67,23 +69,46 @@ impl<'a> Clique<'a> {
         min_difference_and_key.unwrap().1
     }
 
-    pub fn export(&self) -> CliqueExport {
+    /// Exports the clique, along with its edges if `with_edges`.
+    pub fn export(&self, with_edges: bool) -> CliqueExport {
         let core = self.core().to_string();
-        let non_core_members = self
+        let mut non_core_members = self
             .members
             .nodes()
             .filter(|n| *n != core)
             .map(|n| n.to_string())
-            .collect();
+            .collect::<Vec<_>>();
+        non_core_members.sort_unstable();
         let max_ppm = self.max_ppm();
+        let edges = with_edges.then(|| self.edges());
 
         CliqueExport {
+            id: self.id,
             core,
             non_core_members,
             max_ppm,
+            edges,
         }
     }
 
+    /// The edges between members, sorted by PPM and then by their members.
+    fn edges(&self) -> Vec<CliqueEdge> {
+        let mut edges = self
+            .members
+            .all_edges()
+            .map(|(l, r, &ppm)| (ppm, l.min(r), l.max(r)))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+            .into_iter()
+            .map(|(ppm, l, r)| CliqueEdge {
+                left: l.to_string(),
+                right: r.to_string(),
+                ppm,
+            })
+            .collect()
+    }
+
     fn max_ppm(&self) -> u32 {
         self.members
             .all_edges()
@@ -95,39 +120,115 @@ impl<'a> Clique<'a> {
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct CliqueExport {
+    id: usize,
     core: String,
+    /// Sorted, so that exports do not depend on the order in which members were added.
     non_core_members: Vec<String>,
     max_ppm: u32,
+    edges: Option<Vec<CliqueEdge>>,
+}
+
+/// An edge between two members of a clique, with `left` the smaller of them.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CliqueEdge {
+    pub left: String,
+    pub right: String,
+    pub ppm: u32,
+}
+
+impl Serialize for CliqueEdge {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut edge = serializer.serialize_struct("CliqueEdge", 4)?;
+        edge.serialize_field("left", &self.left)?;
+        edge.serialize_field("right", &self.right)?;
+        edge.serialize_field("ppm", &self.ppm)?;
+        edge.serialize_field("percent", &Ppm(self.ppm).percent())?;
+        edge.end()
+    }
+}
+
+impl Display for CliqueEdge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} — {}: {}%", self.left, self.right, Ppm(self.ppm))
+    }
 }
 
 impl CliqueExport {
+    /// Orders cliques by their maximum PPM, breaking ties by their cores and then by their
+    /// members so that equally similar cliques always come out in the same order.
     pub fn cmp_ppm(&self, other: &Self) -> Ordering {
-        self.max_ppm.cmp(&other.max_ppm)
+        self.max_ppm
+            .cmp(&other.max_ppm)
+            .then_with(|| self.core.cmp(&other.core))
+            .then_with(|| self.members().cmp(&other.members()))
+    }
+
+    /// The ID of the clique, which it keeps as it grows and absorbs others.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn core(&self) -> &str {
+        &self.core
+    }
+
+    pub fn max_ppm(&self) -> u32 {
+        self.max_ppm
+    }
+
+    pub fn len(&self) -> usize {
+        self.non_core_members.len() + 1
+    }
+
+    /// The edges between members, if they were exported.
+    pub fn edges(&self) -> Option<&[CliqueEdge]> {
+        self.edges.as_deref()
+    }
+
+    /// A copy of the export that leaves out the edges.
+    pub fn without_edges(&self) -> Self {
+        CliqueExport {
+            core: self.core.clone(),
+            non_core_members: self.non_core_members.clone(),
+            edges: None,
+            ..*self
+        }
+    }
+
+    /// The core, followed by the other members in sorted order.
+    pub fn members(&self) -> Vec<&str> {
+        let mut members = Vec::with_capacity(self.len());
+        members.push(self.core.as_str());
+        members.extend(self.non_core_members.iter().map(String::as_str));
+        members
+    }
+}
+
+impl Serialize for CliqueExport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fields = 5 + self.edges.is_some() as usize;
+        let mut clique = serializer.serialize_struct("CliqueExport", fields)?;
+        clique.serialize_field("id", &self.id)?;
+        clique.serialize_field("core", &self.core)?;
+        clique.serialize_field("members", &self.members())?;
+        clique.serialize_field("max_ppm", &self.max_ppm)?;
+        clique.serialize_field("max_percent", &Ppm(self.max_ppm).percent())?;
+        if let Some(edges) = &self.edges {
+            clique.serialize_field("edges", edges)?;
+        }
+        clique.end()
     }
 }
 
 impl Display for CliqueExport {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut sortable = self.non_core_members.clone();
-        sortable.sort();
-        sortable.insert(0, self.core.clone());
-
-        write!(
-            f,
-            "[{}]",
-            sortable
-                .into_iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>()
-                .join(", ")
-        )?;
-
-        write!(
-            f,
-            " max%: {}.{}",
-            self.max_ppm / 10000,
-            (self.max_ppm % 10000) / 1000
-        )?;
+        write!(f, "[{}]", self.members().join(", "))?;
+
+        write!(f, " max%: {}", Ppm(self.max_ppm))?;
+
+        for edge in self.edges.iter().flatten() {
+            write!(f, "\n       {}", edge)?;
+        }
 
         Ok(())
     }
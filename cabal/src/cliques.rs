@@ -2,24 +2,54 @@ use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 
+use ppm_table::Edge;
+use serde::Serialize;
+
 use crate::clique::{Clique, CliqueExport};
 
+/// The cliques formed by the edges added so far, kept as a disjoint-set forest over clique IDs.
+///
+/// Each member points at the ID of the clique it joined, and each absorbed clique points at the
+/// ID of the clique that absorbed it, so the clique containing a member is found by following
+/// these to a root, which owns the [`Clique`].
 #[derive(Clone, Debug)]
 pub struct Cliques<'a> {
     cliques: HashMap<usize, Clique<'a>>,
+    /// The ID of the clique that each member joined, which is left as it is when that clique is
+    /// absorbed.
+    members: HashMap<&'a str, usize>,
+    /// The ID that each clique was absorbed into, indexed from `first_id`. Roots are their own
+    /// parent.
+    parents: Vec<usize>,
+    first_id: usize,
     base_id: usize,
+    snapshot: Snapshot<'a>,
 }
 
 impl<'a> Cliques<'a> {
     pub fn new(base_id: u32) -> Self {
-        let cliques = HashMap::new();
         let base_id = base_id as usize;
-        Cliques { cliques, base_id }
+        Cliques {
+            cliques: HashMap::new(),
+            members: HashMap::new(),
+            parents: Vec::new(),
+            first_id: base_id,
+            base_id,
+            snapshot: Snapshot::default(),
+        }
     }
 
-    pub fn add(&mut self, l: &'a str, r: &'a str, ppm: u32) {
+    pub fn add(&mut self, edge: Edge<'a>) {
+        let Edge {
+            left: l,
+            right: r,
+            ppm,
+        } = edge;
         let lc = self.find_id_of_clique_containing(l);
         let rc = self.find_id_of_clique_containing(r);
+        let id = lc.or(rc).unwrap_or(self.base_id);
+        self.members.entry(l).or_insert(id);
+        self.members.entry(r).or_insert(id);
 
         match (lc, rc) {
             (Some(lc), Some(rc)) => {
@@ -28,6 +58,7 @@ impl<'a> Cliques<'a> {
                     let left_clique = self.cliques.get_mut(&lc).unwrap();
                     left_clique.merge(right_clique);
                     left_clique.add(l, r, ppm);
+                    self.parents[rc - self.first_id] = lc;
                 } else {
                     self.cliques.get_mut(&lc).unwrap().add(l, r, ppm);
                 }
@@ -41,63 +72,89 @@ impl<'a> Cliques<'a> {
             (None, None) => {
                 self.cliques
                     .insert(self.base_id, Clique::new(l, r, ppm, self.base_id));
+                self.parents.push(self.base_id);
                 self.base_id += 1
             }
         }
     }
 
-    pub fn export(&self, other: &Self) -> CliquesExport {
+    /// Exports the cliques, noting how each grew since the last export, and remembers them so
+    /// that the next export can do the same. The edges of each clique are exported too if
+    /// `with_edges`.
+    pub fn export_and_snapshot(&mut self, with_edges: bool) -> CliquesExport {
         let mut cliques = Vec::new();
+        let mut snapshot_cliques = HashMap::with_capacity(self.cliques.len());
+        let mut member_cliques = HashMap::with_capacity(self.members.len());
 
-        for clique in self.cliques.values() {
-            let merged_cliques = Self::merged_cliques(other, clique);
-            let added_members = Self::added_members(other, clique);
+        for (&id, clique) in &self.cliques {
+            let export = clique.export(with_edges);
+            snapshot_cliques.insert(id, export.without_edges());
+            member_cliques.extend(clique.iter().map(|member| (member, id)));
 
+            let merged_cliques = self.snapshot.merged_cliques(clique);
             if merged_cliques.is_empty() {
-                cliques.push(CliquesExportElement::New(clique.export()))
+                cliques.push(CliquesExportElement::New(export))
             } else {
                 cliques.push(CliquesExportElement::Old {
-                    clique: clique.export(),
+                    clique: export,
                     merged: merged_cliques,
-                    added: added_members,
+                    added: self.snapshot.added_members(clique),
                 })
             }
         }
         cliques.sort_by(CliquesExportElement::cmp_ppm);
+
+        self.snapshot = Snapshot {
+            member_cliques,
+            cliques: snapshot_cliques,
+        };
         CliquesExport { cliques }
     }
 
-    fn find_id_of_clique_containing(&self, id: &str) -> Option<usize> {
-        self.cliques
-            .values()
-            .find_map(|c| if c.contains(id) { Some(c.id()) } else { None })
+    /// Finds the root of the clique `id` is in, halving the path to it along the way.
+    fn find_id_of_clique_containing(&mut self, id: &str) -> Option<usize> {
+        let mut clique = *self.members.get(id)?;
+        loop {
+            let parent = self.parents[clique - self.first_id];
+            if parent == clique {
+                return Some(clique);
+            }
+            let grandparent = self.parents[parent - self.first_id];
+            self.parents[clique - self.first_id] = grandparent;
+            clique = grandparent;
+        }
     }
+}
 
-    fn merged_cliques(other: &Self, clique: &Clique) -> Vec<CliqueExport> {
+/// The cliques as of the last export: just their members and exports, rather than their graphs.
+#[derive(Clone, Debug, Default)]
+struct Snapshot<'a> {
+    member_cliques: HashMap<&'a str, usize>,
+    cliques: HashMap<usize, CliqueExport>,
+}
+
+impl Snapshot<'_> {
+    /// The cliques in the snapshot that have since become part of `clique`.
+    fn merged_cliques(&self, clique: &Clique) -> Vec<CliqueExport> {
         let merged_clique_ids: HashSet<_> = clique
             .iter()
-            .filter_map(|id| {
-                other
-                    .cliques
-                    .values()
-                    .find(|&c| c.contains(id))
-                    .map(Clique::id)
-            })
+            .filter_map(|id| self.member_cliques.get(id))
             .collect();
 
         let mut merged_cliques: Vec<_> = merged_clique_ids
             .into_iter()
-            .map(|id| other.cliques[&id].export())
+            .map(|id| self.cliques[id].clone())
             .collect();
         merged_cliques.sort_by(CliqueExport::cmp_ppm);
 
         merged_cliques
     }
 
-    fn added_members(other: &Self, clique: &Clique) -> Vec<String> {
+    /// The members of `clique` that were not in any clique in the snapshot.
+    fn added_members(&self, clique: &Clique) -> Vec<String> {
         let mut added_members = Vec::new();
         for id in clique.iter() {
-            if !other.cliques.values().any(|c| c.contains(id)) {
+            if !self.member_cliques.contains_key(id) {
                 added_members.push(id.to_string());
             }
         }
@@ -106,11 +163,24 @@ impl<'a> Cliques<'a> {
     }
 }
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
 pub struct CliquesExport {
     cliques: Vec<CliquesExportElement>,
 }
 
+impl CliquesExport {
+    pub fn cliques(&self) -> &[CliquesExportElement] {
+        &self.cliques
+    }
+
+    /// Leaves out the cliques with fewer than `min_size` members. The cliques they were absorbed
+    /// into still list them.
+    pub fn retain_min_size(&mut self, min_size: usize) {
+        self.cliques
+            .retain(|element| element.clique().len() >= min_size);
+    }
+}
+
 impl Display for CliquesExport {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         for clique in &self.cliques {
@@ -120,11 +190,16 @@ impl Display for CliquesExport {
     }
 }
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+/// A clique in a [`CliquesExport`]. In JSON, `"status"` is `"new"` or `"old"` alongside the
+/// clique's fields, and an old clique also lists the cliques it absorbed and the members it added.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
 pub enum CliquesExportElement {
     New(CliqueExport),
     Old {
+        #[serde(flatten)]
         clique: CliqueExport,
+        #[serde(rename = "absorbed")]
         merged: Vec<CliqueExport>,
         added: Vec<String>,
     },
@@ -139,7 +214,7 @@ impl CliquesExportElement {
         }
     }
 
-    fn clique(&self) -> &CliqueExport {
+    pub fn clique(&self) -> &CliqueExport {
         match self {
             CliquesExportElement::New(clique) => clique,
             CliquesExportElement::Old { clique, .. } => clique,
@@ -177,3 +252,236 @@ impl Display for CliquesExportElement {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_cliques_export_json() {
+        let mut cliques = Cliques::new(0);
+        cliques.add(Edge::from(("001", "002", 2191)));
+        cliques.export_and_snapshot(false);
+        cliques.add(Edge::from(("003", "004", 12000)));
+        cliques.add(Edge::from(("002", "005", 15000)));
+
+        assert_eq!(
+            serde_json::to_value(cliques.export_and_snapshot(false)).unwrap(),
+            json!({
+                "cliques": [
+                    {
+                        "status": "old",
+                        "id": 0,
+                        "core": "001",
+                        "members": ["001", "002", "005"],
+                        "max_ppm": 15000,
+                        "max_percent": 1.5,
+                        "absorbed": [
+                            {
+                                "id": 0,
+                                "core": "001",
+                                "members": ["001", "002"],
+                                "max_ppm": 2191,
+                                "max_percent": 0.2191,
+                            },
+                        ],
+                        "added": ["005"],
+                    },
+                    {
+                        "status": "new",
+                        "id": 1,
+                        "core": "003",
+                        "members": ["003", "004"],
+                        "max_ppm": 12000,
+                        "max_percent": 1.2,
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_cliques_export_edges() {
+        let mut cliques = Cliques::new(0);
+        cliques.add(Edge::from(("003", "001", 15000)));
+        cliques.add(Edge::from(("002", "001", 15000)));
+        cliques.add(Edge::from(("003", "002", 12000)));
+
+        let export = cliques.export_and_snapshot(true);
+        assert_eq!(
+            export.to_string(),
+            concat!(
+                "New: [001, 002, 003] max%: 1.5\n",
+                "       002 — 003: 1.2%\n",
+                "       001 — 002: 1.5%\n",
+                "       001 — 003: 1.5%\n",
+            )
+        );
+        assert_eq!(
+            serde_json::to_value(&export).unwrap()["cliques"][0]["edges"][0],
+            json!({"left": "002", "right": "003", "ppm": 12000, "percent": 1.2})
+        );
+        assert!(cliques.export_and_snapshot(false).cliques()[0]
+            .clique()
+            .edges()
+            .is_none());
+    }
+
+    #[test]
+    fn test_cliques_merge_larger_clique() {
+        let mut cliques = Cliques::new(0);
+        cliques.add(Edge::from(("001", "002", 1000)));
+        cliques.add(Edge::from(("003", "004", 2000)));
+        cliques.add(Edge::from(("004", "005", 2500)));
+        cliques.add(Edge::from(("003", "005", 3000)));
+        // The small clique 0 absorbs the larger clique 1, whose edges it is given.
+        cliques.add(Edge::from(("002", "003", 4000)));
+
+        let export = cliques.export_and_snapshot(true);
+        let [CliquesExportElement::New(clique)] = export.cliques() else {
+            panic!("{:?}", export);
+        };
+        assert_eq!(clique.id(), 0);
+        assert_eq!(clique.members(), ["001", "002", "003", "004", "005"]);
+        assert_eq!(clique.edges().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_cliques_export_ties() {
+        let export = || {
+            let mut cliques = Cliques::new(0);
+            cliques.add(Edge::from(("005", "006", 1000)));
+            cliques.add(Edge::from(("003", "004", 1000)));
+            cliques.add(Edge::from(("001", "002", 1000)));
+            cliques.export_and_snapshot(false);
+            cliques.add(Edge::from(("007", "008", 5000)));
+            cliques.add(Edge::from(("002", "005", 3000)));
+            cliques.add(Edge::from(("006", "003", 5000)));
+            cliques.add(Edge::from(("009", "010", 5000)));
+            cliques.export_and_snapshot(false).to_string()
+        };
+
+        let first = export();
+        assert_eq!(
+            first,
+            concat!(
+                "Old: [001, 002, 003, 004, 005, 006] max%: 0.5\n",
+                "     Absorbed 3:\n",
+                "          [001, 002] max%: 0.1\n",
+                "          [003, 004] max%: 0.1\n",
+                "          [005, 006] max%: 0.1\n",
+                "New: [007, 008] max%: 0.5\n",
+                "New: [009, 010] max%: 0.5\n",
+            )
+        );
+        for _ in 0..20 {
+            assert_eq!(export(), first);
+        }
+    }
+
+    /// The linear scan over every clique that [`Cliques`] did before it kept a disjoint-set forest.
+    fn find_naive(cliques: &HashMap<usize, Clique>, id: &str) -> Option<usize> {
+        cliques
+            .iter()
+            .find_map(|(&c, clique)| clique.iter().any(|m| m == id).then_some(c))
+    }
+
+    fn add_naive<'a>(
+        cliques: &mut HashMap<usize, Clique<'a>>,
+        base_id: &mut usize,
+        edge: Edge<'a>,
+    ) {
+        let (l, r, ppm) = (edge.left, edge.right, edge.ppm);
+        match (find_naive(cliques, l), find_naive(cliques, r)) {
+            (Some(lc), Some(rc)) if lc != rc => {
+                let right_clique = cliques.remove(&rc).unwrap();
+                let left_clique = cliques.get_mut(&lc).unwrap();
+                left_clique.merge(right_clique);
+                left_clique.add(l, r, ppm);
+            }
+            (Some(c), _) | (None, Some(c)) => cliques.get_mut(&c).unwrap().add(l, r, ppm),
+            (None, None) => {
+                cliques.insert(*base_id, Clique::new(l, r, ppm, *base_id));
+                *base_id += 1;
+            }
+        }
+    }
+
+    /// Exports `cliques` by comparing them against a full copy of the cliques at the last export,
+    /// as the report did before [`Cliques`] kept snapshots.
+    fn export_naive(
+        cliques: &HashMap<usize, Clique>,
+        prev: &HashMap<usize, Clique>,
+        with_edges: bool,
+    ) -> CliquesExport {
+        let mut elements = Vec::new();
+        for clique in cliques.values() {
+            let merged_ids: HashSet<_> =
+                clique.iter().filter_map(|m| find_naive(prev, m)).collect();
+            let mut merged: Vec<_> = merged_ids
+                .into_iter()
+                .map(|id| prev[&id].export(false))
+                .collect();
+            merged.sort_by(CliqueExport::cmp_ppm);
+            let mut added: Vec<_> = clique
+                .iter()
+                .filter(|m| find_naive(prev, m).is_none())
+                .map(str::to_string)
+                .collect();
+            added.sort();
+
+            let clique = clique.export(with_edges);
+            if merged.is_empty() {
+                elements.push(CliquesExportElement::New(clique));
+            } else {
+                elements.push(CliquesExportElement::Old {
+                    clique,
+                    merged,
+                    added,
+                });
+            }
+        }
+        elements.sort_by(CliquesExportElement::cmp_ppm);
+        CliquesExport { cliques: elements }
+    }
+
+    #[test]
+    fn test_cliques_match_naive() {
+        // A small linear congruential generator, so the edges are the same on every run.
+        let mut state = 0x2545_f491_u64;
+        let mut next = |bound: u64| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            (state >> 33) % bound
+        };
+        let ids = (0..60).map(|i| format!("{:03}", i)).collect::<Vec<_>>();
+        let mut edges = (0..400)
+            .map(|_| {
+                let (l, r) = (next(60) as usize, next(60) as usize);
+                (l, r, next(100_000) as u32)
+            })
+            .filter(|(l, r, _)| l != r)
+            .collect::<Vec<_>>();
+        edges.sort_by_key(|&(_, _, ppm)| ppm);
+
+        let mut cliques = Cliques::new(0);
+        let mut naive = HashMap::new();
+        let mut naive_base_id = 0;
+        let mut prev_naive = naive.clone();
+        for (i, &(l, r, ppm)) in edges.iter().enumerate() {
+            let edge = Edge::from((ids[l].as_str(), ids[r].as_str(), ppm));
+            cliques.add(edge);
+            add_naive(&mut naive, &mut naive_base_id, edge);
+            if i % 10 == 0 {
+                assert_eq!(
+                    cliques.export_and_snapshot(true),
+                    export_naive(&naive, &prev_naive, true)
+                );
+                prev_naive = naive.clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+
+use ppm_table::ppm::Ppm;
+
+use crate::baseline::AdjustedPair;
+use crate::clique::CliqueExport;
+use crate::cliques::{CliquesExport, CliquesExportElement};
+
+const HEADER: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Clique report</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+summary { cursor: pointer; font-weight: bold; }
+table { border-collapse: collapse; margin: 0.5em 0 1em; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; text-align: left; vertical-align: top; }
+tr.new { background: #fff3c4; }
+details details summary { font-weight: normal; }
+</style>
+</head>
+<body>
+<h1>Clique report</h1>
+"#;
+
+const FOOTER: &str = "</body>\n</html>\n";
+
+/// Escapes the characters that are special in HTML text and attribute values.
+struct Escaped<'a>(&'a str);
+
+impl Display for Escaped<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&#39;")?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escapes and joins the members of `clique`, core first.
+struct Members<'a>(&'a CliqueExport);
+
+impl Display for Members<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, member) in self.0.members().into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", Escaped(member))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn write_header(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(HEADER.as_bytes())
+}
+
+pub fn write_footer(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(FOOTER.as_bytes())
+}
+
+pub fn write_excluded(writer: &mut impl Write, excluded: &BTreeSet<String>) -> io::Result<()> {
+    write!(writer, "<p>Excluded: ")?;
+    for (i, id) in excluded.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ", ")?;
+        }
+        write!(writer, "{}", Escaped(id))?;
+    }
+    writeln!(writer, "</p>")
+}
+
+pub fn write_baseline(
+    writer: &mut impl Write,
+    adjusted: &[AdjustedPair],
+    drop_within: u32,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "<p>Corrected for the starter code, dropping pairs both within {}% of it:</p>",
+        Ppm(drop_within).percent()
+    )?;
+    writeln!(writer, "<ul>")?;
+    for pair in adjusted {
+        write!(
+            writer,
+            "<li>{}, {}: {}% &rarr; ",
+            Escaped(&pair.left),
+            Escaped(&pair.right),
+            Ppm(pair.ppm)
+        )?;
+        match pair.adjusted {
+            Some(ppm) => writeln!(writer, "{}%</li>", Ppm(ppm))?,
+            None => writeln!(writer, "dropped</li>")?,
+        }
+    }
+    writeln!(writer, "</ul>")
+}
+
+/// Writes the cliques at one percentage step as a collapsible section with a row per clique. New
+/// cliques are highlighted, and the cliques an old clique absorbed are in a collapsible block.
+pub fn write_step(
+    writer: &mut impl Write,
+    max_ppm: u32,
+    cliques: &CliquesExport,
+) -> io::Result<()> {
+    writeln!(writer, "<details open>")?;
+    writeln!(writer, "<summary>At {}%</summary>", Ppm(max_ppm).percent())?;
+    if cliques.cliques().is_empty() {
+        writeln!(writer, "<p>No cliques.</p>")?;
+        return writeln!(writer, "</details>");
+    }
+    writeln!(writer, "<table>")?;
+    writeln!(
+        writer,
+        "<tr><th>Core</th><th>Members</th><th>Max %</th><th>Changes</th></tr>"
+    )?;
+    for element in cliques.cliques() {
+        let clique = element.clique();
+        let class = match element {
+            CliquesExportElement::New(_) => "new",
+            CliquesExportElement::Old { .. } => "old",
+        };
+        write!(
+            writer,
+            "<tr class=\"{}\"><td>{}</td><td>{}",
+            class,
+            Escaped(clique.core()),
+            Members(clique)
+        )?;
+        if let Some(edges) = clique.edges() {
+            write!(writer, "<details><summary>Pairs</summary><ul>")?;
+            for edge in edges {
+                write!(
+                    writer,
+                    "<li>{} &mdash; {}: {}%</li>",
+                    Escaped(&edge.left),
+                    Escaped(&edge.right),
+                    Ppm(edge.ppm)
+                )?;
+            }
+            write!(writer, "</ul></details>")?;
+        }
+        write!(writer, "</td><td>{:.2}%</td><td>", Ppm(clique.max_ppm()))?;
+        match element {
+            CliquesExportElement::New(_) => write!(writer, "New")?,
+            CliquesExportElement::Old { merged, added, .. } => {
+                if merged.len() > 1 {
+                    write!(
+                        writer,
+                        "<details><summary>Absorbed {}</summary><ul>",
+                        merged.len()
+                    )?;
+                    for clique in merged {
+                        write!(
+                            writer,
+                            "<li>{}: {} ({:.2}%)</li>",
+                            Escaped(clique.core()),
+                            Members(clique),
+                            Ppm(clique.max_ppm())
+                        )?;
+                    }
+                    write!(writer, "</ul></details>")?;
+                }
+                if !added.is_empty() {
+                    write!(writer, "Added: ")?;
+                    for (i, member) in added.iter().enumerate() {
+                        if i > 0 {
+                            write!(writer, ", ")?;
+                        }
+                        write!(writer, "{}", Escaped(member))?;
+                    }
+                }
+            }
+        }
+        writeln!(writer, "</td></tr>")?;
+    }
+    writeln!(writer, "</table>")?;
+    writeln!(writer, "</details>")
+}
+
+#[cfg(test)]
+mod tests {
+    use ppm_table::Edge;
+
+    use super::*;
+    use crate::cliques::Cliques;
+
+    #[test]
+    fn test_write_step_html() {
+        let mut cliques = Cliques::new(0);
+        cliques.add(Edge {
+            left: "<b>001</b>",
+            right: "002",
+            ppm: 2191,
+        });
+        cliques.add(Edge {
+            left: "003",
+            right: "a&b",
+            ppm: 2232,
+        });
+        cliques.export_and_snapshot(false);
+        cliques.add(Edge {
+            left: "002",
+            right: "003",
+            ppm: 15000,
+        });
+        cliques.add(Edge {
+            left: "004",
+            right: "005",
+            ppm: 16000,
+        });
+
+        let mut written = Vec::new();
+        write_step(&mut written, 20000, &cliques.export_and_snapshot(false)).unwrap();
+        let written = String::from_utf8(written).unwrap();
+
+        assert!(written.starts_with("<details open>\n<summary>At 2%</summary>\n"));
+        assert!(!written.contains("<b>"));
+        assert!(written.contains("&lt;b&gt;001&lt;/b&gt;"));
+        assert!(written.contains("a&amp;b"));
+        assert!(written.contains("<tr class=\"new\"><td>004</td><td>004, 005</td><td>1.60%</td>"));
+        assert!(written.contains("<details><summary>Absorbed 2</summary>"));
+        assert!(written.ends_with("</table>\n</details>\n"));
+    }
+}
@@ -1,94 +1,541 @@
+mod baseline;
 mod clique;
 mod cliques;
+mod html;
+mod report;
 
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use allpairs::{LoadAllpairsError, LoadOptions, MaybeGzReader};
+use anyhow::{bail, Context, Result};
+use baseline::Baseline;
 use clap::{Args, Parser};
-use cliques::Cliques;
-use ppm_table::PpmTable;
+use ppm_table::io::ReadTableError;
+use ppm_table::ppm::{self, Ppm, PPM_PER_PERCENT};
+use ppm_table::{LegacyPpmTable, PpmTable};
 use regex::Regex;
+use report::{write_report, OutputFormat, PathIds, ReportOptions, ReportWriter};
 
 /// Parses an allpairs file and produces a list of cliques.
 #[derive(Parser, Debug)]
 #[command(version)]
 struct Cmd {
-    /// Path to the allpairs file.
+    /// Paths to the allpairs files, which may be gzipped. Several files are combined into one
+    /// table, e.g. to compare submissions across terms.
     #[command(flatten)]
     file: InputFile,
     /// Maximum percentage to display similarities at (lower is more similar).
-    #[arg(short, long, default_value_t=6, value_parser=clap::value_parser!(u32).range(0..=100))]
-    max_similarity: u32,
+    #[arg(short, long, default_value_t = 6.0, value_parser = parse_percent)]
+    max_similarity: f64,
     /// File name used in the paths in the allpairs file.
-    #[arg(long = "handin-name", default_value = "handin.rkt")]
+    #[arg(long = "handin-name", default_value = "handin.rkt", group = "id")]
     handin_file_name: String,
+    /// Extract IDs from paths with the first capture group of this regex, instead of with the
+    /// directory above the handin file.
+    #[arg(long, value_name = "REGEX", group = "id")]
+    id_regex: Option<String>,
+    /// Use the full path as the ID of paths that the ID regex does not match, instead of failing.
+    #[arg(long, conflicts_with = "raw_ids")]
+    lenient_ids: bool,
+    /// Use the keys of the table as IDs as they are, e.g. for a PPM table that allpairs-loader
+    /// already keyed by ID. This is assumed, with a warning, if the ID regex matches no keys.
+    #[arg(long, group = "id")]
+    raw_ids: bool,
+    /// A PPM table or allpairs file with the starter code as one of its keys, or a file of `ID PPM`
+    /// lines, giving each submission's similarity to the starter code. Pairs are corrected for how
+    /// much of their similarity both members owe to the starter code.
+    #[arg(long, value_name = "PATH")]
+    baseline: Option<PathBuf>,
+    /// ID of the starter code in a --baseline table.
+    #[arg(long, value_name = "ID", requires = "baseline")]
+    starter: Option<String>,
+    /// Drop pairs whose members are both within this percentage of the starter code.
+    #[arg(long, value_name = "PERCENT", default_value_t = 1.0, value_parser = parse_percent)]
+    baseline_drop_within: f64,
+    /// Leave this ID, e.g. of the starter code, out of the clique report. May be repeated.
+    #[arg(long, value_name = "ID")]
+    exclude: Vec<String>,
+    /// Leave the IDs in this file, one per line, out of the clique report.
+    #[arg(long, value_name = "PATH")]
+    exclude_file: Option<PathBuf>,
+    /// Print the N most similar pairs instead of the clique report.
+    #[arg(long = "top-pairs", value_name = "N")]
+    top_pairs: Option<usize>,
+    /// Also write the similarity graph up to the maximum percentage to this Graphviz DOT file.
+    #[arg(long, value_name = "FILE")]
+    dot: Option<PathBuf>,
+    /// Skip malformed lines in the allpairs file instead of failing.
+    #[arg(long)]
+    skip_malformed: bool,
+    /// Percentage to advance the threshold by between steps of the clique report, e.g. 0.5
+    /// [default: 1, or the maximum percentage if it is lower].
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent)]
+    step: Option<f64>,
+    /// Leave cliques with fewer than N members out of the clique report.
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    min_clique_size: usize,
+    /// List the similarity of each pair of members under each clique.
+    #[arg(short, long, visible_alias = "show-edges")]
+    verbose: bool,
+    /// Format of the clique report.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+    /// Write the clique report or top pairs to this file instead of stdout, which then only gets
+    /// a summary.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+impl Cmd {
+    /// Returns the PPM limit of the report and the PPM to advance its threshold by, checking that
+    /// an explicit `--step` fits within `--max-similarity`.
+    fn thresholds(&self) -> Result<(u32, u32)> {
+        let ppm_limit = ppm::percent_to_ppm(self.max_similarity)?;
+        let Some(step) = self.step else {
+            // A step of 0 is only possible at a limit of 0, which no edge is above.
+            return Ok((ppm_limit, PPM_PER_PERCENT.min(ppm_limit)));
+        };
+        let step_ppm = ppm::percent_to_ppm(step)?;
+        if step_ppm == 0 || step_ppm > ppm_limit {
+            bail!(
+                "--step must be positive and at most --max-similarity ({}%), not {}%",
+                self.max_similarity,
+                step
+            );
+        }
+        Ok((ppm_limit, step_ppm))
+    }
 }
 
 #[derive(Args, Debug)]
 #[group(required = true, multiple = false)]
 struct InputFile {
-    allpairs_file: Option<PathBuf>,
+    /// Any of the paths may be - to read from stdin.
+    allpairs_files: Vec<PathBuf>,
+    /// Path to a PPM table file written by allpairs-loader, or - to read from stdin.
     #[arg(long = "ppm-table")]
     ppm_table_file: Option<PathBuf>,
+    /// Path to a CSV file of `left,right,similarity_ppm` records, which may be gzipped, or - to
+    /// read from stdin.
+    #[arg(long = "csv")]
+    csv_file: Option<PathBuf>,
 }
 
 impl InputFile {
-    fn ppm_table(&self) -> Result<PpmTable> {
-        if let Some(allpairs_file) = &self.allpairs_file {
-            let contents = fs::read_to_string(allpairs_file)?;
-            Ok(allpairs::load(contents)?)
+    /// Loads the table. If `max_ppm` is given, pairs above it may be dropped from an allpairs file
+    /// while loading, and given the PPM [`ppm::MAX_PPM`].
+    fn ppm_table(&self, skip_malformed: bool, max_ppm: Option<u32>) -> Result<PpmTable> {
+        if let [_, _, ..] = self.allpairs_files.as_slice() {
+            if skip_malformed {
+                bail!("--skip-malformed only supports a single allpairs file");
+            }
+            let files = self
+                .allpairs_files
+                .iter()
+                .map(|file| {
+                    let mut contents = String::new();
+                    MaybeGzReader::new(open(file)?)?.read_to_string(&mut contents)?;
+                    Ok((file.display().to_string(), contents))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(allpairs::load_many(files)?)
+        } else if let [allpairs_file] = self.allpairs_files.as_slice() {
+            let options = LoadOptions {
+                max_ppm,
+                ..LoadOptions::default()
+            };
+            // Only regular files can be mapped into memory, so stdin and pipes, such as those that
+            // process substitution gives, are read as they stream in.
+            let mappable =
+                allpairs_file.as_os_str() != "-" && fs::metadata(allpairs_file)?.is_file();
+            let (ppm_table, skipped) = match (mappable, skip_malformed) {
+                (true, false) => (
+                    allpairs::load_from_path_with_options(allpairs_file, options),
+                    Vec::new(),
+                ),
+                (true, true) => allpairs::load_lenient_from_path(allpairs_file, options),
+                (false, false) => {
+                    let reader = MaybeGzReader::new(open(allpairs_file)?)?;
+                    (
+                        allpairs::load_from_reader_with_options(reader, options),
+                        Vec::new(),
+                    )
+                }
+                (false, true) => {
+                    let reader = MaybeGzReader::new(open(allpairs_file)?)?;
+                    allpairs::load_lenient_from_reader(reader, options)
+                }
+            };
+            if let Some(first) = skipped.first().and_then(LoadAllpairsError::line_number) {
+                eprintln!(
+                    "skipped {} malformed lines (first at line {})",
+                    skipped.len(),
+                    first
+                );
+            }
+            Ok(ppm_table?)
+        } else if let Some(csv_file) = &self.csv_file {
+            let reader = MaybeGzReader::new(open(csv_file)?)?;
+            Ok(allpairs::load_csv(reader)?)
         } else {
             // Clap guarantees that one of the fields will not be `None`.
             let ppm_table_file = self.ppm_table_file.clone().unwrap();
-            Ok(postcard::from_bytes(&fs::read(ppm_table_file)?)?)
+            let mut bytes = Vec::new();
+            open(&ppm_table_file)?.read_to_end(&mut bytes)?;
+            match ppm_table::io::read_table(bytes.as_slice()) {
+                Err(ReadTableError::NotPpmTable) => {
+                    eprintln!(
+                        "warning: {} is an unframed PPM table file; support for these will be \
+                         removed in a future release, so regenerate it with allpairs-loader",
+                        ppm_table_file.display()
+                    );
+                    read_unframed(&bytes)
+                }
+                result => Ok(result?),
+            }
         }
     }
 }
 
+/// Builds the regex that IDs are extracted from paths with: `id_regex` if given, or else the
+/// directory above `handin_name`.
+fn id_regex(id_regex: Option<&str>, handin_name: &str) -> Result<Regex> {
+    let pattern = match id_regex {
+        Some(pattern) => pattern.to_string(),
+        None => format!(r"^[^/]+/(.+)/{}", regex::escape(handin_name)),
+    };
+    let regex = Regex::new(&pattern).with_context(|| format!("invalid ID regex {}", pattern))?;
+    if regex.captures_len() < 2 {
+        bail!(
+            "the ID regex {} has no capture group to extract IDs with",
+            pattern
+        );
+    }
+    Ok(regex)
+}
+
+/// Parses a percentage between 0 and 100, which may have a fractional part.
+fn parse_percent(s: &str) -> Result<f64, Box<dyn Error + Send + Sync>> {
+    let pct = s.parse()?;
+    ppm::percent_to_ppm(pct)?;
+    Ok(pct)
+}
+
+/// Opens `path` for reading, or stdin if `path` is -.
+fn open(path: &Path) -> Result<Box<dyn BufRead>> {
+    if path.as_os_str() != "-" {
+        return Ok(Box::new(BufReader::new(File::open(path)?)));
+    }
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        bail!("- reads the input from stdin, but stdin is a terminal; pipe the input in instead");
+    }
+    Ok(Box::new(stdin.lock()))
+}
+
+fn read_unframed(bytes: &[u8]) -> Result<PpmTable> {
+    match postcard::from_bytes(bytes) {
+        Ok(ppm_table) => Ok(ppm_table),
+        Err(err) => match postcard::from_bytes::<LegacyPpmTable>(bytes) {
+            Ok(LegacyPpmTable(ppm_table)) => Ok(ppm_table),
+            Err(_) => Err(err.into()),
+        },
+    }
+}
+
 fn main() -> Result<()> {
     let args = Cmd::parse();
 
-    let ppm_limit = args.max_similarity * 10000;
+    let (ppm_limit, step) = args.thresholds()?;
+
+    let mut ids = if args.raw_ids {
+        PathIds::raw()
+    } else {
+        let regex = id_regex(args.id_regex.as_deref(), &args.handin_file_name)?;
+        PathIds::new(regex, args.lenient_ids)
+    };
+
+    // The clique report only looks at pairs up to the limit, so the rest need not be kept. At
+    // 100%, the PPM that dropped pairs are given would be within the limit.
+    let max_ppm = (args.top_pairs.is_none() && ppm_limit < ppm::MAX_PPM).then_some(ppm_limit);
+    let mut ppm_table = args.file.ppm_table(args.skip_malformed, max_ppm)?;
+    let mut unmatched = ids
+        .unmatched((0..ppm_table.len()).filter_map(|i| ppm_table.key_at(i)))
+        .into_iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if !unmatched.is_empty() && unmatched.len() == ppm_table.len() {
+        eprintln!(
+            "warning: the ID regex matches none of the keys, so they are used as IDs as they are; \
+             pass --raw-ids to do this without a warning"
+        );
+        ids = PathIds::raw();
+        unmatched.clear();
+    }
+    unmatched.sort_unstable();
+    // Fail before any of the output is written.
+    if let Some(path) = unmatched.first() {
+        ids.id(path)?;
+    }
 
-    let regex_string = format!(r"^[^/]+/(.+)/{}", args.handin_file_name);
-    let id_from_path = Regex::new(&regex_string).unwrap();
-    let mut files_to_ids = HashMap::new();
+    let drop_within = ppm::percent_to_ppm(args.baseline_drop_within)?;
+    let mut adjusted = Vec::new();
+    if let Some(baseline_file) = &args.baseline {
+        let baseline = Baseline::load(baseline_file, args.starter.as_deref(), &ids)?;
+        (ppm_table, adjusted) = baseline.apply(ppm_table, &ids, drop_within)?;
+        // Only the pairs that would have been in the report are flagged.
+        adjusted.retain(|pair| pair.ppm <= ppm_limit);
+    }
 
-    let ppm_table = args.file.ppm_table()?;
-    let sorted_ppm_table_edges = {
-        let mut edges = ppm_table
-            .edges()
-            .filter(|e| e.2 <= ppm_limit)
-            .collect::<Vec<_>>();
-        edges.sort_by_key(|e| e.2);
-        edges
+    let output: Box<dyn Write> = match &args.output {
+        Some(output) => {
+            Box::new(BufWriter::new(File::create(output).with_context(|| {
+                format!("could not create {}", output.display())
+            })?))
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    let write_context = || match &args.output {
+        Some(output) => format!("could not write to {}", output.display()),
+        None => "could not write to stdout".to_string(),
     };
 
-    let mut max_ppm = 0;
-    let mut prev_cliques = Cliques::new(max_ppm);
-    let mut cliques = Cliques::new(max_ppm);
-    for (l, r, ppm) in sorted_ppm_table_edges {
-        let l_id = files_to_ids
-            .entry(l)
-            .or_insert_with(|| id_from_path.captures(l).unwrap().get(1).unwrap())
-            .as_str();
-        let r_id = files_to_ids
-            .entry(r)
-            .or_insert_with(|| id_from_path.captures(r).unwrap().get(1).unwrap())
-            .as_str();
-
-        while ppm > max_ppm {
-            println!("At {}%", max_ppm / 10000);
-            println!("{}", cliques.export(&prev_cliques));
-            prev_cliques = cliques.clone();
-            max_ppm += 10000;
+    if let Some(k) = args.top_pairs {
+        let top_pairs = ppm_table.top_k(k);
+        write_top_pairs(output, &top_pairs, &ids).with_context(write_context)?;
+        if let Some(output) = &args.output {
+            println!("wrote {} pairs to {}", top_pairs.len(), output.display());
+        }
+        warn_unmatched(&unmatched);
+        return Ok(());
+    }
+
+    let mut excluded = args.exclude.iter().cloned().collect::<BTreeSet<_>>();
+    if let Some(exclude_file) = &args.exclude_file {
+        let contents = fs::read_to_string(exclude_file)
+            .with_context(|| format!("could not read {}", exclude_file.display()))?;
+        excluded.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(str::to_string),
+        );
+    }
+    let table_ids = (0..ppm_table.len())
+        .filter_map(|i| ppm_table.key_at(i))
+        .map(|path| ids.id(path))
+        .collect::<Result<BTreeSet<_>>>()?;
+    for id in &excluded {
+        if !table_ids.contains(id.as_str()) {
+            eprintln!("warning: the excluded ID {} is not in the table", id);
         }
-        cliques.add(l_id, r_id, ppm)
     }
-    println!("At {}%", max_ppm / 10000);
-    println!("{}", cliques.export(&prev_cliques));
+    let options = ReportOptions {
+        step,
+        min_clique_size: args.min_clique_size,
+        excluded,
+        show_edges: args.verbose,
+        ..ReportOptions::new(ppm_limit)
+    };
+
+    if let Some(dot_file) = &args.dot {
+        fs::write(dot_file, ppm_table.to_dot(ppm_limit))
+            .with_context(|| format!("could not write {}", dot_file.display()))?;
+    }
 
+    let cliques = ReportWriter::new(args.output_format, output)
+        .and_then(|mut report| {
+            if args.baseline.is_some() {
+                report.write_baseline(&adjusted, drop_within)?;
+            }
+            let cliques = write_report(&ppm_table, &options, &ids, &mut report)?;
+            report.finish()?;
+            Ok(cliques)
+        })
+        .with_context(write_context)?;
+    if let Some(output) = &args.output {
+        println!(
+            "wrote the clique report up to {}% ({} cliques) to {}",
+            args.max_similarity,
+            cliques,
+            output.display()
+        );
+    }
+    warn_unmatched(&unmatched);
     Ok(())
 }
+
+/// Lists the paths that were their own IDs because of `--lenient-ids`.
+fn warn_unmatched(unmatched: &[String]) {
+    if unmatched.is_empty() {
+        return;
+    }
+    eprintln!(
+        "warning: {} paths do not match the ID regex, so their IDs are the full paths:",
+        unmatched.len()
+    );
+    for path in unmatched {
+        eprintln!("  {}", path);
+    }
+}
+
+fn write_top_pairs(
+    mut writer: impl Write,
+    top_pairs: &[(&str, &str, u32)],
+    ids: &PathIds,
+) -> Result<()> {
+    for &(l, r, ppm) in top_pairs {
+        let l_id = ids.id(l)?;
+        let r_id = ids.id(r)?;
+        writeln!(writer, "[{}, {}] %: {}", l_id, r_id, Ppm(ppm))?;
+    }
+    Ok(writer.flush()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_CONTENTS: &str = concat!(
+        "  2191     23   5260   5236 a2/001/a2.py a2/002/a2.py\n",
+        "  15000    49   5260   5000 a2/001/a2.py a2/003/a2.py\n",
+        "  22320    12   5236   5000 a2/002/a2.py a2/003/a2.py\n",
+        "  32000    12   5236   5000 a2/004/a2.py a2/003/a2.py\n",
+        "  12000    12   5236   5000 a2/004/a2.py a2/005/a2.py\n",
+        "  52000    12   5236   5000 a2/004/a2.py a2/002/a2.py\n",
+        "  72000    12   5236   5000 a2/004/a2.py a2/001/a2.py\n",
+        "  90000    12   5236   5000 a2/005/a2.py a2/001/a2.py\n",
+        "  91000    12   5236   5000 a2/005/a2.py a2/002/a2.py\n",
+        "  92000    12   5236   5000 a2/005/a2.py a2/003/a2.py\n",
+    );
+
+    /// Writes the text report of `FILE_CONTENTS` for the command line `args`, validated as
+    /// `main` validates it.
+    fn report(args: &[&str]) -> String {
+        let args = Cmd::try_parse_from(["cabal", "-"].iter().chain(args)).unwrap();
+        let (ppm_limit, step) = args.thresholds().unwrap();
+        let ppm_table = allpairs::load(FILE_CONTENTS.to_string()).unwrap();
+        let ids = PathIds::new(Regex::new(r"^[^/]+/(.+)/a2.py").unwrap(), false);
+        let mut report = ReportWriter::new(OutputFormat::Text, Vec::new()).unwrap();
+        let options = ReportOptions {
+            step,
+            ..ReportOptions::new(ppm_limit)
+        };
+        write_report(&ppm_table, &options, &ids, &mut report).unwrap();
+        let ReportWriter::Text(written) = report else {
+            unreachable!();
+        };
+        String::from_utf8(written).unwrap()
+    }
+
+    #[test]
+    fn test_max_similarity_integer() {
+        // The output of the integer-only `--max-similarity`, which must not change.
+        let expected = concat!(
+            "At 0%\n\n",
+            "At 1%\n",
+            "New: [001, 002] max%: 0.2\n\n",
+            "At 2%\n",
+            "Old: [002, 001, 003] max%: 1.5\n",
+            "     Added: 003 \n",
+            "New: [004, 005] max%: 1.2\n\n",
+            "At 3%\n",
+            "Old: [004, 005] max%: 1.2\n",
+            "Old: [001, 002, 003] max%: 2.2\n\n",
+            "At 4%\n",
+            "Old: [005, 001, 002, 003, 004] max%: 3.2\n",
+            "     Absorbed 2:\n",
+            "          [004, 005] max%: 1.2\n",
+            "          [001, 002, 003] max%: 2.2\n\n",
+            "At 5%\n",
+            "Old: [005, 001, 002, 003, 004] max%: 3.2\n\n",
+            "At 6%\n",
+            "Old: [005, 001, 002, 003, 004] max%: 5.2\n\n",
+        );
+        assert_eq!(report(&[]), expected);
+        assert_eq!(report(&["--max-similarity", "6"]), expected);
+        assert_eq!(report(&["--max-similarity", "6.0"]), expected);
+    }
+
+    #[test]
+    fn test_max_similarity_fractional() {
+        let fractional = report(&["--max-similarity", "2.5"]);
+        assert!(fractional
+            .ends_with("At 2.5%\nOld: [004, 005] max%: 1.2\nOld: [001, 002, 003] max%: 2.2\n\n"));
+        assert_eq!(
+            report(&["--max-similarity", "2.5", "--step", "2"]),
+            concat!(
+                "At 0%\n\n",
+                "At 2%\nNew: [004, 005] max%: 1.2\nNew: [002, 001, 003] max%: 1.5\n\n",
+                "At 2.5%\nOld: [004, 005] max%: 1.2\nOld: [001, 002, 003] max%: 2.2\n\n",
+            )
+        );
+        assert_eq!(
+            report(&["--max-similarity", "0.5"]),
+            "At 0%\n\nAt 0.5%\nNew: [001, 002] max%: 0.2\n\n"
+        );
+        assert_eq!(report(&["--max-similarity", "0"]), "At 0%\n\n");
+        assert!(Cmd::try_parse_from(["cabal", "-", "--max-similarity", "100.5"]).is_err());
+        assert!(Cmd::try_parse_from(["cabal", "-", "--max-similarity", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_step() {
+        let thresholds = |args: &[&str]| {
+            Cmd::try_parse_from(["cabal", "-"].iter().chain(args))
+                .map_err(anyhow::Error::from)
+                .and_then(|args| args.thresholds())
+        };
+        assert_eq!(thresholds(&[]).unwrap(), (60000, 10000));
+        assert_eq!(thresholds(&["--step", "0.5"]).unwrap(), (60000, 5000));
+        assert_eq!(thresholds(&["--step", "6"]).unwrap(), (60000, 60000));
+        // Without --step, the step shrinks to fit a limit below 1%.
+        assert_eq!(thresholds(&["-m", "0.5"]).unwrap(), (5000, 5000));
+        assert_eq!(thresholds(&["-m", "0"]).unwrap(), (0, 0));
+
+        let err = thresholds(&["--step", "6.5"]).unwrap_err();
+        assert!(
+            err.to_string().contains("at most --max-similarity"),
+            "{}",
+            err
+        );
+        assert!(thresholds(&["--step", "0"]).is_err());
+        assert!(thresholds(&["-m", "0.5", "--step", "1"]).is_err());
+        assert!(thresholds(&["--step", "-1"]).is_err());
+        assert!(thresholds(&["--step", "101"]).is_err());
+    }
+
+    #[test]
+    fn test_id_regex() {
+        let paths = [
+            "submissions/2024w1/alice/3/main.py",
+            "submissions/2024w2/bob/1/main.py",
+        ];
+        let handin_name = PathIds::new(id_regex(None, "main.py").unwrap(), false);
+        assert_eq!(handin_name.id(paths[0]).unwrap(), "2024w1/alice/3");
+
+        let student = id_regex(
+            Some(r"^submissions/[^/]+/([^/]+)/\d+/main\.py$"),
+            "handin.rkt",
+        )
+        .unwrap();
+        let student = PathIds::new(student, false);
+        assert_eq!(student.id(paths[0]).unwrap(), "alice");
+        assert_eq!(student.id(paths[1]).unwrap(), "bob");
+        let err = student.id("other/alice/main.py").unwrap_err();
+        assert!(err.to_string().contains("other/alice/main.py"));
+
+        assert!(id_regex(Some("(unclosed"), "handin.rkt").is_err());
+        let err = id_regex(Some(r"\d+"), "handin.rkt").unwrap_err();
+        assert!(err.to_string().contains("no capture group"));
+        assert!(
+            Cmd::try_parse_from(["cabal", "-", "--id-regex", "(.+)", "--handin-name", "a"])
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,600 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use ppm_table::ppm::{Ppm, PPM_PER_PERCENT};
+use ppm_table::{Edge, PpmTable};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::baseline::AdjustedPair;
+use crate::cliques::{Cliques, CliquesExport};
+use crate::html;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    /// One JSON object per percentage step, each on its own line, after objects listing the
+    /// pairs corrected for a baseline and the excluded IDs if there are any.
+    Json,
+    /// One `threshold,clique_id,member,is_core,max_percent` row per member of each clique at each
+    /// percentage step. The corrected pairs and excluded IDs are noted on stderr instead.
+    Csv,
+    /// A self-contained HTML page with a collapsible section per percentage step.
+    Html,
+}
+
+/// The cliques at one percentage step of the report.
+#[derive(Debug, Serialize)]
+struct Step {
+    max_ppm: u32,
+    max_percent: f64,
+    #[serde(flatten)]
+    cliques: CliquesExport,
+}
+
+/// The IDs that were left out of the report, which the JSON report lists before its steps.
+#[derive(Debug, Serialize)]
+struct Excluded<'a> {
+    excluded: &'a BTreeSet<String>,
+}
+
+/// The pairs that were corrected for the starter code, which the JSON report lists before its
+/// steps.
+#[derive(Debug, Serialize)]
+struct Corrected<'a> {
+    drop_within: u32,
+    drop_within_percent: f64,
+    adjusted_pairs: &'a [AdjustedPair],
+}
+
+/// Writes each percentage step of the clique report in an [`OutputFormat`].
+pub enum ReportWriter<W: Write> {
+    Text(W),
+    Json(W),
+    Csv(Box<csv::Writer<W>>),
+    Html(W),
+}
+
+impl<W: Write> ReportWriter<W> {
+    pub fn new(output_format: OutputFormat, writer: W) -> Result<Self> {
+        Ok(match output_format {
+            OutputFormat::Text => ReportWriter::Text(writer),
+            OutputFormat::Json => ReportWriter::Json(writer),
+            OutputFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(writer);
+                writer.write_record([
+                    "threshold",
+                    "clique_id",
+                    "member",
+                    "is_core",
+                    "max_percent",
+                ])?;
+                ReportWriter::Csv(Box::new(writer))
+            }
+            OutputFormat::Html => {
+                let mut writer = writer;
+                html::write_header(&mut writer)?;
+                ReportWriter::Html(writer)
+            }
+        })
+    }
+
+    /// Notes the IDs that were left out of the report. The CSV report has no room for the note,
+    /// so it goes to stderr.
+    fn write_excluded(&mut self, excluded: &BTreeSet<String>) -> Result<()> {
+        let joined = || {
+            excluded
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            ReportWriter::Text(writer) => {
+                writeln!(writer, "Excluded: {}", joined())?;
+                writeln!(writer)?;
+            }
+            ReportWriter::Json(writer) => {
+                serde_json::to_writer(&mut *writer, &Excluded { excluded })?;
+                writeln!(writer)?;
+            }
+            ReportWriter::Csv(_) => {
+                eprintln!(
+                    "note: the CSV report leaves out the excluded IDs {}",
+                    joined()
+                )
+            }
+            ReportWriter::Html(writer) => html::write_excluded(writer, excluded)?,
+        }
+        Ok(())
+    }
+
+    /// Flags the pairs that were corrected for the starter code's baseline. The CSV report has no
+    /// room for the flags, so they go to stderr.
+    pub fn write_baseline(&mut self, adjusted: &[AdjustedPair], drop_within: u32) -> Result<()> {
+        match self {
+            ReportWriter::Text(writer) => {
+                writeln!(
+                    writer,
+                    "Corrected for the starter code, dropping pairs both within {}% of it:",
+                    Ppm(drop_within).percent()
+                )?;
+                write_adjusted_pairs(&mut *writer, adjusted)?;
+                writeln!(writer)?;
+            }
+            ReportWriter::Json(writer) => {
+                let corrected = Corrected {
+                    drop_within,
+                    drop_within_percent: Ppm(drop_within).percent(),
+                    adjusted_pairs: adjusted,
+                };
+                serde_json::to_writer(&mut *writer, &corrected)?;
+                writeln!(writer)?;
+            }
+            ReportWriter::Csv(_) => {
+                let mut stderr = io::stderr().lock();
+                writeln!(
+                    stderr,
+                    "note: the CSV report is corrected for the starter code, dropping pairs both \
+                     within {}% of it:",
+                    Ppm(drop_within).percent()
+                )?;
+                write_adjusted_pairs(stderr, adjusted)?;
+            }
+            ReportWriter::Html(writer) => html::write_baseline(writer, adjusted, drop_within)?,
+        }
+        Ok(())
+    }
+
+    fn write_step(&mut self, max_ppm: u32, cliques: CliquesExport) -> Result<()> {
+        match self {
+            ReportWriter::Text(writer) => {
+                writeln!(writer, "At {}%", Ppm(max_ppm).percent())?;
+                writeln!(writer, "{}", cliques)?;
+            }
+            ReportWriter::Json(writer) => {
+                let step = Step {
+                    max_ppm,
+                    max_percent: Ppm(max_ppm).percent(),
+                    cliques,
+                };
+                serde_json::to_writer(&mut *writer, &step)?;
+                writeln!(writer)?;
+            }
+            ReportWriter::Csv(writer) => {
+                let threshold = Ppm(max_ppm).percent().to_string();
+                for clique in cliques.cliques().iter().map(|element| element.clique()) {
+                    let id = clique.id().to_string();
+                    let max_percent = Ppm(clique.max_ppm()).percent().to_string();
+                    for member in clique.members() {
+                        let is_core = (member == clique.core()).to_string();
+                        writer.write_record([&threshold, &id, member, &is_core, &max_percent])?;
+                    }
+                }
+            }
+            ReportWriter::Html(writer) => html::write_step(writer, max_ppm, &cliques)?,
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self {
+            ReportWriter::Text(mut writer) | ReportWriter::Json(mut writer) => writer.flush()?,
+            ReportWriter::Csv(mut writer) => writer.flush()?,
+            ReportWriter::Html(mut writer) => {
+                html::write_footer(&mut writer)?;
+                writer.flush()?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a line for each pair that was corrected for the baseline, with its PPM before and after.
+fn write_adjusted_pairs(mut writer: impl Write, adjusted: &[AdjustedPair]) -> Result<()> {
+    for pair in adjusted {
+        write!(
+            writer,
+            "     [{}, {}] {}% -> ",
+            pair.left,
+            pair.right,
+            Ppm(pair.ppm)
+        )?;
+        match pair.adjusted {
+            Some(ppm) => writeln!(writer, "{}%", Ppm(ppm))?,
+            None => writeln!(writer, "dropped")?,
+        }
+    }
+    Ok(())
+}
+
+/// Extracts IDs from paths with the first capture group of a regex, or uses the keys of the table
+/// as IDs as they are.
+#[derive(Clone, Debug)]
+pub struct PathIds {
+    regex: Option<Regex>,
+    lenient: bool,
+}
+
+impl PathIds {
+    /// If `lenient`, a path that does not match `regex` is its own ID instead of an error.
+    pub fn new(regex: Regex, lenient: bool) -> Self {
+        PathIds {
+            regex: Some(regex),
+            lenient,
+        }
+    }
+
+    /// Uses every key as its own ID, e.g. for tables that were keyed by ID when they were built.
+    pub fn raw() -> Self {
+        PathIds {
+            regex: None,
+            lenient: false,
+        }
+    }
+
+    pub fn id<'a>(&self, path: &'a str) -> Result<&'a str> {
+        let Some(regex) = &self.regex else {
+            return Ok(path);
+        };
+        match regex.captures(path).and_then(|captures| captures.get(1)) {
+            Some(id) => Ok(id.as_str()),
+            None if self.lenient => Ok(path),
+            None => bail!("the path {} does not match the ID regex {}", path, regex),
+        }
+    }
+
+    /// The paths that do not match the regex.
+    pub fn unmatched<'a>(&self, paths: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+        let Some(regex) = &self.regex else {
+            return Vec::new();
+        };
+        paths
+            .into_iter()
+            .filter(|path| !regex.is_match(path))
+            .collect()
+    }
+}
+
+/// What the clique report covers.
+#[derive(Clone, Debug)]
+pub struct ReportOptions {
+    /// Pairs above this PPM are left out.
+    pub ppm_limit: u32,
+    /// The PPM between steps of the report.
+    pub step: u32,
+    /// Cliques with fewer members are left out, though they are still tracked so that they have
+    /// a history once they grow.
+    pub min_clique_size: usize,
+    /// Edges involving these IDs are left out.
+    pub excluded: BTreeSet<String>,
+    /// Whether to list the edges within each clique.
+    pub show_edges: bool,
+}
+
+impl ReportOptions {
+    /// Reports every clique up to `ppm_limit` in steps of 1%.
+    pub fn new(ppm_limit: u32) -> Self {
+        ReportOptions {
+            ppm_limit,
+            step: PPM_PER_PERCENT,
+            min_clique_size: 2,
+            excluded: BTreeSet::new(),
+            show_edges: false,
+        }
+    }
+}
+
+/// Writes the cliques at every step of the report, keying the cliques by the IDs that `ids`
+/// extracts from the paths. Returns the number of cliques at the last step.
+pub fn write_report<W: Write>(
+    ppm_table: &PpmTable,
+    options: &ReportOptions,
+    ids: &PathIds,
+    report: &mut ReportWriter<W>,
+) -> Result<usize> {
+    let ReportOptions {
+        ppm_limit,
+        step,
+        min_clique_size,
+        ref excluded,
+        show_edges,
+    } = *options;
+    if !excluded.is_empty() {
+        report.write_excluded(excluded)?;
+    }
+    let mut files_to_ids = HashMap::new();
+    let sorted_ppm_table_edges = ppm_table.edges_sorted_below(ppm_limit);
+
+    let mut max_ppm = 0;
+    let mut cliques = Cliques::new(max_ppm);
+    for edge in sorted_ppm_table_edges.map(Edge::from) {
+        let mut id = |path| match files_to_ids.get(path) {
+            Some(&id) => Ok(id),
+            None => {
+                let id = ids.id(path)?;
+                files_to_ids.insert(path, id);
+                Ok::<_, anyhow::Error>(id)
+            }
+        };
+        let l_id = id(edge.left)?;
+        let r_id = id(edge.right)?;
+
+        while edge.ppm > max_ppm {
+            let mut export = cliques.export_and_snapshot(show_edges);
+            export.retain_min_size(min_clique_size);
+            report.write_step(max_ppm, export)?;
+            max_ppm += step;
+        }
+        if excluded.contains(l_id) || excluded.contains(r_id) {
+            continue;
+        }
+        cliques.add(Edge {
+            left: l_id,
+            right: r_id,
+            ..edge
+        })
+    }
+    let mut export = cliques.export_and_snapshot(show_edges);
+    export.retain_min_size(min_clique_size);
+    let num_cliques = export.cliques().len();
+    // The last step overshoots a limit that is not a multiple of the step, but only has the pairs
+    // up to the limit.
+    report.write_step(max_ppm.min(ppm_limit), export)?;
+    Ok(num_cliques)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_CONTENTS: &str = concat!(
+        "  2191     23   5260   5236 a2/001/a2.py a2/002/a2.py\n",
+        "  15000    49   5260   5000 a2/001/a2.py a2/003/a2.py\n",
+        "  22320    12   5236   5000 a2/002/a2.py a2/003/a2.py\n",
+        "  32000    12   5236   5000 a2/004/a2.py a2/003/a2.py\n",
+        "  12000    12   5236   5000 a2/004/a2.py a2/\"Smith, J\"/a2.py\n",
+        "  52000    12   5236   5000 a2/004/a2.py a2/002/a2.py\n",
+        "  72000    12   5236   5000 a2/004/a2.py a2/001/a2.py\n",
+        "  90000    12   5236   5000 a2/\"Smith, J\"/a2.py a2/001/a2.py\n",
+        "  91000    12   5236   5000 a2/\"Smith, J\"/a2.py a2/002/a2.py\n",
+        "  92000    12   5236   5000 a2/\"Smith, J\"/a2.py a2/003/a2.py\n",
+    );
+
+    #[test]
+    fn test_write_report_csv() {
+        let ppm_table = allpairs::load(FILE_CONTENTS.to_string()).unwrap();
+        let ids = PathIds::new(Regex::new(r"^[^/]+/(.+)/a2.py").unwrap(), false);
+        let mut report = ReportWriter::new(OutputFormat::Csv, Vec::new()).unwrap();
+        write_report(&ppm_table, &ReportOptions::new(30_000), &ids, &mut report).unwrap();
+        let ReportWriter::Csv(writer) = report else {
+            unreachable!();
+        };
+
+        assert_eq!(
+            String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+            concat!(
+                "threshold,clique_id,member,is_core,max_percent\n",
+                "1,0,001,true,0.2191\n",
+                "1,0,002,false,0.2191\n",
+                "2,0,002,true,1.5\n",
+                "2,0,001,false,1.5\n",
+                "2,0,003,false,1.5\n",
+                "2,1,\"\"\"Smith, J\"\"\",true,1.2\n",
+                "2,1,004,false,1.2\n",
+                "3,1,\"\"\"Smith, J\"\"\",true,1.2\n",
+                "3,1,004,false,1.2\n",
+                "3,0,001,true,2.232\n",
+                "3,0,002,false,2.232\n",
+                "3,0,003,false,2.232\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_report_steps() {
+        let ppm_table = allpairs::load(FILE_CONTENTS.to_string()).unwrap();
+        let ids = PathIds::new(Regex::new(r"^[^/]+/(.+)/a2.py").unwrap(), false);
+        let thresholds = |step| {
+            let mut report = ReportWriter::new(OutputFormat::Text, Vec::new()).unwrap();
+            write_report(
+                &ppm_table,
+                &ReportOptions {
+                    step,
+                    ..ReportOptions::new(30_000)
+                },
+                &ids,
+                &mut report,
+            )
+            .unwrap();
+            let ReportWriter::Text(written) = report else {
+                unreachable!();
+            };
+            String::from_utf8(written)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with("At "))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            thresholds(5_000),
+            ["At 0%", "At 0.5%", "At 1%", "At 1.5%", "At 2%", "At 2.5%"]
+        );
+        assert_eq!(thresholds(20_000), ["At 0%", "At 2%", "At 3%"]);
+    }
+
+    #[test]
+    fn test_write_report_unmatched_ids() {
+        let ppm_table = allpairs::load(FILE_CONTENTS.to_string()).unwrap();
+        let regex = Regex::new(r"^[^/]+/(\d+)/a2.py").unwrap();
+
+        let strict = PathIds::new(regex.clone(), false);
+        let mut report = ReportWriter::new(OutputFormat::Csv, Vec::new()).unwrap();
+        let err = write_report(
+            &ppm_table,
+            &ReportOptions::new(30_000),
+            &strict,
+            &mut report,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"the path a2/"Smith, J"/a2.py does not match the ID regex ^[^/]+/(\d+)/a2.py"#
+        );
+
+        let lenient = PathIds::new(regex, true);
+        let mut paths = (0..ppm_table.len()).filter_map(|i| ppm_table.key_at(i));
+        assert_eq!(lenient.unmatched(&mut paths), [r#"a2/"Smith, J"/a2.py"#]);
+        let mut report = ReportWriter::new(OutputFormat::Csv, Vec::new()).unwrap();
+        write_report(
+            &ppm_table,
+            &ReportOptions::new(30_000),
+            &lenient,
+            &mut report,
+        )
+        .unwrap();
+        let ReportWriter::Csv(writer) = report else {
+            unreachable!();
+        };
+        let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(written.contains("2,1,\"a2/\"\"Smith, J\"\"/a2.py\",false,1.2\n"));
+    }
+
+    #[test]
+    fn test_write_report_excluded() {
+        let ppm_table = allpairs::load(
+            concat!(
+                "  1000     23   5260   5236 a2/hub/a2.py a2/001/a2.py\n",
+                "  2000     49   5260   5000 a2/hub/a2.py a2/002/a2.py\n",
+                "  90000    12   5236   5000 a2/001/a2.py a2/002/a2.py\n",
+            )
+            .to_string(),
+        )
+        .unwrap();
+        let ids = PathIds::new(Regex::new(r"^[^/]+/(.+)/a2.py").unwrap(), false);
+        let excluded = BTreeSet::from(["hub".to_string()]);
+        let mut report = ReportWriter::new(OutputFormat::Text, Vec::new()).unwrap();
+        let cliques = write_report(
+            &ppm_table,
+            &ReportOptions {
+                excluded,
+                ..ReportOptions::new(30_000)
+            },
+            &ids,
+            &mut report,
+        )
+        .unwrap();
+        let ReportWriter::Text(written) = report else {
+            unreachable!();
+        };
+
+        assert_eq!(cliques, 0);
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "Excluded: hub\n\nAt 0%\n\nAt 1%\n\n"
+        );
+    }
+
+    #[test]
+    fn test_write_report_excluded_json() {
+        let ppm_table = allpairs::load(FILE_CONTENTS.to_string()).unwrap();
+        let ids = PathIds::new(Regex::new(r"^[^/]+/(.+)/a2.py").unwrap(), false);
+        let excluded = BTreeSet::from(["003".to_string(), "004".to_string()]);
+        let mut report = ReportWriter::new(OutputFormat::Json, Vec::new()).unwrap();
+        write_report(
+            &ppm_table,
+            &ReportOptions {
+                excluded,
+                ..ReportOptions::new(10_000)
+            },
+            &ids,
+            &mut report,
+        )
+        .unwrap();
+        let ReportWriter::Json(written) = report else {
+            unreachable!();
+        };
+
+        let written = String::from_utf8(written).unwrap();
+        let mut lines = written.lines();
+        assert_eq!(lines.next(), Some(r#"{"excluded":["003","004"]}"#));
+        assert!(lines.all(|line| line.starts_with(r#"{"max_ppm":"#)));
+    }
+
+    #[test]
+    fn test_write_baseline_json() {
+        let adjusted = [
+            AdjustedPair {
+                left: "001".to_string(),
+                right: "002".to_string(),
+                ppm: 2191,
+                adjusted: None,
+            },
+            AdjustedPair {
+                left: "001".to_string(),
+                right: "003".to_string(),
+                ppm: 15000,
+                adjusted: Some(25000),
+            },
+        ];
+        let mut report = ReportWriter::new(OutputFormat::Json, Vec::new()).unwrap();
+        report.write_baseline(&adjusted, 10_000).unwrap();
+        let ReportWriter::Json(written) = report else {
+            unreachable!();
+        };
+
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            concat!(
+                r#"{"drop_within":10000,"drop_within_percent":1.0,"adjusted_pairs":["#,
+                r#"{"left":"001","right":"002","ppm":2191,"adjusted":null},"#,
+                r#"{"left":"001","right":"003","ppm":15000,"adjusted":25000}]}"#,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_report_min_clique_size() {
+        let ppm_table = allpairs::load(
+            concat!(
+                "  5000     23   5260   5236 a2/001/a2.py a2/002/a2.py\n",
+                "  25000    49   5260   5000 a2/002/a2.py a2/003/a2.py\n",
+                "  90000    12   5236   5000 a2/001/a2.py a2/003/a2.py\n",
+            )
+            .to_string(),
+        )
+        .unwrap();
+        let ids = PathIds::new(Regex::new(r"^[^/]+/(.+)/a2.py").unwrap(), false);
+        let mut report = ReportWriter::new(OutputFormat::Text, Vec::new()).unwrap();
+        write_report(
+            &ppm_table,
+            &ReportOptions {
+                min_clique_size: 3,
+                ..ReportOptions::new(30_000)
+            },
+            &ids,
+            &mut report,
+        )
+        .unwrap();
+        let ReportWriter::Text(written) = report else {
+            unreachable!();
+        };
+
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            concat!(
+                "At 0%\n\n",
+                "At 1%\n\n",
+                "At 2%\n\n",
+                "At 3%\n",
+                "Old: [001, 002, 003] max%: 2.5\n",
+                "     Added: 003 \n\n",
+            )
+        );
+    }
+}
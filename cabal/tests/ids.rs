@@ -0,0 +1,165 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const FILE_CONTENTS: &str = concat!(
+    "  2191     23   5260   5236 a2/001/a2.py a2/002/a2.py\n",
+    "  15000    49   5260   5000 a2/001/a2.py a2/003/a2.py\n",
+    "  22320    12   5236   5000 a2/002/a2.py a2/003/a2.py\n",
+);
+
+/// Writes the fixture as an allpairs file, a PPM table keyed by path, and a PPM table keyed by
+/// ID, in that order.
+fn fixtures(name: &str) -> [PathBuf; 3] {
+    let dir = std::env::temp_dir().join(format!("cabal-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let allpairs_file = dir.join("allpairs.txt");
+    fs::write(&allpairs_file, FILE_CONTENTS).unwrap();
+
+    let by_path = allpairs::load(FILE_CONTENTS.to_string()).unwrap();
+    let by_path_file = dir.join("by-path.ppm");
+    ppm_table::io::write_table(File::create(&by_path_file).unwrap(), &by_path).unwrap();
+
+    let mut builder = ppm_table::PpmTableBuilder::default();
+    for (l, r, ppm) in by_path.edges() {
+        let id = |path: &str| path.split('/').nth(1).unwrap().to_string();
+        builder.add_ppm(id(l), id(r), ppm);
+    }
+    let by_id_file = dir.join("by-id.ppm");
+    let by_id = builder.build().unwrap();
+    ppm_table::io::write_table(File::create(&by_id_file).unwrap(), &by_id).unwrap();
+
+    [allpairs_file, by_path_file, by_id_file]
+}
+
+fn cabal(args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_cabal"))
+        .args(["--max-similarity", "2"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn test_ids_allpairs() {
+    let [allpairs_file, _, _] = fixtures("ids-allpairs");
+    let allpairs_file = allpairs_file.to_str().unwrap();
+
+    let extracted = cabal(&[allpairs_file, "--handin-name", "a2.py"]);
+    assert!(stdout(&extracted).contains("Old: [002, 001, 003] max%: 1.5"));
+    assert!(extracted.stderr.is_empty());
+
+    let raw = cabal(&[allpairs_file, "--raw-ids"]);
+    assert!(stdout(&raw).contains("Old: [a2/002/a2.py, a2/001/a2.py, a2/003/a2.py] max%: 1.5"));
+    assert!(raw.stderr.is_empty());
+
+    fs::remove_dir_all(PathBuf::from(allpairs_file).parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_ids_truncated_allpairs() {
+    let [allpairs_file, _, _] = fixtures("ids-truncated-allpairs");
+    let truncated = FILE_CONTENTS.lines().take(2).collect::<Vec<_>>().join("\n");
+    fs::write(&allpairs_file, truncated).unwrap();
+
+    // At the default --max-similarity, pairs above it are dropped while loading, which must not
+    // hide a pair that is missing from the file altogether.
+    let output = Command::new(env!("CARGO_BIN_EXE_cabal"))
+        .args([allpairs_file.to_str().unwrap(), "--handin-name", "a2.py"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("missing 1 pairs, e.g. (a2/002/a2.py, a2/003/a2.py)"),
+        "{}",
+        stderr
+    );
+
+    fs::remove_dir_all(allpairs_file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_ids_excluded_csv() {
+    let [allpairs_file, _, _] = fixtures("ids-excluded-csv");
+    let allpairs_file = allpairs_file.to_str().unwrap();
+
+    let args = [
+        allpairs_file,
+        "--handin-name",
+        "a2.py",
+        "--output-format",
+        "csv",
+    ];
+    let excluded = cabal(&[&args[..], &["--exclude", "003", "--exclude", "001"]].concat());
+    assert!(!stdout(&excluded).contains("003"));
+    assert_eq!(
+        String::from_utf8_lossy(&excluded.stderr),
+        "note: the CSV report leaves out the excluded IDs 001, 003\n"
+    );
+    assert!(cabal(&args).stderr.is_empty());
+
+    fs::remove_dir_all(PathBuf::from(allpairs_file).parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_ids_baseline_csv() {
+    let [allpairs_file, _, _] = fixtures("ids-baseline-csv");
+    let baseline_file = allpairs_file.with_file_name("baseline.txt");
+    fs::write(&baseline_file, "001 5000\n002 5000\n003 900000\n").unwrap();
+    let allpairs_file = allpairs_file.to_str().unwrap();
+
+    let corrected = cabal(&[
+        allpairs_file,
+        "--handin-name",
+        "a2.py",
+        "--output-format",
+        "csv",
+        "--baseline",
+        baseline_file.to_str().unwrap(),
+    ]);
+    assert!(stdout(&corrected).starts_with("threshold,clique_id,member,is_core,max_percent\n"));
+    let stderr = String::from_utf8_lossy(&corrected.stderr);
+    assert!(
+        stderr.starts_with(
+            "note: the CSV report is corrected for the starter code, dropping pairs both within \
+             1% of it:\n"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("[001, 002] 0.2% -> dropped"), "{}", stderr);
+
+    fs::remove_dir_all(baseline_file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_ids_ppm_table() {
+    let [_, by_path_file, by_id_file] = fixtures("ids-ppm-table");
+    let by_path_file = by_path_file.to_str().unwrap();
+    let by_id_file = by_id_file.to_str().unwrap();
+
+    let extracted = cabal(&["--ppm-table", by_path_file, "--handin-name", "a2.py"]);
+    assert!(stdout(&extracted).contains("Old: [002, 001, 003] max%: 1.5"));
+
+    let raw = cabal(&["--ppm-table", by_id_file, "--raw-ids"]);
+    assert_eq!(stdout(&raw), stdout(&extracted));
+    assert!(raw.stderr.is_empty());
+
+    // The handin name matches none of the IDs, so they are assumed to be raw.
+    let detected = cabal(&["--ppm-table", by_id_file, "--handin-name", "a2.py"]);
+    assert_eq!(stdout(&detected), stdout(&extracted));
+    assert!(String::from_utf8_lossy(&detected.stderr).contains("--raw-ids"));
+
+    fs::remove_dir_all(PathBuf::from(by_id_file).parent().unwrap()).unwrap();
+}
@@ -0,0 +1,45 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use ppm_table::{PpmTable, PpmTableBuilder};
+
+fn synthetic_table(n: usize) -> (PpmTable, Vec<String>) {
+    let keys = (0..n).map(|i| format!("{:04}", i)).collect::<Vec<_>>();
+    let mut builder = PpmTableBuilder::default();
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let ppm = ((i * 7919 + j * 104729) % 1_000_000) as u32;
+            builder.add_ppm(keys[i].clone(), keys[j].clone(), ppm);
+        }
+    }
+    (builder.build().expect("Table should be buildable."), keys)
+}
+
+fn bench_ppm_table(c: &mut Criterion) {
+    let (table, keys) = synthetic_table(1000);
+
+    c.bench_function("ppm", |b| {
+        let mut i = 0;
+        b.iter(|| {
+            i = (i + 1) % keys.len();
+            let j = (i * 31 + 7) % keys.len();
+            black_box(table.ppm(&keys[i], &keys[j]))
+        })
+    });
+
+    c.bench_function("get_ppm_by_index", |b| {
+        let mut i = 0;
+        b.iter(|| {
+            i = (i + 1) % keys.len();
+            let j = (i * 31 + 7) % keys.len();
+            black_box(table.get_ppm_by_index(i, j))
+        })
+    });
+
+    c.bench_function("edges", |b| {
+        b.iter(|| black_box(table.edges().map(|(_, _, ppm)| ppm as u64).sum::<u64>()))
+    });
+}
+
+criterion_group!(benches, bench_ppm_table);
+criterion_main!(benches);
@@ -0,0 +1,148 @@
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::hash::BuildHasher;
+
+use bimap::BiHashMap;
+use proptest::collection::{btree_set, vec};
+use proptest::prelude::*;
+
+use crate::PpmTable;
+
+/// Bounds on the tables generated by [`PpmTable`]'s [`Arbitrary`] implementation.
+#[derive(Clone, Copy, Debug)]
+pub struct TableParameters {
+    pub max_keys: usize,
+}
+
+impl Default for TableParameters {
+    fn default() -> Self {
+        TableParameters { max_keys: 16 }
+    }
+}
+
+impl<V, S> Arbitrary for PpmTable<V, S>
+where
+    V: Arbitrary + Copy + 'static,
+    S: BuildHasher + Default + Debug + 'static,
+{
+    type Parameters = TableParameters;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(params: Self::Parameters) -> Self::Strategy {
+        btree_set("[a-z0-9/]{1,8}", 0..=params.max_keys)
+            .prop_flat_map(|keys| {
+                let pairs = keys.len() * keys.len().saturating_sub(1) / 2;
+                (Just(keys), vec(any::<V>(), pairs))
+            })
+            .prop_map(|(keys, ppm_table): (BTreeSet<String>, Vec<V>)| {
+                let mut indices = BiHashMap::with_capacity_and_hashers(
+                    keys.len(),
+                    Default::default(),
+                    Default::default(),
+                );
+                for (i, key) in keys.into_iter().enumerate() {
+                    indices.insert(key, i);
+                }
+                PpmTable { ppm_table, indices }
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::PpmTable;
+
+    /// The connected components of the graph of edges with a PPM of at most `max_ppm`, found by
+    /// depth-first search.
+    fn components_below(table: &PpmTable, max_ppm: u32) -> Vec<Vec<&str>> {
+        let mut adjacent = vec![Vec::new(); table.len()];
+        for (l, r, _) in table.sparse_edges_below(max_ppm) {
+            adjacent[l].push(r);
+            adjacent[r].push(l);
+        }
+
+        let mut seen = vec![false; table.len()];
+        let mut components = Vec::new();
+        for start in 0..table.len() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut stack = vec![start];
+            let mut component = Vec::new();
+            while let Some(i) = stack.pop() {
+                component.push(table.key_at(i).unwrap());
+                for &j in &adjacent[i] {
+                    if !seen[j] {
+                        seen[j] = true;
+                        stack.push(j);
+                    }
+                }
+            }
+            component.sort_unstable();
+            components.push(component);
+        }
+        components.sort_unstable();
+        components
+    }
+
+    proptest! {
+        #[test]
+        fn test_arbitrary_table_shape(table in any::<PpmTable>()) {
+            let n = table.len();
+            prop_assert_eq!(table.check_invariants(), Ok(()));
+            prop_assert_eq!(table.edges().count(), n * n.saturating_sub(1) / 2);
+            prop_assert_eq!(table.edges().count(), table.num_edges());
+        }
+
+        #[test]
+        fn test_arbitrary_table_builder_round_trip(table in any::<PpmTable>()) {
+            let rebuilt = table.clone().into_builder().build();
+            prop_assert_eq!(rebuilt.ok(), Some(table));
+        }
+
+        #[test]
+        fn test_arbitrary_table_minimum_spanning_tree(table in any::<PpmTable>()) {
+            // Kruskal's algorithm, which Prim's must agree with on the total weight.
+            let mut components = (0..table.len()).collect::<Vec<_>>();
+            fn find(components: &mut [usize], mut i: usize) -> usize {
+                while components[i] != i {
+                    i = components[i];
+                }
+                i
+            }
+            let mut kruskal_weight = 0_u64;
+            for (l, r, ppm) in table.edges_sorted() {
+                let l = find(&mut components, table.index_of(l).unwrap());
+                let r = find(&mut components, table.index_of(r).unwrap());
+                if l != r {
+                    components[l] = r;
+                    kruskal_weight += ppm as u64;
+                }
+            }
+
+            let tree = table.minimum_spanning_tree();
+            prop_assert_eq!(tree.len(), table.len().saturating_sub(1));
+            prop_assert_eq!(tree.iter().map(|&(_, _, ppm)| ppm as u64).sum::<u64>(), kruskal_weight);
+        }
+
+        #[test]
+        fn test_arbitrary_table_clusters_at(table in any::<PpmTable>(), max_ppm in any::<u32>()) {
+            let dendrogram = table.single_linkage();
+            let thresholds = dendrogram.merges().iter().map(|merge| merge.ppm);
+            for max_ppm in thresholds.chain([0, max_ppm]) {
+                prop_assert_eq!(dendrogram.clusters_at(max_ppm), components_below(&table, max_ppm));
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_arbitrary_table_postcard_round_trip(table in any::<PpmTable>()) {
+            let bytes = postcard::to_stdvec(&table).unwrap();
+            prop_assert_eq!(postcard::from_bytes::<PpmTable>(&bytes).unwrap(), table);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::hash::{BuildHasher, RandomState};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use crate::{BuildError, DuplicatePolicy, PpmTable, PpmTableBuilder};
+
+/// A [`PpmTableBuilder`] that many threads can add PPMs to at once.
+///
+/// Pairs are sharded by the hash of their smaller key, each shard behind its own lock, so threads
+/// only contend when they add pairs that land in the same shard. Every PPM for a given pair goes
+/// to the same shard, so duplicates are resolved just as a single builder would resolve them.
+#[derive(Debug)]
+pub struct ConcurrentPpmTableBuilder<V = u32, S: BuildHasher + Default = RandomState> {
+    shards: Vec<Mutex<PpmTableBuilder<V, S>>>,
+    hasher: S,
+}
+
+impl<V: Copy, S: BuildHasher + Default> ConcurrentPpmTableBuilder<V, S> {
+    /// Creates a builder with four shards per available thread.
+    pub fn new() -> Self {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        Self::with_shards(4 * threads)
+    }
+
+    pub fn with_shards(shards: usize) -> Self {
+        let shards = (0..shards.max(1))
+            .map(|_| Mutex::new(PpmTableBuilder::new()))
+            .collect();
+        ConcurrentPpmTableBuilder {
+            shards,
+            hasher: S::default(),
+        }
+    }
+
+    /// Adds a PPM as with [`PpmTableBuilder::add_ppm`].
+    pub fn add_ppm(&self, l: String, r: String, ppm: V) {
+        self.shard(&l, &r).add_ppm(l, r, ppm);
+    }
+
+    /// Combines the shards into a single builder.
+    pub fn into_builder(self) -> PpmTableBuilder<V, S> {
+        let mut shards = self
+            .shards
+            .into_iter()
+            .map(|shard| shard.into_inner().unwrap_or_else(PoisonError::into_inner));
+        let mut builder = shards.next().expect("There is always at least one shard.");
+        for shard in shards {
+            // No two shards share a smaller key, so their pairs are disjoint.
+            builder.ppms.extend(shard.ppms);
+            builder.keys.extend(shard.keys);
+            builder.conflicts.extend(shard.conflicts);
+        }
+        builder
+    }
+
+    pub fn build(self) -> Result<PpmTable<V, S>, BuildError<V, S>> {
+        self.into_builder().build()
+    }
+
+    fn shard(&self, l: &str, r: &str) -> MutexGuard<'_, PpmTableBuilder<V, S>> {
+        let smaller = l.min(r);
+        let shard = self.hasher.hash_one(smaller) as usize % self.shards.len();
+        self.shards[shard]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<V: Copy + PartialOrd, S: BuildHasher + Default> ConcurrentPpmTableBuilder<V, S> {
+    /// Resolves duplicates as with [`PpmTableBuilder::with_duplicate_policy`].
+    pub fn with_duplicate_policy(self, policy: DuplicatePolicy) -> Self {
+        let shards = self
+            .shards
+            .into_iter()
+            .map(|shard| {
+                let shard = shard.into_inner().unwrap_or_else(PoisonError::into_inner);
+                Mutex::new(shard.with_duplicate_policy(policy))
+            })
+            .collect();
+        ConcurrentPpmTableBuilder {
+            shards,
+            hasher: self.hasher,
+        }
+    }
+}
+
+impl Default for ConcurrentPpmTableBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_builder_matches_serial() {
+        const KEYS: u32 = 800;
+        const THREADS: u32 = 8;
+
+        let ppm = |i: u32, j: u32| i.wrapping_mul(2_654_435_761) ^ j.wrapping_mul(40_503);
+        let concurrent = ConcurrentPpmTableBuilder::default();
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let concurrent = &concurrent;
+                scope.spawn(move || {
+                    for i in (thread..KEYS).step_by(THREADS as usize) {
+                        for j in i + 1..KEYS {
+                            // Alternate the order of the keys to exercise normalization.
+                            let (l, r) = if (i + j) % 2 == 0 { (i, j) } else { (j, i) };
+                            concurrent.add_ppm(format!("{:04}", l), format!("{:04}", r), ppm(i, j));
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut serial = PpmTableBuilder::default();
+        for i in 0..KEYS {
+            for j in i + 1..KEYS {
+                serial.add_ppm(format!("{:04}", i), format!("{:04}", j), ppm(i, j));
+            }
+        }
+
+        let table = concurrent.build().expect("Table should be buildable.");
+        assert_eq!(table.num_edges(), (KEYS * (KEYS - 1) / 2) as usize);
+        assert_eq!(table, serial.build().expect("Table should be buildable."));
+    }
+
+    #[test]
+    fn test_concurrent_builder_duplicate_policy() {
+        let concurrent =
+            ConcurrentPpmTableBuilder::with_shards(3).with_duplicate_policy(DuplicatePolicy::Error);
+        concurrent.add_ppm("a".to_string(), "b".to_string(), 10);
+        concurrent.add_ppm("b".to_string(), "a".to_string(), 20);
+        concurrent.add_ppm("a".to_string(), "c".to_string(), 5);
+        concurrent.add_ppm("c".to_string(), "b".to_string(), 7);
+
+        let builder: PpmTableBuilder = concurrent.into_builder();
+        assert_eq!(
+            builder.conflicting_pairs(),
+            [("a".to_string(), "b".to_string())]
+        );
+        assert_eq!(builder.num_keys(), 3);
+        assert_eq!(builder.num_pairs(), 3);
+    }
+}
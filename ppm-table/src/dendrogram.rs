@@ -0,0 +1,161 @@
+/// The result of [`PpmTable::single_linkage`](crate::PpmTable::single_linkage).
+///
+/// Clusters are numbered like SciPy's linkage matrices: cluster `i` for `i < keys().len()` is the
+/// singleton of `keys()[i]`, and cluster `keys().len() + i` is the one formed by `merges()[i]`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dendrogram<'a, V = u32> {
+    keys: Vec<&'a str>,
+    merges: Vec<Merge<V>>,
+}
+
+/// Two clusters joining at `ppm` into a cluster of `size` keys.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Merge<V = u32> {
+    pub left: usize,
+    pub right: usize,
+    pub ppm: V,
+    pub size: usize,
+}
+
+impl<'a, V: Copy + Ord> Dendrogram<'a, V> {
+    /// Builds the dendrogram from a minimum spanning tree over the indices of `keys`, sorted by
+    /// PPM.
+    pub(crate) fn new(keys: Vec<&'a str>, tree: Vec<(usize, usize, V)>) -> Self {
+        let n = keys.len();
+        let mut parents = (0..n).collect::<Vec<_>>();
+        let mut clusters = (0..n).collect::<Vec<_>>();
+        let mut sizes = vec![1; n];
+        let mut merges = Vec::with_capacity(tree.len());
+        for (l, r, ppm) in tree {
+            let (l, r) = (find(&mut parents, l), find(&mut parents, r));
+            let size = sizes[l] + sizes[r];
+            merges.push(Merge {
+                left: clusters[l].min(clusters[r]),
+                right: clusters[l].max(clusters[r]),
+                ppm,
+                size,
+            });
+            parents[r] = l;
+            sizes[l] = size;
+            clusters[l] = n + merges.len() - 1;
+        }
+        Dendrogram { keys, merges }
+    }
+
+    /// The keys of the table, in index order.
+    pub fn keys(&self) -> &[&'a str] {
+        &self.keys
+    }
+
+    /// The merges in ascending PPM order.
+    pub fn merges(&self) -> &[Merge<V>] {
+        &self.merges
+    }
+
+    /// Partitions the keys into the clusters formed by merges at or below `max_ppm`, which are the
+    /// connected components of the graph of edges whose PPM is at most `max_ppm`. Keys that have
+    /// not merged with anything are returned as singletons. Each cluster is sorted, and the
+    /// clusters are sorted by their first key.
+    pub fn clusters_at(&self, max_ppm: V) -> Vec<Vec<&'a str>> {
+        let n = self.keys.len();
+        let merged = self.merges.partition_point(|merge| merge.ppm <= max_ppm);
+        let mut parents = (0..n + merged).collect::<Vec<_>>();
+        for (i, merge) in self.merges[..merged].iter().enumerate() {
+            parents[merge.left] = n + i;
+            parents[merge.right] = n + i;
+        }
+
+        let mut clusters = vec![Vec::new(); n + merged];
+        for (i, &key) in self.keys.iter().enumerate() {
+            clusters[find(&mut parents, i)].push(key);
+        }
+        let mut clusters = clusters
+            .into_iter()
+            .filter(|cluster| !cluster.is_empty())
+            .collect::<Vec<_>>();
+        for cluster in &mut clusters {
+            cluster.sort_unstable();
+        }
+        clusters.sort_unstable();
+        clusters
+    }
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PpmTableBuilder;
+
+    #[test]
+    fn test_dendrogram_single_linkage() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        builder.add_ppm("a".to_string(), "d".to_string(), 5);
+        builder.add_ppm("b".to_string(), "c".to_string(), 14);
+        builder.add_ppm("b".to_string(), "d".to_string(), 30);
+        builder.add_ppm("c".to_string(), "d".to_string(), 14);
+        let table = builder.build().expect("Table should be buildable.");
+        let dendrogram = table.single_linkage();
+
+        assert_eq!(dendrogram.keys(), ["a", "b", "c", "d"]);
+        assert_eq!(
+            dendrogram.merges(),
+            [
+                Merge {
+                    left: 0,
+                    right: 3,
+                    ppm: 5,
+                    size: 2
+                },
+                Merge {
+                    left: 1,
+                    right: 4,
+                    ppm: 10,
+                    size: 3
+                },
+                Merge {
+                    left: 2,
+                    right: 5,
+                    ppm: 14,
+                    size: 4
+                },
+            ]
+        );
+
+        assert_eq!(
+            dendrogram.clusters_at(4),
+            vec![vec!["a"], vec!["b"], vec!["c"], vec!["d"]]
+        );
+        assert_eq!(
+            dendrogram.clusters_at(5),
+            vec![vec!["a", "d"], vec!["b"], vec!["c"]]
+        );
+        assert_eq!(
+            dendrogram.clusters_at(13),
+            vec![vec!["a", "b", "d"], vec!["c"]]
+        );
+        assert_eq!(
+            dendrogram.clusters_at(u32::MAX),
+            vec![vec!["a", "b", "c", "d"]]
+        );
+    }
+
+    #[test]
+    fn test_dendrogram_empty() {
+        let table = PpmTableBuilder::default()
+            .build()
+            .expect("Table should be buildable.");
+        let dendrogram = table.single_linkage();
+        assert!(dendrogram.merges().is_empty());
+        assert!(dendrogram.clusters_at(0).is_empty());
+    }
+}
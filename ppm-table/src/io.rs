@@ -0,0 +1,483 @@
+use std::hash::{BuildHasher, RandomState};
+use std::io::{Read, Write};
+
+use bimap::BiHashMap;
+use serde::de::value::U32Deserializer;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{InvariantError, PpmTable, PpmTableBuilder};
+
+/// The bytes at the start of every framed PPM table file with an uncompressed payload.
+pub const MAGIC: &[u8; 4] = b"PPMT";
+
+/// The bytes at the start of every framed PPM table file with a zstd-compressed payload.
+pub const COMPRESSED_MAGIC: &[u8; 4] = b"PPMZ";
+
+/// The bytes at the start of every framed PPM table file written by [`write_table_compact`].
+pub const COMPACT_MAGIC: &[u8; 4] = b"PPMC";
+
+/// The bytes at the start of every checkpoint file written by [`write_checkpoint`].
+pub const CHECKPOINT_MAGIC: &[u8; 4] = b"PPMK";
+
+/// The version of the framed format written by [`write_table`].
+pub const FORMAT_VERSION: u8 = 1;
+
+#[derive(Debug, Error)]
+pub enum ReadTableError {
+    #[error("The file is not a PPM table file.")]
+    NotPpmTable,
+    #[error("The file is not a PPM table builder checkpoint.")]
+    NotCheckpoint,
+    #[error("The PPM table file has unsupported format version {0}.")]
+    UnsupportedVersion(u8),
+    #[error("The PPM table file is compressed, but compression support is not enabled.")]
+    CompressionUnsupported,
+    #[error("The PPM table file could not be decompressed: {0}")]
+    Decompress(#[source] std::io::Error),
+    #[error("The PPM table file is corrupt: {0}")]
+    CorruptPayload(#[source] postcard::Error),
+    #[error("The PPM table file stores PPMs with unsupported width {0}.")]
+    UnsupportedWidth(u8),
+    #[error("The PPM table file holds an invalid table: {0}")]
+    InvalidTable(#[from] InvariantError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum WriteTableError {
+    #[error("The PPM table could not be encoded: {0}")]
+    Encode(#[source] postcard::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Writes `table` as [`MAGIC`], then [`FORMAT_VERSION`], then the table encoded with postcard.
+pub fn write_table<V: Serialize + Copy, S: BuildHasher + Default>(
+    mut writer: impl Write,
+    table: &PpmTable<V, S>,
+) -> Result<(), WriteTableError> {
+    let payload = postcard::to_stdvec(table).map_err(WriteTableError::Encode)?;
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[FORMAT_VERSION])?;
+    writer.write_all(&payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Like [`write_table`], but compresses the payload with zstd at the given level, where 0 selects
+/// zstd's default. [`read_table`] reads either kind of file.
+#[cfg(feature = "compress")]
+pub fn write_table_compressed<V: Serialize + Copy, S: BuildHasher + Default>(
+    mut writer: impl Write,
+    table: &PpmTable<V, S>,
+    level: i32,
+) -> Result<(), WriteTableError> {
+    let payload = postcard::to_stdvec(table).map_err(WriteTableError::Encode)?;
+    writer.write_all(COMPRESSED_MAGIC)?;
+    writer.write_all(&[FORMAT_VERSION])?;
+    zstd::stream::copy_encode(payload.as_slice(), &mut writer, level)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Like [`write_table`], but stores each PPM as a fixed-width little-endian integer after the keys:
+/// two bytes wide if every PPM fits in a `u16`, and four otherwise. The width is written after
+/// [`FORMAT_VERSION`]. When most PPMs are below 65,536 but above 16,383, this takes about two
+/// thirds of the space of postcard's variable-length integers.
+pub fn write_table_compact<S: BuildHasher + Default>(
+    mut writer: impl Write,
+    table: &PpmTable<u32, S>,
+) -> Result<(), WriteTableError> {
+    let keys = (0..table.len())
+        .map(|i| table.str_from_table_index(i))
+        .collect::<Vec<_>>();
+    let keys = postcard::to_stdvec(&keys).map_err(WriteTableError::Encode)?;
+    let fits_u16 = table.ppm_table.iter().all(|&ppm| ppm <= u16::MAX as u32);
+    let width = if fits_u16 { 2 } else { 4 };
+
+    let mut ppms = Vec::with_capacity(width * table.ppm_table.len());
+    for &ppm in &table.ppm_table {
+        if fits_u16 {
+            ppms.extend_from_slice(&(ppm as u16).to_le_bytes());
+        } else {
+            ppms.extend_from_slice(&ppm.to_le_bytes());
+        }
+    }
+
+    writer.write_all(COMPACT_MAGIC)?;
+    writer.write_all(&[FORMAT_VERSION, width as u8])?;
+    writer.write_all(&keys)?;
+    writer.write_all(&ppms)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a table written by [`write_table`] or [`write_table_compact`], or by
+/// `write_table_compressed` if the `compress` feature is enabled.
+pub fn read_table<V, S>(mut reader: impl Read) -> Result<PpmTable<V, S>, ReadTableError>
+where
+    V: for<'de> Deserialize<'de> + Copy,
+    S: BuildHasher + Default,
+{
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let Some((magic, payload)) = bytes.split_first_chunk::<4>() else {
+        return Err(ReadTableError::NotPpmTable);
+    };
+    if ![MAGIC, COMPRESSED_MAGIC, COMPACT_MAGIC].contains(&magic) {
+        return Err(ReadTableError::NotPpmTable);
+    }
+    let (&version, payload) = payload.split_first().ok_or(ReadTableError::NotPpmTable)?;
+    if version != FORMAT_VERSION {
+        return Err(ReadTableError::UnsupportedVersion(version));
+    }
+
+    if magic == COMPRESSED_MAGIC {
+        let payload = decompress(payload)?;
+        postcard::from_bytes(&payload).map_err(ReadTableError::CorruptPayload)
+    } else if magic == COMPACT_MAGIC {
+        read_compact(payload)
+    } else {
+        postcard::from_bytes(payload).map_err(ReadTableError::CorruptPayload)
+    }
+}
+
+/// A builder saved partway through reading some input, so that reading can resume from where it
+/// left off.
+#[derive(Clone, Debug)]
+pub struct Checkpoint<V = u32, S: BuildHasher + Default = RandomState> {
+    pub builder: PpmTableBuilder<V, S>,
+    /// The number of bytes of the input that had been read.
+    pub offset: u64,
+    /// The number of lines of the input that had been read.
+    pub lines: u64,
+    /// The number of columns in the first line of the input and that line's number, once it had
+    /// been read, for inputs whose lines must all have as many columns as the first.
+    pub first_line: Option<(u64, u64)>,
+    /// The number of malformed lines that had been skipped and the number of the first of them,
+    /// if any had been.
+    pub skipped: Option<(u64, u64)>,
+}
+
+/// Writes `checkpoint` as [`CHECKPOINT_MAGIC`], then [`FORMAT_VERSION`], then the checkpoint
+/// encoded with postcard. As with serializing a builder, its duplicate policy is not saved.
+pub fn write_checkpoint<V: Serialize, S: BuildHasher + Default>(
+    mut writer: impl Write,
+    checkpoint: &Checkpoint<V, S>,
+) -> Result<(), WriteTableError> {
+    let payload = postcard::to_stdvec(&(
+        checkpoint.offset,
+        checkpoint.lines,
+        checkpoint.first_line,
+        checkpoint.skipped,
+        &checkpoint.builder,
+    ))
+    .map_err(WriteTableError::Encode)?;
+    writer.write_all(CHECKPOINT_MAGIC)?;
+    writer.write_all(&[FORMAT_VERSION])?;
+    writer.write_all(&payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a checkpoint written by [`write_checkpoint`].
+pub fn read_checkpoint<V, S>(mut reader: impl Read) -> Result<Checkpoint<V, S>, ReadTableError>
+where
+    V: for<'de> Deserialize<'de> + Copy,
+    S: BuildHasher + Default,
+{
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let Some((CHECKPOINT_MAGIC, payload)) = bytes.split_first_chunk::<4>() else {
+        return Err(ReadTableError::NotCheckpoint);
+    };
+    let (&version, payload) = payload.split_first().ok_or(ReadTableError::NotCheckpoint)?;
+    if version != FORMAT_VERSION {
+        return Err(ReadTableError::UnsupportedVersion(version));
+    }
+    let (offset, lines, first_line, skipped, builder) =
+        postcard::from_bytes(payload).map_err(ReadTableError::CorruptPayload)?;
+    Ok(Checkpoint {
+        builder,
+        offset,
+        lines,
+        first_line,
+        skipped,
+    })
+}
+
+fn read_compact<V, S>(payload: &[u8]) -> Result<PpmTable<V, S>, ReadTableError>
+where
+    V: for<'de> Deserialize<'de> + Copy,
+    S: BuildHasher + Default,
+{
+    let truncated = || ReadTableError::CorruptPayload(postcard::Error::DeserializeUnexpectedEnd);
+    let (&width, payload) = payload.split_first().ok_or_else(truncated)?;
+    let (keys, ppms) = postcard::take_from_bytes::<Vec<String>>(payload)
+        .map_err(ReadTableError::CorruptPayload)?;
+
+    let ppms = match width {
+        2 => ppms
+            .chunks(2)
+            .map(|ppm| Some(u16::from_le_bytes(ppm.try_into().ok()?) as u32))
+            .collect::<Option<Vec<_>>>(),
+        4 => ppms
+            .chunks(4)
+            .map(|ppm| Some(u32::from_le_bytes(ppm.try_into().ok()?)))
+            .collect::<Option<Vec<_>>>(),
+        _ => return Err(ReadTableError::UnsupportedWidth(width)),
+    }
+    .ok_or_else(truncated)?;
+    // Deserializing from a `u32` widens it to `V`, or fails if `V` cannot hold it.
+    let ppm_table = ppms
+        .into_iter()
+        .map(|ppm| V::deserialize(U32Deserializer::<serde::de::value::Error>::new(ppm)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ReadTableError::CorruptPayload(postcard::Error::SerdeDeCustom))?;
+
+    let mut indices =
+        BiHashMap::with_capacity_and_hashers(keys.len(), Default::default(), Default::default());
+    for (i, key) in keys.into_iter().enumerate() {
+        indices.insert(key, i);
+    }
+    let table = PpmTable { ppm_table, indices };
+    table.check_invariants()?;
+    Ok(table)
+}
+
+#[cfg(feature = "compress")]
+fn decompress(payload: &[u8]) -> Result<Vec<u8>, ReadTableError> {
+    zstd::stream::decode_all(payload).map_err(ReadTableError::Decompress)
+}
+
+#[cfg(not(feature = "compress"))]
+fn decompress(_payload: &[u8]) -> Result<Vec<u8>, ReadTableError> {
+    Err(ReadTableError::CompressionUnsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::hash::RandomState;
+
+    use super::*;
+    use crate::PpmTableBuilder;
+
+    fn table() -> PpmTable {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        builder.add_ppm("b".to_string(), "c".to_string(), 14);
+        builder.build().expect("Table should be buildable.")
+    }
+
+    fn framed_bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_table(&mut bytes, &table()).expect("Table should be writable.");
+        bytes
+    }
+
+    #[test]
+    fn test_io_round_trip() {
+        let bytes = framed_bytes();
+        assert_eq!(&bytes[..5], b"PPMT\x01");
+        let read =
+            read_table::<u32, RandomState>(bytes.as_slice()).expect("Table should be readable.");
+        assert_eq!(read, table());
+    }
+
+    #[test]
+    fn test_io_not_ppm_table() {
+        let unframed = postcard::to_stdvec(&table()).unwrap();
+        assert!(matches!(
+            read_table::<u32, RandomState>(unframed.as_slice()),
+            Err(ReadTableError::NotPpmTable)
+        ));
+        assert!(matches!(
+            read_table::<u32, RandomState>(&b"PPMT"[..]),
+            Err(ReadTableError::NotPpmTable)
+        ));
+    }
+
+    #[test]
+    fn test_io_unsupported_version() {
+        let mut bytes = framed_bytes();
+        bytes[4] = 2;
+        assert!(matches!(
+            read_table::<u32, RandomState>(bytes.as_slice()),
+            Err(ReadTableError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn test_io_compressed_round_trip() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut builder = PpmTableBuilder::default();
+        for i in 0..300 {
+            for j in i + 1..300 {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1);
+                let ppm = ((state >> 33) % 1_000_000) as u32;
+                builder.add_ppm(format!("course/{:04}", i), format!("course/{:04}", j), ppm);
+            }
+        }
+        let table = builder.build().expect("Table should be buildable.");
+
+        let mut compressed = Vec::new();
+        write_table_compressed(&mut compressed, &table, 0).expect("Table should be writable.");
+        assert_eq!(&compressed[..5], b"PPMZ\x01");
+        let mut uncompressed = Vec::new();
+        write_table(&mut uncompressed, &table).expect("Table should be writable.");
+        assert!(compressed.len() < uncompressed.len());
+
+        let read = read_table::<u32, RandomState>(compressed.as_slice())
+            .expect("Table should be readable.");
+        assert_eq!(read, table);
+
+        compressed.truncate(compressed.len() / 2);
+        assert!(matches!(
+            read_table::<u32, RandomState>(compressed.as_slice()),
+            Err(ReadTableError::Decompress(_))
+        ));
+    }
+
+    fn compact_round_trip(max_ppm: u32) -> Vec<u8> {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 0);
+        builder.add_ppm("a".to_string(), "c".to_string(), 60_000);
+        builder.add_ppm("b".to_string(), "c".to_string(), max_ppm);
+        let table = builder.build().expect("Table should be buildable.");
+
+        let mut bytes = Vec::new();
+        write_table_compact(&mut bytes, &table).expect("Table should be writable.");
+        let read =
+            read_table::<u32, RandomState>(bytes.as_slice()).expect("Table should be readable.");
+        assert_eq!(read, table);
+        bytes
+    }
+
+    #[test]
+    fn test_io_compact_round_trip() {
+        let narrow = compact_round_trip(u16::MAX as u32);
+        assert_eq!(&narrow[..6], b"PPMC\x01\x02");
+        assert!(narrow.ends_with(&[0, 0, 0x60, 0xea, 0xff, 0xff]));
+
+        let wide = compact_round_trip(u16::MAX as u32 + 1);
+        assert_eq!(&wide[..6], b"PPMC\x01\x04");
+        assert!(wide.ends_with(&[0, 0, 0, 0, 0x60, 0xea, 0, 0, 0, 0, 1, 0]));
+
+        // The PPMs widen to any type that can hold them.
+        let read =
+            read_table::<u64, RandomState>(wide.as_slice()).expect("Table should be readable.");
+        assert_eq!(read.ppm("b", "c"), Some(u16::MAX as u64 + 1));
+        assert!(matches!(
+            read_table::<u16, RandomState>(wide.as_slice()),
+            Err(ReadTableError::CorruptPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_io_compact_corrupt() {
+        let mut bytes = compact_round_trip(10);
+        bytes.pop();
+        assert!(matches!(
+            read_table::<u32, RandomState>(bytes.as_slice()),
+            Err(ReadTableError::CorruptPayload(_))
+        ));
+        bytes.truncate(bytes.len() - 1);
+        assert!(matches!(
+            read_table::<u32, RandomState>(bytes.as_slice()),
+            Err(ReadTableError::InvalidTable(
+                InvariantError::WrongPpmCount { .. }
+            ))
+        ));
+
+        bytes[5] = 3;
+        assert!(matches!(
+            read_table::<u32, RandomState>(bytes.as_slice()),
+            Err(ReadTableError::UnsupportedWidth(3))
+        ));
+    }
+
+    #[test]
+    fn test_io_checkpoint_round_trip() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        let checkpoint = Checkpoint {
+            builder,
+            offset: 123,
+            lines: 2,
+            first_line: Some((6, 1)),
+            skipped: Some((1, 2)),
+        };
+
+        let mut bytes = Vec::new();
+        write_checkpoint(&mut bytes, &checkpoint).expect("Checkpoint should be writable.");
+        assert_eq!(&bytes[..5], b"PPMK\x01");
+        let read = read_checkpoint::<u32, RandomState>(bytes.as_slice())
+            .expect("Checkpoint should be readable.");
+        assert_eq!((read.offset, read.lines), (123, 2));
+        assert_eq!(
+            (read.first_line, read.skipped),
+            (Some((6, 1)), Some((1, 2)))
+        );
+        assert_eq!(read.builder, checkpoint.builder);
+
+        // A table is not a checkpoint, nor is a checkpoint from another version.
+        assert!(matches!(
+            read_checkpoint::<u32, RandomState>(framed_bytes().as_slice()),
+            Err(ReadTableError::NotCheckpoint)
+        ));
+        bytes[4] = FORMAT_VERSION + 1;
+        assert!(matches!(
+            read_checkpoint::<u32, RandomState>(bytes.as_slice()),
+            Err(ReadTableError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn test_io_checkpoint_corrupt_builder() {
+        /// A builder as it is serialized, but with a pair that pairs a key with itself.
+        #[derive(Serialize)]
+        struct CorruptBuilder {
+            ppms: HashMap<String, HashMap<String, u32>>,
+            keys: Vec<String>,
+        }
+
+        let builder = CorruptBuilder {
+            ppms: HashMap::from([("a".to_string(), HashMap::from([("a".to_string(), 10)]))]),
+            keys: vec!["a".to_string()],
+        };
+        let mut bytes = CHECKPOINT_MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+        bytes.extend(
+            postcard::to_stdvec(&(
+                0_u64,
+                0_u64,
+                None::<(u64, u64)>,
+                None::<(u64, u64)>,
+                builder,
+            ))
+            .unwrap(),
+        );
+        assert!(matches!(
+            read_checkpoint::<u32, RandomState>(bytes.as_slice()),
+            Err(ReadTableError::CorruptPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_io_corrupt_payload() {
+        let mut bytes = framed_bytes();
+        bytes.truncate(bytes.len() - 3);
+        assert!(matches!(
+            read_table::<u32, RandomState>(bytes.as_slice()),
+            Err(ReadTableError::CorruptPayload(_))
+        ));
+    }
+}
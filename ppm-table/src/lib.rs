@@ -1,250 +1,3034 @@
+#[cfg(any(test, feature = "proptest"))]
+mod arbitrary;
+mod concurrent;
+mod dendrogram;
+#[cfg(feature = "postcard")]
+pub mod io;
+pub mod ppm;
 #[cfg(feature = "serde")]
 mod serde;
 
-use std::collections::{HashMap, HashSet};
-use std::hash::{BuildHasher, RandomState};
-use std::ops::Index;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::hash::{BuildHasher, Hash, Hasher, RandomState};
+use std::ops::{Index, RangeInclusive};
+use std::sync::Arc;
 
+#[cfg(feature = "serde")]
+use ::serde::{Deserialize, Serialize};
+#[cfg(feature = "proptest")]
+pub use arbitrary::TableParameters;
 use bimap::BiHashMap;
+pub use concurrent::ConcurrentPpmTableBuilder;
+pub use dendrogram::{Dendrogram, Merge};
+#[cfg(feature = "petgraph")]
+use petgraph::graphmap::UnGraphMap;
+#[cfg(feature = "rand")]
+use rand::Rng;
+#[cfg(feature = "serde")]
+pub use serde::LegacyPpmTable;
+use thiserror::Error;
 
-#[derive(Clone, Debug)]
-pub struct PpmTable<S: BuildHasher + Default = RandomState> {
-    pub(crate) ppm_table: Vec<Vec<u32>>,
-    pub(crate) indices: BiHashMap<String, usize, S, S>,
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum RenameError {
+    #[error("The key to rename is not in the table.")]
+    UnknownKey(String),
+    #[error("The new key is already in the table.")]
+    DuplicateKey(String),
 }
 
-impl<S: BuildHasher + Default> PpmTable<S> {
-    const INDEX_FAIL_PANIC_MESSAGE: &'static str =
-        "A PpmTable must correspond to a fully-connected graph.";
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+#[error("The key {0} is not in the table.")]
+pub struct UnknownKeyError(pub String);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MergePolicy {
+    KeepMin,
+    KeepLast,
+    #[default]
+    Error,
+}
+
+/// How a [`PpmTableBuilder`] handles a pair that is added more than once.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DuplicatePolicy {
+    #[default]
+    Overwrite,
+    KeepFirst,
+    KeepMin,
+    KeepMax,
+    /// Treat a duplicate with a different PPM as an error. Duplicates with the same PPM are
+    /// allowed.
+    Error,
+}
+
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+#[error("The pair ({l}, {r}) was added with conflicting PPMs {first} and {second}.")]
+pub struct DuplicateError<V = u32> {
+    pub l: String,
+    pub r: String,
+    pub first: V,
+    pub second: V,
+}
+
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum MergeError<V = u32> {
+    #[error("The pair ({l}, {r}) has conflicting PPMs {first} and {second}.")]
+    ConflictingPpm {
+        l: String,
+        r: String,
+        first: V,
+        second: V,
+    },
+    #[error("The merged data does not correspond to a complete similarity graph.")]
+    IncompleteGraph { missing: Vec<(String, String)> },
+}
+
+#[derive(Clone, Copy, Debug, Error, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum BuildErrorKind {
+    #[error("The graph of {keys} keys is incomplete: {missing_pairs} pairs have no PPM.")]
+    IncompleteGraph { keys: usize, missing_pairs: usize },
+    #[error("{pairs} pairs were added more than once with conflicting PPMs.")]
+    ConflictingDuplicates { pairs: usize },
+}
 
-    pub fn get_ppm(&self, l: &str, r: &str) -> Option<&u32> {
-        let (l_idx, r_idx) = self.table_indices_from_strs(l, r)?;
-        Some(&self.ppm_table[l_idx][r_idx])
+/// The error returned by [`PpmTableBuilder::build`]. It hands back the builder so that the
+/// missing data can be added and the build retried.
+#[derive(Clone, Debug, Error)]
+#[error("{kind}")]
+pub struct BuildError<V = u32, S: BuildHasher + Default = RandomState> {
+    builder: PpmTableBuilder<V, S>,
+    kind: BuildErrorKind,
+}
+
+impl<V, S: BuildHasher + Default> BuildError<V, S> {
+    pub fn kind(&self) -> BuildErrorKind {
+        self.kind
     }
 
-    pub fn edges(&self) -> impl Iterator<Item = (&str, &str, u32)> {
-        self.ppm_table
-            .iter()
-            .enumerate()
-            .flat_map(|(i, v)| v.iter().enumerate().map(move |(j, ppm)| (i, j, ppm)))
-            .map(|(i, j, ppm)| {
-                let (l, r) = self.strs_from_table_indices(i, j);
-                (l, r, *ppm)
-            })
+    pub fn builder(&self) -> &PpmTableBuilder<V, S> {
+        &self.builder
     }
 
-    fn table_indices_from_strs(&self, l: &str, r: &str) -> Option<(usize, usize)> {
-        let (l, r) = if l < r { (l, r) } else { (r, l) };
-        let l_idx = *self.indices.get_by_left(l)?;
-        let r_idx = *self.indices.get_by_left(r)? - l_idx - 1;
-        Some((l_idx, r_idx))
+    pub fn into_builder(self) -> PpmTableBuilder<V, S> {
+        self.builder
     }
+}
 
-    fn strs_from_table_indices(&self, l_idx: usize, r_idx: usize) -> (&str, &str) {
-        let l = self
-            .indices
-            .get_by_right(&l_idx)
-            .expect(Self::INDEX_FAIL_PANIC_MESSAGE)
-            .as_str();
-        let r = self
-            .indices
-            .get_by_right(&(r_idx + l_idx + 1))
-            .expect(Self::INDEX_FAIL_PANIC_MESSAGE)
-            .as_str();
-        (l, r)
+impl<V: PartialEq> PartialEq<PpmTableBuilder<V>> for BuildError<V> {
+    fn eq(&self, other: &PpmTableBuilder<V>) -> bool {
+        self.builder == *other
+    }
+}
+
+/// The number of pairs missing from a [`PpmTableBuilder`], along with the first few of them.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MissingPairs {
+    pub sample: Vec<(String, String)>,
+    pub total: usize,
+}
+
+impl Display for MissingPairs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "missing {} pairs", self.total)?;
+        for (i, (l, r)) in self.sample.iter().enumerate() {
+            let separator = if i == 0 { ", e.g." } else { "," };
+            write!(f, "{} ({}, {})", separator, l, r)?;
+        }
+        Ok(())
     }
 }
 
-impl Index<(&str, &str)> for PpmTable {
-    type Output = u32;
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum InvariantError {
+    #[error("The key {key} has index {index}, but the table only has {len} keys.")]
+    IndexOutOfRange {
+        key: String,
+        index: usize,
+        len: usize,
+    },
+    #[error("A table with {len} keys needs {expected} PPMs, but this one has {actual}.")]
+    WrongPpmCount {
+        len: usize,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("The keys {first} and {second} are indexed out of sorted order.")]
+    UnsortedKeys { first: String, second: String },
+}
 
-    fn index(&self, index: (&str, &str)) -> &Self::Output {
-        let (l, r) = index;
-        self.get_ppm(l, r).expect("no ppm found for strings")
+#[derive(Clone, Copy, Debug, Error, Eq, PartialEq)]
+#[error("The histogram bucket width must be greater than zero.")]
+pub struct ZeroBucketWidthError;
+
+#[derive(Clone, Copy, Debug, Error, PartialEq)]
+#[error("The quantile {0} is not between 0 and 1.")]
+pub struct QuantileOutOfRangeError(pub f64);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PpmStats {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+}
+
+/// A pair of keys and the PPM between them. Edges order by PPM, then by left key, then by right
+/// key.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Edge<'a, V = u32> {
+    pub left: &'a str,
+    pub right: &'a str,
+    pub ppm: V,
+}
+
+impl Edge<'_, u32> {
+    pub fn percent(&self) -> f64 {
+        ppm::ppm_to_percent(self.ppm)
+    }
+}
+
+impl<'a, V> From<(&'a str, &'a str, V)> for Edge<'a, V> {
+    fn from((left, right, ppm): (&'a str, &'a str, V)) -> Self {
+        Edge { left, right, ppm }
     }
 }
 
-impl Eq for PpmTable {}
+impl<V: Ord> Ord for Edge<'_, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.ppm, self.left, self.right).cmp(&(&other.ppm, other.left, other.right))
+    }
+}
 
-impl<S1: BuildHasher + Default, S2: BuildHasher + Default> PartialEq<PpmTable<S2>>
-    for PpmTable<S1>
-{
-    fn eq(&self, other: &PpmTable<S2>) -> bool {
-        HashSet::<(&str, &str, u32), S1>::from_iter(self.edges())
-            == HashSet::<(&str, &str, u32), S1>::from_iter(other.edges())
+impl<V: Ord> PartialOrd for Edge<'_, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct TableDiff<V = u32> {
+    pub only_in_self: Vec<String>,
+    pub only_in_other: Vec<String>,
+    pub changed: Vec<ChangedPpm<V>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ChangedPpm<V = u32> {
+    pub l: String,
+    pub r: String,
+    pub before: V,
+    pub after: V,
+}
+
 #[derive(Clone, Debug)]
-pub struct PpmTableBuilder<S: BuildHasher + Default = RandomState> {
-    ppms: HashMap<String, HashMap<String, u32, S>, S>,
-    keys: HashSet<String, S>,
+pub struct PpmTable<V = u32, S: BuildHasher + Default = RandomState> {
+    /// The upper triangle of the PPM matrix, stored row by row.
+    pub(crate) ppm_table: Vec<V>,
+    pub(crate) indices: BiHashMap<String, usize, S, S>,
 }
 
-impl<S: BuildHasher + Default> PpmTableBuilder<S> {
-    pub fn new() -> Self {
-        let ppms = HashMap::default();
-        let keys = HashSet::default();
-        Self { ppms, keys }
+impl<V: Copy, S: BuildHasher + Default> PpmTable<V, S> {
+    const INDEX_FAIL_PANIC_MESSAGE: &'static str =
+        "A PpmTable must correspond to a fully-connected graph.";
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
     }
 
-    pub fn add_ppm(&mut self, l: String, r: String, ppm: u32) {
-        let (l, r) = if l < r { (l, r) } else { (r, l) };
-        self.keys.insert(l.clone());
-        self.keys.insert(r.clone());
-        self.ppms.entry(l).or_default().insert(r, ppm);
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn num_edges(&self) -> usize {
+        let n = self.len();
+        n * n.saturating_sub(1) / 2
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.indices.contains_left(key)
+    }
+
+    /// Returns the position of `key` in sorted key order. Indices are stable for the lifetime of
+    /// the table.
+    pub fn index_of(&self, key: &str) -> Option<usize> {
+        self.indices.get_by_left(key).copied()
+    }
+
+    pub fn key_at(&self, idx: usize) -> Option<&str> {
+        self.indices.get_by_right(&idx).map(String::as_str)
+    }
+
+    pub fn ppm(&self, l: &str, r: &str) -> Option<V> {
+        self.ppm_ref(l, r).copied()
+    }
+
+    #[deprecated(note = "use `PpmTable::ppm`, which returns the PPM by value")]
+    pub fn get_ppm(&self, l: &str, r: &str) -> Option<&V> {
+        self.ppm_ref(l, r)
+    }
+
+    /// Looks up a PPM using indices obtained from [`PpmTable::index_of`].
+    pub fn get_ppm_by_index(&self, l_idx: usize, r_idx: usize) -> Option<V> {
+        Some(self.ppm_table[self.flat_index(l_idx, r_idx)?])
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str, V)> {
+        self.table_entries().map(|(i, j, ppm)| {
+            let (l, r) = self.strs_from_table_indices(i, j);
+            (l, r, ppm)
+        })
+    }
+
+    /// Like [`PpmTable::edges`], but yields named [`Edge`]s rather than tuples.
+    pub fn edges_structured(&self) -> impl Iterator<Item = Edge<'_, V>> {
+        self.edges().map(Edge::from)
     }
 
-    pub fn build(self) -> Result<PpmTable<S>, Self> {
-        if !self.data_is_complete() {
-            return Err(self);
+    /// Removes `key` and all of its PPMs, shifting the indices of every later key down by one.
+    /// Returns whether the key was present.
+    pub fn remove_key(&mut self, key: &str) -> bool {
+        let Some((_, idx)) = self.indices.remove_by_left(key) else {
+            return false;
+        };
+
+        // Dropping a key's row and column leaves the remaining PPMs in row-major order.
+        let old_len = self.len() + 1;
+        let mut pairs = (0..old_len).flat_map(|i| ((i + 1)..old_len).map(move |j| (i, j)));
+        self.ppm_table.retain(|_| {
+            let (i, j) = pairs.next().expect(Self::INDEX_FAIL_PANIC_MESSAGE);
+            i != idx && j != idx
+        });
+
+        for old_idx in (idx + 1)..old_len {
+            let (key, _) = self
+                .indices
+                .remove_by_right(&old_idx)
+                .expect(Self::INDEX_FAIL_PANIC_MESSAGE);
+            self.indices.insert(key, old_idx - 1);
         }
 
-        let sorted_keys = Self::sorted_keys(self.keys);
+        true
+    }
 
-        let ppm_table = Self::generate_ppm_table(&sorted_keys, self.ppms);
-        let indices = Self::indices_from_sorted_keys(sorted_keys);
+    /// Renames `old` to `new`, reordering the table so that indices remain in sorted key order.
+    pub fn rename_key(&mut self, old: &str, new: String) -> Result<(), RenameError> {
+        if self.contains_key(&new) {
+            return Err(RenameError::DuplicateKey(new));
+        }
+        let Some((_, idx)) = self.indices.remove_by_left(old) else {
+            return Err(RenameError::UnknownKey(old.to_string()));
+        };
+        self.indices.insert(new, idx);
 
-        Ok(PpmTable { ppm_table, indices })
+        let mut order = (0..self.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| self.str_from_table_index(i));
+        *self = self.reordered(&order);
+
+        Ok(())
     }
 
-    fn data_is_complete(&self) -> bool {
-        for l in &self.keys {
-            for r in &self.keys {
-                if l < r {
-                    let Some(l_ppms) = self.ppms.get(l) else {
-                        return false;
-                    };
-                    if !l_ppms.contains_key(r) {
-                        return false;
-                    }
+    /// Prepends `prefix` to every key, e.g. to keep the same student in tables from different
+    /// assignments apart. A shared prefix keeps the keys in the same relative order, so only the
+    /// keys are rewritten.
+    ///
+    /// [`PpmTable::merge`] still needs a PPM for every pair of keys across the merged tables. To
+    /// combine prefixed tables into a block-diagonal one, merge their builders and fill in the
+    /// missing pairs with [`PpmTableBuilder::build_with_default`].
+    pub fn with_key_prefix(self, prefix: &str) -> PpmTable<V, S> {
+        let mut indices =
+            BiHashMap::with_capacity_and_hashers(self.len(), S::default(), S::default());
+        for (key, idx) in self.indices {
+            indices.insert(format!("{}{}", prefix, key), idx);
+        }
+        PpmTable {
+            ppm_table: self.ppm_table,
+            indices,
+        }
+    }
+
+    /// Picks `k` distinct edges uniformly at random, or every edge if there are fewer than `k`.
+    /// The edges are returned in the same order as [`PpmTable::edges`].
+    #[cfg(feature = "rand")]
+    pub fn sample_edges(&self, k: usize, rng: &mut impl Rng) -> Vec<(&str, &str, V)> {
+        let num_edges = self.ppm_table.len();
+        let mut flat_indices =
+            rand::seq::index::sample(rng, num_edges, k.min(num_edges)).into_vec();
+        flat_indices.sort_unstable();
+        self.edges_at(flat_indices)
+    }
+
+    /// Builds a table containing only the given keys and the PPMs between them.
+    pub fn subtable<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a str>,
+    ) -> Result<PpmTable<V, S>, UnknownKeyError> {
+        let mut order = keys
+            .into_iter()
+            .map(|k| {
+                self.index_of(k)
+                    .ok_or_else(|| UnknownKeyError(k.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        order.sort();
+        order.dedup();
+        Ok(self.reordered(&order))
+    }
+
+    /// Like [`PpmTable::subtable`], but silently skips keys that are not in the table.
+    pub fn subtable_lenient<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> PpmTable<V, S> {
+        let mut order = keys
+            .into_iter()
+            .filter_map(|k| self.index_of(k))
+            .collect::<Vec<_>>();
+        order.sort();
+        order.dedup();
+        self.reordered(&order)
+    }
+
+    pub fn neighbors(&self, key: &str) -> Option<impl Iterator<Item = (&str, V)>> {
+        let idx = *self.indices.get_by_left(key)?;
+        let earlier = (0..idx).map(move |i| {
+            let flat_idx = self
+                .flat_index(i, idx)
+                .expect(Self::INDEX_FAIL_PANIC_MESSAGE);
+            (i, self.ppm_table[flat_idx])
+        });
+        let later = self
+            .row(idx)
+            .iter()
+            .enumerate()
+            .map(move |(j, ppm)| (idx + j + 1, *ppm));
+        Some(
+            earlier
+                .chain(later)
+                .map(move |(i, ppm)| (self.str_from_table_index(i), ppm)),
+        )
+    }
+
+    /// Lists the keys present in only one of the tables and the pairs of shared keys whose PPM
+    /// differs, with `self` as the "before" table. All lists are in sorted key order.
+    pub fn diff<S2: BuildHasher + Default>(&self, other: &PpmTable<V, S2>) -> TableDiff<V>
+    where
+        V: PartialEq,
+    {
+        let mut diff = TableDiff {
+            only_in_self: Vec::new(),
+            only_in_other: Vec::new(),
+            changed: Vec::new(),
+        };
+
+        // Both tables index their keys in sorted order, so the shared keys can be found by
+        // walking the two key lists together.
+        let mut shared = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.len() || j < other.len() {
+            match (self.key_at(i), other.key_at(j)) {
+                (Some(l), Some(r)) if l == r => {
+                    shared.push((i, j));
+                    i += 1;
+                    j += 1;
+                }
+                (Some(l), r) if r.is_none_or(|r| l < r) => {
+                    diff.only_in_self.push(l.to_string());
+                    i += 1;
                 }
+                (_, Some(r)) => {
+                    diff.only_in_other.push(r.to_string());
+                    j += 1;
+                }
+                (_, None) => unreachable!("a key remains in one of the tables"),
             }
         }
-        true
+
+        for (a, &(l_self, l_other)) in shared.iter().enumerate() {
+            for &(r_self, r_other) in &shared[a + 1..] {
+                let before = self
+                    .get_ppm_by_index(l_self, r_self)
+                    .expect(Self::INDEX_FAIL_PANIC_MESSAGE);
+                let after = other
+                    .get_ppm_by_index(l_other, r_other)
+                    .expect(Self::INDEX_FAIL_PANIC_MESSAGE);
+                if before != after {
+                    diff.changed.push(ChangedPpm {
+                        l: self.str_from_table_index(l_self).to_string(),
+                        r: self.str_from_table_index(r_self).to_string(),
+                        before,
+                        after,
+                    });
+                }
+            }
+        }
+
+        diff
     }
 
-    fn sorted_keys(keys: HashSet<String, S>) -> Vec<String> {
-        let mut key_vec = keys.into_iter().collect::<Vec<_>>();
-        key_vec.sort();
-        key_vec
+    /// Applies `f` to every stored value, keeping the keys and their indices.
+    pub fn map_ppms<W: Copy>(self, f: impl FnMut(V) -> W) -> PpmTable<W, S> {
+        PpmTable {
+            ppm_table: self.ppm_table.into_iter().map(f).collect(),
+            indices: self.indices,
+        }
     }
 
-    fn generate_ppm_table(
-        sorted_keys: &[String],
-        ppms: HashMap<String, HashMap<String, u32, S>, S>,
-    ) -> Vec<Vec<u32>> {
-        let mut ppm_table = Self::allocate_ppm_table(sorted_keys.len());
-        Self::populate_ppm_table(&mut ppm_table, sorted_keys, ppms);
-        ppm_table
+    /// Like [`PpmTable::map_ppms`], but stops at the first value for which `f` fails.
+    pub fn try_map_ppms<W: Copy, E>(
+        self,
+        f: impl FnMut(V) -> Result<W, E>,
+    ) -> Result<PpmTable<W, S>, E> {
+        Ok(PpmTable {
+            ppm_table: self
+                .ppm_table
+                .into_iter()
+                .map(f)
+                .collect::<Result<_, _>>()?,
+            indices: self.indices,
+        })
     }
 
-    fn indices_from_sorted_keys(sorted_keys: Vec<String>) -> BiHashMap<String, usize, S, S> {
+    /// Checks that every key has a distinct index in `0..len`, that the indices follow the sorted
+    /// order of the keys, and that there is exactly one PPM for each pair of keys. Tables built
+    /// with [`PpmTableBuilder`] always pass; this is for tables read from untrusted data.
+    pub fn check_invariants(&self) -> Result<(), InvariantError> {
+        let len = self.len();
+        // The map is bijective, so `len` in-range indices cover `0..len` without gaps.
+        if let Some((key, &index)) = self.indices.iter().find(|(_, &index)| index >= len) {
+            return Err(InvariantError::IndexOutOfRange {
+                key: key.clone(),
+                index,
+                len,
+            });
+        }
+
+        let expected = len * len.saturating_sub(1) / 2;
+        if self.ppm_table.len() != expected {
+            return Err(InvariantError::WrongPpmCount {
+                len,
+                expected,
+                actual: self.ppm_table.len(),
+            });
+        }
+
+        for i in 1..len {
+            let (first, second) = (
+                self.str_from_table_index(i - 1),
+                self.str_from_table_index(i),
+            );
+            if first >= second {
+                return Err(InvariantError::UnsortedKeys {
+                    first: first.to_string(),
+                    second: second.to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Expands the table into a dense symmetric matrix, returned with the keys in index order.
+    /// The diagonal holds `V::default()`, which is 0 for the integer types.
+    pub fn to_matrix(&self) -> (Vec<String>, Vec<Vec<V>>)
+    where
+        V: Default,
+    {
+        let n = self.len();
+        let keys = (0..n)
+            .map(|i| self.str_from_table_index(i).to_string())
+            .collect();
+        let mut matrix = vec![vec![V::default(); n]; n];
+        for (i, j_offset, ppm) in self.table_entries() {
+            let j = i + j_offset + 1;
+            matrix[i][j] = ppm;
+            matrix[j][i] = ppm;
+        }
+        (keys, matrix)
+    }
+
+    /// Consumes the table, returning its edges with owned keys in the same order as
+    /// [`PpmTable::edges`]: sorted by the indices of the left key and then the right key.
+    pub fn into_edges(self) -> Vec<(String, String, V)> {
+        self.into_iter().collect()
+    }
+
+    /// Estimates the number of bytes the table owns on the heap. The PPMs and keys are counted
+    /// exactly, but the share of the key-index bimap is approximated from its layout and
+    /// capacity, and allocator overhead is ignored.
+    pub fn heap_size(&self) -> usize {
+        let ppms = self.ppm_table.capacity() * size_of::<V>();
+        let keys = self
+            .indices
+            .left_values()
+            .map(String::capacity)
+            .sum::<usize>();
+        // The bimap puts each key and index behind an `Rc`, with two counts apiece, and each of its
+        // two hash maps stores a pair of `Rc`s and a control byte per slot.
+        let entries =
+            self.len() * (4 * size_of::<usize>() + size_of::<String>() + size_of::<usize>());
+        let slots = 2 * self.indices.capacity() * (2 * size_of::<usize>() + 1);
+        ppms + keys + entries + slots
+    }
+
+    /// Releases any spare capacity in the PPMs, the keys and the key-index bimap.
+    pub fn shrink_to_fit(&mut self) {
+        self.ppm_table.shrink_to_fit();
+        let indices = std::mem::take(&mut self.indices);
+        self.indices =
+            BiHashMap::with_capacity_and_hashers(indices.len(), S::default(), S::default());
+        for (mut key, idx) in indices {
+            key.shrink_to_fit();
+            self.indices.insert(key, idx);
+        }
+    }
+
+    /// Converts the table back into a builder so that more PPMs can be added to it.
+    pub fn into_builder(self) -> PpmTableBuilder<V, S> {
+        let mut builder = PpmTableBuilder::new();
+        for (i, j, ppm) in self.table_entries() {
+            let (l, r) = self.strs_from_table_indices(i, j);
+            builder.add_ppm_interned(l, r, ppm);
+        }
+        // Keys without any PPMs, as in a single-key table, would otherwise be lost.
+        for key in self.indices.left_values() {
+            builder.intern(key);
+        }
+        builder
+    }
+
+    /// Builds a table in which the key at index `order[i]` of this table is at index `i`. Keys
+    /// whose index does not appear in `order` are dropped.
+    fn reordered(&self, order: &[usize]) -> PpmTable<V, S> {
+        let mut ppm_table = Vec::with_capacity(order.len() * order.len().saturating_sub(1) / 2);
+        for (i, &l) in order.iter().enumerate() {
+            for &r in &order[i + 1..] {
+                ppm_table.push(
+                    self.get_ppm_by_index(l, r)
+                        .expect(Self::INDEX_FAIL_PANIC_MESSAGE),
+                );
+            }
+        }
+
         let mut indices = BiHashMap::with_capacity_and_hashers(
-            sorted_keys.len(),
+            order.len(),
             Default::default(),
             Default::default(),
         );
-        for (i, k) in sorted_keys.into_iter().enumerate() {
-            indices.insert(k, i);
+        for (i, &old_idx) in order.iter().enumerate() {
+            indices.insert(self.str_from_table_index(old_idx).to_string(), i);
         }
-        indices
+
+        PpmTable { ppm_table, indices }
     }
 
-    fn allocate_ppm_table(n: usize) -> Vec<Vec<u32>> {
-        let mut outer = Vec::with_capacity(n);
-        for i in 0..n {
-            outer.push(Vec::with_capacity(n - i - 1));
+    fn ppm_ref(&self, l: &str, r: &str) -> Option<&V> {
+        let flat_idx = self.flat_index(self.index_of(l)?, self.index_of(r)?)?;
+        Some(&self.ppm_table[flat_idx])
+    }
+
+    fn table_entries(&self) -> impl Iterator<Item = (usize, usize, V)> + '_ {
+        (0..self.len()).flat_map(move |i| {
+            self.row(i)
+                .iter()
+                .enumerate()
+                .map(move |(j, ppm)| (i, j, *ppm))
+        })
+    }
+
+    /// Returns the PPMs between the key at index `i` and every key after it.
+    pub(crate) fn row(&self, i: usize) -> &[V] {
+        let start = Self::row_start(self.len(), i);
+        &self.ppm_table[start..start + (self.len() - i - 1)]
+    }
+
+    fn row_start(n: usize, i: usize) -> usize {
+        i * (2 * n - i - 1) / 2
+    }
+
+    fn flat_index(&self, l_idx: usize, r_idx: usize) -> Option<usize> {
+        let (l_idx, r_idx) = if l_idx < r_idx {
+            (l_idx, r_idx)
+        } else {
+            (r_idx, l_idx)
+        };
+        if l_idx == r_idx || r_idx >= self.len() {
+            return None;
         }
-        outer
+        Some(Self::row_start(self.len(), l_idx) + (r_idx - l_idx - 1))
     }
 
-    fn populate_ppm_table(
-        ppm_table: &mut [Vec<u32>],
-        sorted_keys: &[String],
-        ppms: HashMap<String, HashMap<String, u32, S>, S>,
-    ) {
-        for (i, l) in sorted_keys.iter().enumerate() {
-            for (j, r) in sorted_keys.iter().enumerate() {
-                if i < j {
-                    ppm_table[i].push(ppms[l][r])
+    /// Resolves the edges at the given ascending positions in the flattened table.
+    #[cfg(feature = "rand")]
+    fn edges_at(&self, flat_indices: Vec<usize>) -> Vec<(&str, &str, V)> {
+        let n = self.len();
+        let mut i = 0;
+        flat_indices
+            .into_iter()
+            .map(|flat_idx| {
+                while Self::row_start(n, i + 1) <= flat_idx {
+                    i += 1;
                 }
-            }
-        }
+                let (l, r) = self.strs_from_table_indices(i, flat_idx - Self::row_start(n, i));
+                (l, r, self.ppm_table[flat_idx])
+            })
+            .collect()
     }
-}
 
-impl Default for PpmTableBuilder {
-    fn default() -> Self {
-        Self::new()
+    fn strs_from_table_indices(&self, l_idx: usize, r_idx: usize) -> (&str, &str) {
+        let l = self.str_from_table_index(l_idx);
+        let r = self.str_from_table_index(r_idx + l_idx + 1);
+        (l, r)
+    }
+
+    fn str_from_table_index(&self, idx: usize) -> &str {
+        self.indices
+            .get_by_right(&idx)
+            .expect(Self::INDEX_FAIL_PANIC_MESSAGE)
+            .as_str()
     }
 }
 
-impl Eq for PpmTableBuilder {}
+impl<V: Copy + PartialOrd, S: BuildHasher + Default> PpmTable<V, S> {
+    /// Yields the edges whose PPM is at most `max_ppm`, only resolving the keys of those edges.
+    pub fn edges_below(&self, max_ppm: V) -> impl Iterator<Item = (&str, &str, V)> {
+        self.table_entries()
+            .filter(move |&(_, _, ppm)| ppm <= max_ppm)
+            .map(|(i, j, ppm)| {
+                let (l, r) = self.strs_from_table_indices(i, j);
+                (l, r, ppm)
+            })
+    }
 
-impl PartialEq for PpmTableBuilder {
-    fn eq(&self, other: &Self) -> bool {
-        (self.ppms == other.ppms) && (self.keys == other.keys)
+    /// Collects the edges whose PPM is at most `max_ppm` as pairs of key indices (see
+    /// [`PpmTable::index_of`]), with the smaller index first.
+    pub fn sparse_edges_below(&self, max_ppm: V) -> Vec<(usize, usize, V)> {
+        self.table_entries()
+            .filter(|&(_, _, ppm)| ppm <= max_ppm)
+            .map(|(i, j, ppm)| (i, i + j + 1, ppm))
+            .collect()
+    }
+
+    /// Like [`PpmTable::sample_edges`], but only picks from the edges whose PPM is at most
+    /// `max_ppm`. Reservoir sampling keeps the memory used proportional to `k`.
+    #[cfg(feature = "rand")]
+    pub fn sample_edges_below(
+        &self,
+        max_ppm: V,
+        k: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<(&str, &str, V)> {
+        let mut reservoir = Vec::with_capacity(k);
+        let below = (0..self.ppm_table.len()).filter(|&i| self.ppm_table[i] <= max_ppm);
+        for (seen, flat_idx) in below.enumerate() {
+            if seen < k {
+                reservoir.push(flat_idx);
+            } else {
+                let replaced = rng.gen_range(0..=seen);
+                if replaced < k {
+                    reservoir[replaced] = flat_idx;
+                }
+            }
+        }
+        reservoir.sort_unstable();
+        self.edges_at(reservoir)
+    }
+
+    /// Converts the table to a petgraph graph whose edge weights are PPMs. With `max_ppm`, only
+    /// the edges whose PPM is at most `max_ppm` are included, along with their endpoints;
+    /// otherwise, the graph is complete.
+    #[cfg(feature = "petgraph")]
+    pub fn to_graph(&self, max_ppm: Option<V>) -> UnGraphMap<&str, V> {
+        match max_ppm {
+            Some(max_ppm) => UnGraphMap::from_edges(self.edges_below(max_ppm)),
+            None => {
+                let mut graph = UnGraphMap::with_capacity(self.len(), self.num_edges());
+                for i in 0..self.len() {
+                    graph.add_node(self.str_from_table_index(i));
+                }
+                graph.extend(self.edges());
+                graph
+            }
+        }
+    }
+
+    /// Combines the PPMs of two tables. The tables may cover different keys, but the result must
+    /// still contain a PPM for every pair of keys.
+    pub fn merge(
+        self,
+        other: PpmTable<V, S>,
+        policy: MergePolicy,
+    ) -> Result<PpmTable<V, S>, MergeError<V>> {
+        let mut builder = self.into_builder();
+        builder.merge(other.into_builder(), policy)?;
+        builder.build().map_err(|err| MergeError::IncompleteGraph {
+            missing: err.builder().missing_pairs(),
+        })
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl<V: Copy + Ord, S: BuildHasher + Default> PpmTable<V, S> {
+    /// Yields every edge in ascending PPM order, breaking ties by left key and then right key.
+    pub fn edges_sorted(&self) -> impl Iterator<Item = (&str, &str, V)> {
+        self.sorted_edges_where(|_| true)
+    }
 
-    #[test]
-    fn test_ppm_table_builder_empty() {
-        let builder = PpmTableBuilder::default();
-        let table = builder.build().expect("Table should be buildable.");
-        assert!(table.edges().next().is_none());
+    /// Like [`PpmTable::edges_sorted`], but only yields edges whose PPM is at most `max_ppm`.
+    pub fn edges_sorted_below(&self, max_ppm: V) -> impl Iterator<Item = (&str, &str, V)> {
+        self.sorted_edges_where(move |ppm| ppm <= max_ppm)
     }
 
-    #[test]
-    fn test_ppm_table_builder_two_keys() {
-        let mut builder = PpmTableBuilder::default();
-        builder.add_ppm("a".to_string(), "b".to_string(), 10);
-        let table = builder.build().expect("Table should be buildable.");
+    fn sorted_edges_where(
+        &self,
+        keep: impl Fn(V) -> bool,
+    ) -> impl Iterator<Item = (&str, &str, V)> {
+        let mut entries = self
+            .table_entries()
+            .filter(|&(_, _, ppm)| keep(ppm))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|&(i, j, ppm)| (ppm, i, j));
+        entries.into_iter().map(|(i, j, ppm)| {
+            let (l, r) = self.strs_from_table_indices(i, j);
+            (l, r, ppm)
+        })
+    }
 
-        assert_eq!(table[("a", "b")], 10);
-        assert_eq!(table.edges().collect::<Vec<_>>(), vec![("a", "b", 10)]);
+    /// Returns the edge with the smallest PPM, breaking ties by key.
+    pub fn min_edge(&self) -> Option<(&str, &str, V)> {
+        let (i, j, ppm) = self
+            .table_entries()
+            .min_by_key(|&(i, j, ppm)| (ppm, i, j))?;
+        let (l, r) = self.strs_from_table_indices(i, j);
+        Some((l, r, ppm))
     }
 
-    #[test]
-    fn test_ppm_table_builder_missing_similarity() {
-        let mut builder = PpmTableBuilder::default();
-        builder.add_ppm("a".to_string(), "b".to_string(), 10);
-        builder.add_ppm("b".to_string(), "c".to_string(), 20);
-        let old_builder = builder.clone();
-        let table = builder.build().expect_err("Table should not be buildable.");
-        assert_eq!(table, old_builder);
+    /// Returns the edge with the largest PPM, breaking ties by key.
+    pub fn max_edge(&self) -> Option<(&str, &str, V)> {
+        let (i, j, ppm) = self
+            .table_entries()
+            .max_by_key(|&(i, j, ppm)| (ppm, Reverse((i, j))))?;
+        let (l, r) = self.strs_from_table_indices(i, j);
+        Some((l, r, ppm))
     }
 
-    #[test]
-    fn test_ppm_table_builder_three_nodes() {
-        let mut builder = PpmTableBuilder::default();
-        builder.add_ppm("a".to_string(), "b".to_string(), 10);
-        builder.add_ppm("a".to_string(), "c".to_string(), 20);
-        builder.add_ppm("b".to_string(), "c".to_string(), 14);
-        let table = builder.build().expect("Table should be buildable.");
+    /// Returns the `k` edges with the smallest PPMs in ascending order, breaking ties by key.
+    pub fn top_k(&self, k: usize) -> Vec<(&str, &str, V)> {
+        let mut heap = BinaryHeap::with_capacity(k.min(self.num_edges()));
+        for (i, j, ppm) in self.table_entries() {
+            let entry = (ppm, i, j);
+            if heap.len() < k {
+                heap.push(entry);
+            } else if heap.peek().is_some_and(|&largest| entry < largest) {
+                heap.pop();
+                heap.push(entry);
+            }
+        }
 
-        let expected = {
-            let mut set = HashSet::new();
-            set.insert(("a", "b", 10));
-            set.insert(("a", "c", 20));
-            set.insert(("b", "c", 14));
-            set
-        };
-        assert_eq!(table.edges().collect::<HashSet<_>>(), expected);
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|(ppm, i, j)| {
+                let (l, r) = self.strs_from_table_indices(i, j);
+                (l, r, ppm)
+            })
+            .collect()
     }
 
-    #[test]
-    fn test_ppm_table_builder_overwrite() {
-        let mut builder = PpmTableBuilder::default();
-        builder.add_ppm("a".to_string(), "b".to_string(), 25);
-        builder.add_ppm("a".to_string(), "b".to_string(), 16);
-        let table = builder.build().expect("Table should be buildable.");
+    /// Yields each key in sorted order together with the other key it has the smallest PPM
+    /// against, breaking ties by key.
+    pub fn nearest_neighbors(&self) -> impl Iterator<Item = (&str, &str, V)> {
+        let mut nearest: Vec<Option<(V, usize)>> = vec![None; self.len()];
+        for (i, j, ppm) in self.table_entries() {
+            let j = i + j + 1;
+            for (key, other) in [(i, j), (j, i)] {
+                if nearest[key].is_none_or(|best| (ppm, other) < best) {
+                    nearest[key] = Some((ppm, other));
+                }
+            }
+        }
 
-        assert_eq!(table[("a", "b")], 16);
-        assert_eq!(table.edges().collect::<Vec<_>>(), vec![("a", "b", 16)]);
+        nearest
+            .into_iter()
+            .enumerate()
+            .filter_map(move |(i, best)| {
+                let (ppm, j) = best?;
+                Some((
+                    self.str_from_table_index(i),
+                    self.str_from_table_index(j),
+                    ppm,
+                ))
+            })
+    }
+
+    /// Returns the key whose largest PPM against any other key is smallest, i.e. the submission
+    /// most central to the table, along with that PPM. Ties are broken by key, and tables with
+    /// fewer than two keys have no medoid.
+    pub fn medoid(&self) -> Option<(&str, V)> {
+        let (i, (_, max)) = self
+            .ppm_extremes()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, extremes)| Some((i, extremes?)))
+            .min_by_key(|&(i, (_, max))| (max, i))?;
+        Some((self.str_from_table_index(i), max))
+    }
+
+    /// Returns the key whose smallest PPM against any other key is largest, i.e. the submission
+    /// least like any other, along with that PPM. Ties are broken by key.
+    pub fn most_isolated(&self) -> Option<(&str, V)> {
+        let (i, (min, _)) = self
+            .ppm_extremes()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, extremes)| Some((i, extremes?)))
+            .min_by_key(|&(i, (min, _))| (Reverse(min), i))?;
+        Some((self.str_from_table_index(i), min))
+    }
+
+    /// The smallest and largest PPM of each key against the others, by index.
+    fn ppm_extremes(&self) -> Vec<Option<(V, V)>> {
+        let mut extremes: Vec<Option<(V, V)>> = vec![None; self.len()];
+        for (i, j, ppm) in self.table_entries() {
+            for key in [i, i + j + 1] {
+                extremes[key] = Some(match extremes[key] {
+                    Some((min, max)) => (min.min(ppm), max.max(ppm)),
+                    None => (ppm, ppm),
+                });
+            }
+        }
+        extremes
+    }
+
+    /// Like [`PpmTable::neighbors`], but ordered by ascending PPM with ties broken by key.
+    pub fn neighbors_sorted(&self, key: &str) -> Option<impl Iterator<Item = (&str, V)>> {
+        let mut neighbors = self.neighbors(key)?.collect::<Vec<_>>();
+        neighbors.sort_by_key(|&(k, ppm)| (ppm, k));
+        Some(neighbors.into_iter())
+    }
+
+    /// Returns the `len() - 1` edges of a minimum spanning tree in ascending PPM order, breaking
+    /// ties by left key and then right key. Among equally light edges, those between keys earlier
+    /// in sorted order are preferred, so the tree is the same for equal tables.
+    pub fn minimum_spanning_tree(&self) -> Vec<(&str, &str, V)> {
+        self.spanning_tree_indices()
+            .into_iter()
+            .map(|(l, r, ppm)| {
+                (
+                    self.str_from_table_index(l),
+                    self.str_from_table_index(r),
+                    ppm,
+                )
+            })
+            .collect()
+    }
+
+    /// Clusters the keys by single linkage, where two clusters join at the lowest PPM between any
+    /// of their members.
+    pub fn single_linkage(&self) -> Dendrogram<'_, V> {
+        let keys = (0..self.len())
+            .map(|i| self.str_from_table_index(i))
+            .collect();
+        Dendrogram::new(keys, self.spanning_tree_indices())
+    }
+
+    /// The edges of the minimum spanning tree as pairs of key indices, with the smaller index first,
+    /// sorted by PPM and then by indices. Since keys are indexed in sorted order, this is also
+    /// the order of [`PpmTable::minimum_spanning_tree`].
+    fn spanning_tree_indices(&self) -> Vec<(usize, usize, V)> {
+        // Prim's algorithm over the dense matrix, which is O(n²) for a complete graph.
+        let n = self.len();
+        let mut in_tree = vec![false; n];
+        let mut lightest: Vec<Option<(V, usize)>> = vec![None; n];
+        let mut tree = Vec::with_capacity(n.saturating_sub(1));
+        let mut newest = 0;
+        for _ in 1..n {
+            in_tree[newest] = true;
+            for (j, lightest_j) in lightest.iter_mut().enumerate() {
+                if in_tree[j] {
+                    continue;
+                }
+                let ppm = self
+                    .get_ppm_by_index(newest, j)
+                    .expect(Self::INDEX_FAIL_PANIC_MESSAGE);
+                if lightest_j.is_none_or(|lightest| (ppm, newest) < lightest) {
+                    *lightest_j = Some((ppm, newest));
+                }
+            }
+
+            let (next, (ppm, parent)) = (0..n)
+                .filter(|&j| !in_tree[j])
+                .filter_map(|j| Some((j, lightest[j]?)))
+                .min_by_key(|&(j, (ppm, parent))| (ppm, parent.min(j), parent.max(j)))
+                .expect(Self::INDEX_FAIL_PANIC_MESSAGE);
+            tree.push((parent.min(next), parent.max(next), ppm));
+            newest = next;
+        }
+        tree.sort_by_key(|&(l, r, ppm)| (ppm, l, r));
+        tree
+    }
+}
+
+impl<S: BuildHasher + Default> PpmTable<u32, S> {
+    /// Summarizes the distribution of PPMs, or returns `None` if the table has no edges. The
+    /// standard deviation is that of the population.
+    pub fn stats(&self) -> Option<PpmStats> {
+        let mut ppms = self
+            .table_entries()
+            .map(|(_, _, ppm)| ppm)
+            .collect::<Vec<_>>();
+        let count = ppms.len();
+        if count == 0 {
+            return None;
+        }
+
+        let mean = ppms.iter().map(|&ppm| ppm as f64).sum::<f64>() / count as f64;
+        let variance = ppms
+            .iter()
+            .map(|&ppm| (ppm as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count as f64;
+
+        let mid = count / 2;
+        let (lower, &mut upper, _) = ppms.select_nth_unstable(mid);
+        let median = if count % 2 == 0 {
+            let lower = *lower.iter().max().expect("lower half is non-empty");
+            (lower as f64 + upper as f64) / 2.0
+        } else {
+            upper as f64
+        };
+
+        Some(PpmStats {
+            count,
+            mean,
+            median,
+            std_dev: variance.sqrt(),
+        })
+    }
+
+    /// Counts the edges falling into consecutive buckets of `bucket_width_ppm` PPMs each, starting
+    /// at 0. The last bucket ends at the largest PPM in the table, so it may be narrower than the
+    /// others.
+    pub fn histogram(
+        &self,
+        bucket_width_ppm: u32,
+    ) -> Result<Vec<(RangeInclusive<u32>, usize)>, ZeroBucketWidthError> {
+        if bucket_width_ppm == 0 {
+            return Err(ZeroBucketWidthError);
+        }
+
+        let mut counts = Vec::new();
+        let mut max_ppm = 0;
+        for (_, _, ppm) in self.table_entries() {
+            let bucket = (ppm / bucket_width_ppm) as usize;
+            if bucket >= counts.len() {
+                counts.resize(bucket + 1, 0);
+            }
+            counts[bucket] += 1;
+            max_ppm = max_ppm.max(ppm);
+        }
+
+        Ok(counts
+            .into_iter()
+            .enumerate()
+            .map(|(bucket, count)| {
+                let start = bucket as u32 * bucket_width_ppm;
+                let end = start.saturating_add(bucket_width_ppm - 1).min(max_ppm);
+                (start..=end, count)
+            })
+            .collect())
+    }
+
+    /// Returns the PPM at quantile `q` of all edges, or `None` if the table has no edges. This is
+    /// the PPM whose rank among the sorted PPMs is nearest to `q * (count - 1)`, so 0 gives the
+    /// smallest PPM and 1 the largest.
+    pub fn quantile(&self, q: f64) -> Result<Option<u32>, QuantileOutOfRangeError> {
+        let rank = Self::quantile_rank(q, self.ppm_table.len())?;
+        let mut ppms = self.ppm_table.clone();
+        Ok(rank.map(|rank| *ppms.select_nth_unstable(rank).1))
+    }
+
+    /// Like [`PpmTable::quantile`] for every quantile in `qs`, sorting the PPMs only once.
+    pub fn quantiles(&self, qs: &[f64]) -> Result<Option<Vec<u32>>, QuantileOutOfRangeError> {
+        let ranks = qs
+            .iter()
+            .map(|&q| Self::quantile_rank(q, self.ppm_table.len()))
+            .collect::<Result<Option<Vec<_>>, _>>()?;
+        let mut ppms = self.ppm_table.clone();
+        ppms.sort_unstable();
+        Ok(ranks.map(|ranks| ranks.into_iter().map(|rank| ppms[rank]).collect()))
+    }
+
+    fn quantile_rank(q: f64, count: usize) -> Result<Option<usize>, QuantileOutOfRangeError> {
+        if !(0.0..=1.0).contains(&q) {
+            return Err(QuantileOutOfRangeError(q));
+        }
+        Ok(count
+            .checked_sub(1)
+            .map(|last| (q * last as f64).round() as usize))
+    }
+
+    /// Encodes the table's contents in a form that does not depend on the hasher or on the order
+    /// in which PPMs were added, suitable for computing a content digest. The encoding is the
+    /// number of keys, each key in sorted order as a length followed by its UTF-8 bytes, and then
+    /// every PPM in index order, with all integers as little-endian `u64`s and `u32`s.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.len() as u64).to_le_bytes());
+        for i in 0..self.len() {
+            let key = self.str_from_table_index(i);
+            bytes.extend_from_slice(&(key.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(key.as_bytes());
+        }
+        for (_, _, ppm) in self.table_entries() {
+            bytes.extend_from_slice(&ppm.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Renders the edges with PPMs of at most `max_ppm` as an undirected Graphviz DOT graph, with
+    /// each edge labelled by its percentage. Keys without any such edges are left out.
+    pub fn to_dot(&self, max_ppm: u32) -> String {
+        let mut dot = String::from("graph {\n");
+        for (l, r, ppm) in self.edges_below(max_ppm) {
+            dot.push_str(&format!(
+                "    {} -- {} [label=\"{}\"];\n",
+                dot_quote(l),
+                dot_quote(r),
+                ppm::Ppm(ppm)
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn dot_quote(key: &str) -> String {
+    format!("\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// An owning iterator over the edges of a [`PpmTable`], created by its `into_iter` method.
+///
+/// Each key is moved out of the table on its final edge and cloned for the ones before it.
+#[derive(Debug)]
+pub struct IntoEdges<V> {
+    keys: Vec<Option<String>>,
+    ppms: std::vec::IntoIter<V>,
+    l_idx: usize,
+    r_idx: usize,
+}
+
+impl<V> Iterator for IntoEdges<V> {
+    type Item = (String, String, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ppm = self.ppms.next()?;
+        let n = self.keys.len();
+        let (l_idx, r_idx) = (self.l_idx, self.r_idx);
+
+        // A key's last edge is the final one in its own row, or the last edge overall for the
+        // final key.
+        let r = if l_idx + 2 == n {
+            self.keys[r_idx].take()
+        } else {
+            self.keys[r_idx].clone()
+        };
+        let l = if r_idx + 1 == n {
+            self.keys[l_idx].take()
+        } else {
+            self.keys[l_idx].clone()
+        };
+
+        if r_idx + 1 == n {
+            self.l_idx += 1;
+            self.r_idx = self.l_idx + 1;
+        } else {
+            self.r_idx += 1;
+        }
+
+        Some((
+            l.expect("Keys are taken only on their last edge."),
+            r.expect("Keys are taken only on their last edge."),
+            ppm,
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ppms.size_hint()
+    }
+}
+
+impl<V> ExactSizeIterator for IntoEdges<V> {}
+
+impl<V: Copy, S: BuildHasher + Default> IntoIterator for PpmTable<V, S> {
+    type Item = (String, String, V);
+    type IntoIter = IntoEdges<V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut keys = vec![None; self.len()];
+        for (key, idx) in self.indices {
+            keys[idx] = Some(key);
+        }
+        IntoEdges {
+            keys,
+            ppms: self.ppm_table.into_iter(),
+            l_idx: 0,
+            r_idx: 1,
+        }
+    }
+}
+
+impl<V: Copy> Index<(&str, &str)> for PpmTable<V> {
+    type Output = V;
+
+    fn index(&self, index: (&str, &str)) -> &Self::Output {
+        let (l, r) = index;
+        self.ppm_ref(l, r).expect("no ppm found for strings")
+    }
+}
+
+impl<V: Copy> Index<(&String, &String)> for PpmTable<V> {
+    type Output = V;
+
+    fn index(&self, index: (&String, &String)) -> &Self::Output {
+        let (l, r) = index;
+        &self[(l.as_str(), r.as_str())]
+    }
+}
+
+impl<V: Copy> Index<(String, String)> for PpmTable<V> {
+    type Output = V;
+
+    fn index(&self, index: (String, String)) -> &Self::Output {
+        let (l, r) = index;
+        &self[(l.as_str(), r.as_str())]
+    }
+}
+
+impl<V: Copy + Eq, S: BuildHasher + Default> Eq for PpmTable<V, S> {}
+
+impl<V: Copy + Hash, S: BuildHasher + Default> Hash for PpmTable<V, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for i in 0..self.len() {
+            self.str_from_table_index(i).hash(state);
+        }
+        self.ppm_table.hash(state);
+    }
+}
+
+impl<V: Copy + PartialEq, S1: BuildHasher + Default, S2: BuildHasher + Default>
+    PartialEq<PpmTable<V, S2>> for PpmTable<V, S1>
+{
+    fn eq(&self, other: &PpmTable<V, S2>) -> bool {
+        // Both tables index their keys in sorted order, so equal key sets have equal indices and
+        // the triangular storage can be compared directly.
+        self.len() == other.len()
+            && (0..self.len()).all(|i| self.key_at(i) == other.key_at(i))
+            && self.ppm_table == other.ppm_table
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PpmTableBuilder<V = u32, S: BuildHasher + Default = RandomState> {
+    /// Every key is stored once in `keys`, and shared with the maps in `ppms`, since each key
+    /// appears in a pair with almost every other key.
+    ppms: HashMap<Arc<str>, HashMap<Arc<str>, V, S>, S>,
+    keys: HashSet<Arc<str>, S>,
+    duplicate_policy: DuplicatePolicy,
+    /// Set by [`PpmTableBuilder::with_duplicate_policy`], which is the only way to choose a
+    /// policy that compares PPMs, so that the builder itself does not require ordered values.
+    compare: Option<fn(&V, &V) -> Option<Ordering>>,
+    conflicts: Vec<(String, String)>,
+}
+
+impl<V: Copy, S: BuildHasher + Default> PpmTableBuilder<V, S> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates a builder with room for `keys` keys before reallocating.
+    pub fn with_capacity(keys: usize) -> Self {
+        Self {
+            ppms: HashMap::with_capacity_and_hasher(keys, S::default()),
+            keys: HashSet::with_capacity_and_hasher(keys, S::default()),
+            duplicate_policy: DuplicatePolicy::default(),
+            compare: None,
+            conflicts: Vec::new(),
+        }
+    }
+
+    /// Records the PPM between `l` and `r`. If the pair already has a PPM, the builder's
+    /// [`DuplicatePolicy`] decides which one is kept; under [`DuplicatePolicy::Error`] a
+    /// conflicting PPM is discarded and reported by [`PpmTableBuilder::build`]. Self-pairs, where
+    /// `l == r`, are ignored entirely and do not add their key to the builder.
+    pub fn add_ppm(&mut self, l: String, r: String, ppm: V) {
+        if let Err(err) = self.try_add_ppm(l, r, ppm) {
+            self.conflicts.push((err.l, err.r));
+        }
+    }
+
+    /// Like [`PpmTableBuilder::add_ppm`], but reports a conflicting duplicate immediately.
+    pub fn try_add_ppm(&mut self, l: String, r: String, ppm: V) -> Result<(), DuplicateError<V>> {
+        self.try_add_ppm_interned(&l, &r, ppm)
+    }
+
+    /// Like [`PpmTableBuilder::add_ppm`], but borrows the keys, so that only keys the builder has
+    /// not seen before are allocated.
+    pub fn add_ppm_interned(&mut self, l: &str, r: &str, ppm: V) {
+        if let Err(err) = self.try_add_ppm_interned(l, r, ppm) {
+            self.conflicts.push((err.l, err.r));
+        }
+    }
+
+    /// Like [`PpmTableBuilder::add_ppm_interned`], but reports a conflicting duplicate
+    /// immediately.
+    pub fn try_add_ppm_interned(
+        &mut self,
+        l: &str,
+        r: &str,
+        ppm: V,
+    ) -> Result<(), DuplicateError<V>> {
+        if l == r {
+            return Ok(());
+        }
+        let (l, r) = if l < r { (l, r) } else { (r, l) };
+        let ppm = match self.ppms.get(l).and_then(|r_ppms| r_ppms.get(r)) {
+            Some(&first) => match self.resolve_duplicate(first, ppm) {
+                Some(ppm) => ppm,
+                None => {
+                    return Err(DuplicateError {
+                        l: l.to_string(),
+                        r: r.to_string(),
+                        first,
+                        second: ppm,
+                    })
+                }
+            },
+            None => ppm,
+        };
+        self.insert_ppm(l, r, ppm);
+        Ok(())
+    }
+
+    /// Returns the pairs that [`PpmTableBuilder::add_ppm`] rejected under
+    /// [`DuplicatePolicy::Error`].
+    pub fn conflicting_pairs(&self) -> &[(String, String)] {
+        &self.conflicts
+    }
+
+    fn resolve_duplicate(&self, first: V, second: V) -> Option<V> {
+        let ordering = || self.compare.and_then(|compare| compare(&first, &second));
+        match self.duplicate_policy {
+            DuplicatePolicy::Overwrite => Some(second),
+            DuplicatePolicy::KeepFirst => Some(first),
+            DuplicatePolicy::KeepMin if ordering() == Some(Ordering::Greater) => Some(second),
+            DuplicatePolicy::KeepMax if ordering() == Some(Ordering::Less) => Some(second),
+            DuplicatePolicy::KeepMin | DuplicatePolicy::KeepMax => Some(first),
+            DuplicatePolicy::Error => (ordering() == Some(Ordering::Equal)).then_some(first),
+        }
+    }
+
+    /// Stores a PPM for a pair of distinct keys with `l < r`, ignoring the duplicate policy.
+    fn insert_ppm(&mut self, l: &str, r: &str, ppm: V) {
+        let (l, r) = (self.intern(l), self.intern(r));
+        self.ppms.entry(l).or_default().insert(r, ppm);
+    }
+
+    /// Returns the builder's copy of `key`, adding it if it is new.
+    fn intern(&mut self, key: &str) -> Arc<str> {
+        if let Some(key) = self.keys.get(key) {
+            return Arc::clone(key);
+        }
+        let key = Arc::<str>::from(key);
+        self.keys.insert(Arc::clone(&key));
+        key
+    }
+
+    /// Adds each `(l, r, ppm)` as with [`PpmTableBuilder::add_ppm`], reserving space up front
+    /// when the iterator reports its length.
+    pub fn add_ppms(&mut self, ppms: impl IntoIterator<Item = (String, String, V)>) {
+        let ppms = ppms.into_iter();
+        let (pairs, _) = ppms.size_hint();
+        // A complete graph with `pairs` edges has about `sqrt(2 * pairs)` keys.
+        let keys = ((2 * pairs) as f64).sqrt().ceil() as usize;
+        self.keys.reserve(keys.saturating_sub(self.keys.len()));
+        self.ppms.reserve(keys.saturating_sub(self.ppms.len()));
+        for (l, r, ppm) in ppms {
+            self.add_ppm(l, r, ppm);
+        }
+    }
+
+    pub fn build(self) -> Result<PpmTable<V, S>, BuildError<V, S>> {
+        if let Some(kind) = self.build_error_kind() {
+            return Err(BuildError {
+                builder: self,
+                kind,
+            });
+        }
+
+        Ok(self.into_table(None))
+    }
+
+    fn build_error_kind(&self) -> Option<BuildErrorKind> {
+        if !self.conflicts.is_empty() {
+            Some(BuildErrorKind::ConflictingDuplicates {
+                pairs: self.conflicts.len(),
+            })
+        } else if !self.data_is_complete() {
+            let missing_pairs = match self.num_expected_pairs().checked_sub(self.num_pairs()) {
+                Some(missing_pairs) if missing_pairs > 0 => missing_pairs,
+                // The counts only disagree this way when stray pairs are stored, e.g. in a
+                // hand-edited checkpoint, so the missing pairs have to be counted one by one.
+                _ => {
+                    let mut missing_pairs = 0;
+                    self.for_each_missing_pair(|_, _| missing_pairs += 1);
+                    missing_pairs
+                }
+            };
+            Some(BuildErrorKind::IncompleteGraph {
+                keys: self.num_keys(),
+                missing_pairs,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Builds the table, giving every pair that has no PPM the PPM `default_ppm`, e.g. for
+    /// similarity tools that leave out pairs above a cutoff. Conflicting duplicates recorded under
+    /// [`DuplicatePolicy::Error`] keep their first PPM.
+    pub fn build_with_default(self, default_ppm: V) -> PpmTable<V, S> {
+        self.into_table(Some(default_ppm))
+    }
+
+    fn into_table(self, default_ppm: Option<V>) -> PpmTable<V, S> {
+        let sorted_keys = Self::sorted_keys(self.keys);
+
+        let ppm_table = Self::generate_ppm_table(&sorted_keys, self.ppms, default_ppm);
+        let indices = Self::indices_from_sorted_keys(sorted_keys);
+
+        PpmTable { ppm_table, indices }
+    }
+
+    /// Adds a key without any PPMs, e.g. one whose pairs were all filtered out before being
+    /// added, so that it is still in the table built with [`PpmTableBuilder::build_with_default`].
+    pub fn add_key(&mut self, key: &str) {
+        self.intern(key);
+    }
+
+    pub fn num_keys(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Iterates over the keys added so far, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(AsRef::as_ref)
+    }
+
+    /// Removes `key` and every PPM involving it. Returns whether the key was present.
+    pub fn remove_key(&mut self, key: &str) -> bool {
+        if !self.keys.remove(key) {
+            return false;
+        }
+        // Pairs are stored under their smaller key, so the key's PPMs with smaller keys live in
+        // other keys' maps.
+        self.ppms.remove(key);
+        self.ppms.retain(|_, r_ppms| {
+            r_ppms.remove(key);
+            !r_ppms.is_empty()
+        });
+        self.conflicts.retain(|(l, r)| l != key && r != key);
+        true
+    }
+
+    /// Returns the number of distinct pairs that have a PPM.
+    pub fn num_pairs(&self) -> usize {
+        self.ppms.values().map(HashMap::len).sum()
+    }
+
+    /// Returns the fraction of all pairs of keys that have a PPM, which is 1.0 once the builder
+    /// can be built.
+    pub fn completeness(&self) -> f64 {
+        let expected = self.num_expected_pairs();
+        if expected == 0 {
+            1.0
+        } else {
+            self.num_pairs() as f64 / expected as f64
+        }
+    }
+
+    fn num_expected_pairs(&self) -> usize {
+        self.num_keys() * self.num_keys().saturating_sub(1) / 2
+    }
+
+    fn data_is_complete(&self) -> bool {
+        // A builder without exactly as many pairs as the complete graph cannot be complete, which
+        // is cheap to rule out. Matching counts still need every pair checked, since a builder
+        // that was not filled through `add_ppm` may store unsorted or unknown pairs.
+        self.num_pairs() == self.num_expected_pairs()
+            && self.keys.iter().all(|l| {
+                let l_ppms = self.ppms.get(l);
+                self.keys
+                    .iter()
+                    .all(|r| l >= r || l_ppms.is_some_and(|l_ppms| l_ppms.contains_key(r)))
+            })
+    }
+
+    /// Returns every pair of keys that has no PPM yet, in sorted order.
+    pub fn missing_pairs(&self) -> Vec<(String, String)> {
+        let mut missing = Vec::new();
+        self.for_each_missing_pair(|l, r| missing.push((l.to_string(), r.to_string())));
+        missing
+    }
+
+    /// Counts the pairs of keys that have no PPM yet, keeping only the first `limit` of them so
+    /// that a very incomplete builder does not produce an enormous report.
+    pub fn missing_pairs_summary(&self, limit: usize) -> MissingPairs {
+        let mut summary = MissingPairs::default();
+        self.for_each_missing_pair(|l, r| {
+            if summary.sample.len() < limit {
+                summary.sample.push((l.to_string(), r.to_string()));
+            }
+            summary.total += 1;
+        });
+        summary
+    }
+
+    fn for_each_missing_pair(&self, mut f: impl FnMut(&str, &str)) {
+        let mut sorted_keys = self.keys.iter().collect::<Vec<_>>();
+        sorted_keys.sort();
+        for (i, &l) in sorted_keys.iter().enumerate() {
+            for &r in &sorted_keys[i + 1..] {
+                if !self
+                    .ppms
+                    .get(l)
+                    .is_some_and(|l_ppms| l_ppms.contains_key(r))
+                {
+                    f(l, r);
+                }
+            }
+        }
+    }
+
+    fn sorted_keys(keys: HashSet<Arc<str>, S>) -> Vec<String> {
+        let mut key_vec = keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+        key_vec.sort();
+        key_vec
+    }
+
+    fn generate_ppm_table(
+        sorted_keys: &[String],
+        ppms: HashMap<Arc<str>, HashMap<Arc<str>, V, S>, S>,
+        default_ppm: Option<V>,
+    ) -> Vec<V> {
+        let n = sorted_keys.len();
+        let mut ppm_table = Vec::with_capacity(n * n.saturating_sub(1) / 2);
+        Self::populate_ppm_table(&mut ppm_table, sorted_keys, ppms, default_ppm);
+        ppm_table
+    }
+
+    fn indices_from_sorted_keys(sorted_keys: Vec<String>) -> BiHashMap<String, usize, S, S> {
+        let mut indices = BiHashMap::with_capacity_and_hashers(
+            sorted_keys.len(),
+            Default::default(),
+            Default::default(),
+        );
+        for (i, k) in sorted_keys.into_iter().enumerate() {
+            indices.insert(k, i);
+        }
+        indices
+    }
+
+    fn populate_ppm_table(
+        ppm_table: &mut Vec<V>,
+        sorted_keys: &[String],
+        ppms: HashMap<Arc<str>, HashMap<Arc<str>, V, S>, S>,
+        default_ppm: Option<V>,
+    ) {
+        for (i, l) in sorted_keys.iter().enumerate() {
+            let l_ppms = ppms.get(l.as_str());
+            for r in &sorted_keys[i + 1..] {
+                let ppm = l_ppms.and_then(|l_ppms| l_ppms.get(r.as_str())).copied();
+                ppm_table.push(
+                    ppm.or(default_ppm)
+                        .expect("Only complete builders are built without a default PPM."),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<V: Copy + Send + Sync, S: BuildHasher + Default + Sync> PpmTableBuilder<V, S> {
+    /// Like [`PpmTableBuilder::build`], but fills the rows of the table in parallel.
+    pub fn build_parallel(self) -> Result<PpmTable<V, S>, BuildError<V, S>> {
+        use rayon::prelude::*;
+
+        if let Some(kind) = self.build_error_kind() {
+            return Err(BuildError {
+                builder: self,
+                kind,
+            });
+        }
+
+        let sorted_keys = Self::sorted_keys(self.keys);
+        let ppms = self.ppms;
+        let ppm_table = sorted_keys
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(i, l)| {
+                // The last key has no PPMs of its own, so it may have no map.
+                let l_ppms = ppms.get(l.as_str());
+                sorted_keys[i + 1..].iter().map(move |r| {
+                    l_ppms.expect(PpmTable::<V, S>::INDEX_FAIL_PANIC_MESSAGE)[r.as_str()]
+                })
+            })
+            .collect();
+        let indices = Self::indices_from_sorted_keys(sorted_keys);
+
+        Ok(PpmTable { ppm_table, indices })
+    }
+}
+
+impl<V: Copy + PartialOrd, S: BuildHasher + Default> PpmTableBuilder<V, S> {
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self.compare = Some(V::partial_cmp);
+        self
+    }
+
+    /// Adds every key and PPM from `other`, resolving pairs present in both builders according to
+    /// `policy`. If a conflict is reported, the PPMs merged before it are kept.
+    pub fn merge(&mut self, other: Self, policy: MergePolicy) -> Result<(), MergeError<V>> {
+        // Keys without any PPMs, as in a single-key table, are only in `keys`.
+        for key in &other.keys {
+            self.intern(key);
+        }
+        for (l, r_ppms) in other.ppms {
+            for (r, ppm) in r_ppms {
+                let existing = self.ppms.get(&l).and_then(|r_ppms| r_ppms.get(&r)).copied();
+                let ppm = match (existing, policy) {
+                    (None, _) | (Some(_), MergePolicy::KeepLast) => ppm,
+                    (Some(first), MergePolicy::KeepMin) if ppm < first => ppm,
+                    (Some(first), MergePolicy::KeepMin) => first,
+                    (Some(first), MergePolicy::Error) if first != ppm => {
+                        return Err(MergeError::ConflictingPpm {
+                            l: l.to_string(),
+                            r: r.to_string(),
+                            first,
+                            second: ppm,
+                        });
+                    }
+                    (Some(first), MergePolicy::Error) => first,
+                };
+                self.insert_ppm(&l, &r, ppm);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for PpmTableBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Copy, S: BuildHasher + Default> Extend<(String, String, V)> for PpmTableBuilder<V, S> {
+    fn extend<I: IntoIterator<Item = (String, String, V)>>(&mut self, iter: I) {
+        self.add_ppms(iter);
+    }
+}
+
+impl<V: Copy, S: BuildHasher + Default> FromIterator<(String, String, V)>
+    for PpmTableBuilder<V, S>
+{
+    fn from_iter<I: IntoIterator<Item = (String, String, V)>>(iter: I) -> Self {
+        let mut builder = Self::new();
+        builder.add_ppms(iter);
+        builder
+    }
+}
+
+impl<V: Eq> Eq for PpmTableBuilder<V> {}
+
+impl<V: PartialEq> PartialEq for PpmTableBuilder<V> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.ppms == other.ppms) && (self.keys == other.keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{BuildHasherDefault, DefaultHasher};
+
+    use super::*;
+
+    #[test]
+    fn test_ppm_table_builder_empty() {
+        let builder = PpmTableBuilder::default();
+        let table = builder.build().expect("Table should be buildable.");
+        assert!(table.edges().next().is_none());
+    }
+
+    #[test]
+    fn test_ppm_table_builder_two_keys() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        let table = builder.build().expect("Table should be buildable.");
+
+        assert_eq!(table[("a", "b")], 10);
+        assert_eq!(table.edges().collect::<Vec<_>>(), vec![("a", "b", 10)]);
+    }
+
+    #[test]
+    fn test_ppm_table_builder_missing_similarity() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("b".to_string(), "c".to_string(), 20);
+        let old_builder = builder.clone();
+        let err = builder.build().expect_err("Table should not be buildable.");
+        assert_eq!(err, old_builder);
+        assert_eq!(
+            err.kind(),
+            BuildErrorKind::IncompleteGraph {
+                keys: 3,
+                missing_pairs: 1
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "The graph of 3 keys is incomplete: 1 pairs have no PPM."
+        );
+
+        let mut builder = err.into_builder();
+        builder.add_ppm("a".to_string(), "c".to_string(), 30);
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn test_ppm_table_builder_three_nodes() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        builder.add_ppm("b".to_string(), "c".to_string(), 14);
+        let table = builder.build().expect("Table should be buildable.");
+
+        let expected = {
+            let mut set = HashSet::new();
+            set.insert(("a", "b", 10));
+            set.insert(("a", "c", 20));
+            set.insert(("b", "c", 14));
+            set
+        };
+        assert_eq!(table.edges().collect::<HashSet<_>>(), expected);
+    }
+
+    #[test]
+    fn test_ppm_table_builder_overwrite() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 25);
+        builder.add_ppm("a".to_string(), "b".to_string(), 16);
+        let table = builder.build().expect("Table should be buildable.");
+
+        assert_eq!(table[("a", "b")], 16);
+        assert_eq!(table.edges().collect::<Vec<_>>(), vec![("a", "b", 16)]);
+    }
+
+    #[test]
+    fn test_ppm_table_builder_only_self_pair() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "a".to_string(), 0);
+        assert_eq!(builder, PpmTableBuilder::default());
+        assert!(builder.build().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ppm_table_builder_self_pair_with_edges() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 4);
+        builder.add_ppm("b".to_string(), "b".to_string(), 0);
+        builder.add_ppm("c".to_string(), "c".to_string(), 0);
+        let table = builder.build().unwrap();
+        assert_eq!(table.len(), 2);
+        assert!(!table.contains_key("c"));
+        assert_eq!(table.ppm("a", "b"), Some(4));
+        assert_eq!(table.ppm("b", "b"), None);
+    }
+
+    #[test]
+    fn test_ppm_table_builder_missing_pairs() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 1);
+        builder.add_ppm("c".to_string(), "d".to_string(), 2);
+        builder.add_ppm("b".to_string(), "d".to_string(), 3);
+        assert_eq!(
+            builder.missing_pairs(),
+            vec![
+                ("a".to_string(), "c".to_string()),
+                ("a".to_string(), "d".to_string()),
+                ("b".to_string(), "c".to_string()),
+            ]
+        );
+
+        let summary = builder.missing_pairs_summary(2);
+        assert_eq!(summary.total, 3);
+        assert_eq!(summary.to_string(), "missing 3 pairs, e.g. (a, c), (a, d)");
+        assert_eq!(
+            builder.missing_pairs_summary(0).to_string(),
+            "missing 3 pairs"
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_builder_from_iter() {
+        let edges = four_key_table().into_edges();
+        let builder = edges.iter().cloned().collect::<PpmTableBuilder>();
+        assert_eq!(builder, four_key_table().into_builder());
+
+        let mut extended = PpmTableBuilder::with_capacity(4);
+        extended.extend(edges[..3].iter().cloned());
+        extended.add_ppms(edges[3..].iter().cloned());
+        assert_eq!(extended, builder);
+        assert_eq!(extended.build().unwrap(), four_key_table());
+    }
+
+    #[test]
+    fn test_ppm_table_builder_interned() {
+        let edges = four_key_table().into_edges();
+        let mut interned = PpmTableBuilder::default();
+        for (l, r, ppm) in &edges {
+            // Add each pair backwards too, so every key is looked up after it is first added.
+            interned.add_ppm_interned(r, l, ppm + 1);
+            interned.add_ppm_interned(l, r, *ppm);
+        }
+        assert_eq!(interned.num_keys(), 4);
+        assert_eq!(interned, edges.into_iter().collect::<PpmTableBuilder>());
+        assert_eq!(interned.build().unwrap(), four_key_table());
+    }
+
+    #[test]
+    fn test_ppm_table_builder_duplicate_policies() {
+        let kept = |policy| {
+            let mut builder = PpmTableBuilder::default().with_duplicate_policy(policy);
+            builder.add_ppm("a".to_string(), "b".to_string(), 25);
+            builder.add_ppm("b".to_string(), "a".to_string(), 16);
+            builder.add_ppm("a".to_string(), "b".to_string(), 20);
+            builder.build().expect("Table should be buildable.")[("a", "b")]
+        };
+        assert_eq!(kept(DuplicatePolicy::Overwrite), 20);
+        assert_eq!(kept(DuplicatePolicy::KeepFirst), 25);
+        assert_eq!(kept(DuplicatePolicy::KeepMin), 16);
+        assert_eq!(kept(DuplicatePolicy::KeepMax), 25);
+    }
+
+    #[test]
+    fn test_ppm_table_builder_duplicate_error() {
+        let mut builder = PpmTableBuilder::default().with_duplicate_policy(DuplicatePolicy::Error);
+        builder.add_ppm("a".to_string(), "b".to_string(), 25);
+        assert_eq!(
+            builder.try_add_ppm("a".to_string(), "b".to_string(), 25),
+            Ok(())
+        );
+        assert_eq!(
+            builder.try_add_ppm("b".to_string(), "a".to_string(), 16),
+            Err(DuplicateError {
+                l: "a".to_string(),
+                r: "b".to_string(),
+                first: 25,
+                second: 16
+            })
+        );
+        assert!(builder.conflicting_pairs().is_empty());
+
+        builder.add_ppm("a".to_string(), "b".to_string(), 16);
+        assert_eq!(
+            builder.conflicting_pairs(),
+            &[("a".to_string(), "b".to_string())]
+        );
+        let err = builder.build().unwrap_err();
+        assert_eq!(
+            err.kind(),
+            BuildErrorKind::ConflictingDuplicates { pairs: 1 }
+        );
+        assert_eq!(err.builder().ppms["a"]["b"], 25);
+    }
+
+    #[test]
+    fn test_ppm_table_builder_progress() {
+        let mut builder = PpmTableBuilder::default();
+        assert_eq!(builder.completeness(), 1.0);
+
+        builder.add_ppm("a".to_string(), "b".to_string(), 1);
+        builder.add_ppm("b".to_string(), "c".to_string(), 2);
+        builder.add_ppm("c".to_string(), "b".to_string(), 3);
+        builder.add_ppm("c".to_string(), "d".to_string(), 4);
+        assert_eq!(builder.num_keys(), 4);
+        assert_eq!(builder.num_pairs(), 3);
+        assert_eq!(builder.completeness(), 0.5);
+        assert!(!builder.data_is_complete());
+
+        for (l, r) in builder.missing_pairs() {
+            builder.add_ppm(l, r, 5);
+        }
+        assert_eq!(builder.num_pairs(), 6);
+        assert_eq!(builder.completeness(), 1.0);
+        assert!(builder.data_is_complete());
+        assert!(builder.missing_pairs().is_empty());
+    }
+
+    #[test]
+    fn test_ppm_table_builder_progress_stray_pairs() {
+        // The pair counts match, but "a" and "b" are stored in the wrong order.
+        let mut builder = PpmTableBuilder::<u32>::default();
+        builder.insert_ppm("b", "a", 5);
+        builder.insert_ppm("a", "c", 1);
+        builder.insert_ppm("b", "c", 2);
+        assert_eq!(builder.num_pairs(), 3);
+        assert!(!builder.data_is_complete());
+        assert_eq!(
+            builder.build().unwrap_err().kind(),
+            BuildErrorKind::IncompleteGraph {
+                keys: 3,
+                missing_pairs: 1
+            }
+        );
+
+        // More pairs than the complete graph has.
+        let mut builder = PpmTableBuilder::<u32>::default();
+        builder.insert_ppm("a", "b", 5);
+        builder.insert_ppm("b", "a", 5);
+        assert!(!builder.data_is_complete());
+        assert!(matches!(
+            builder.build().unwrap_err().kind(),
+            BuildErrorKind::IncompleteGraph { keys: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn test_ppm_table_builder_build_with_default() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("c".to_string(), "b".to_string(), 20);
+        builder.add_ppm("d".to_string(), "d".to_string(), 0);
+        let table = builder.build_with_default(1_000_000);
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.check_invariants(), Ok(()));
+        assert_eq!(
+            table.edges().collect::<Vec<_>>(),
+            vec![("a", "b", 10), ("a", "c", 1_000_000), ("b", "c", 20)]
+        );
+
+        let complete = four_key_table().into_builder().build_with_default(0);
+        assert_eq!(complete, four_key_table());
+
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_key("c");
+        builder.add_key("a");
+        assert_eq!(builder.num_keys(), 3);
+        assert!(!builder.data_is_complete());
+        assert_eq!(
+            builder.build_with_default(7).edges().collect::<Vec<_>>(),
+            vec![("a", "b", 10), ("a", "c", 7), ("b", "c", 7)]
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_builder_remove_key() {
+        for key in ["a", "b", "c", "d"] {
+            let mut builder = four_key_table().into_builder();
+            assert!(builder.remove_key(key));
+            assert!(!builder.remove_key(key));
+            assert_eq!(builder.num_keys(), 3);
+            assert!(builder.keys().all(|k| k != key));
+
+            let table = builder.build().expect("Table should be buildable.");
+            let mut expected = four_key_table();
+            expected.remove_key(key);
+            assert_eq!(table, expected);
+        }
+
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 1);
+        assert!(builder.remove_key("b"));
+        assert_eq!(builder.keys().collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(builder.num_pairs(), 0);
+        assert!(!builder.remove_key("z"));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_ppm_table_builder_build_parallel() {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next_ppm = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 1_000_000) as u32
+        };
+        let keys = (0..200).map(|i| format!("{:03}", i)).collect::<Vec<_>>();
+        let mut builder = PpmTableBuilder::default();
+        for (i, l) in keys.iter().enumerate() {
+            for r in &keys[i + 1..] {
+                builder.add_ppm(r.clone(), l.clone(), next_ppm());
+            }
+        }
+
+        let serial = builder.clone().build().unwrap();
+        let parallel = builder.build_parallel().unwrap();
+        assert_eq!(parallel, serial);
+        assert_eq!(parallel.canonical_bytes(), serial.canonical_bytes());
+
+        let mut incomplete = PpmTableBuilder::default();
+        incomplete.add_ppm("a".to_string(), "b".to_string(), 1);
+        incomplete.add_ppm("b".to_string(), "c".to_string(), 1);
+        assert_eq!(
+            incomplete.build_parallel().unwrap_err().kind(),
+            BuildErrorKind::IncompleteGraph {
+                keys: 3,
+                missing_pairs: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_len_empty() {
+        let table = PpmTableBuilder::default()
+            .build()
+            .expect("Table should be buildable.");
+        assert_eq!(table.len(), 0);
+        assert!(table.is_empty());
+        assert_eq!(table.num_edges(), 0);
+    }
+
+    #[test]
+    fn test_ppm_table_len_single_key() {
+        let table = PpmTable::<u32, RandomState> {
+            ppm_table: Vec::<u32>::new(),
+            indices: BiHashMap::from_iter([("a".to_string(), 0)]),
+        };
+        assert_eq!(table.len(), 1);
+        assert!(!table.is_empty());
+        assert_eq!(table.num_edges(), 0);
+    }
+
+    #[test]
+    fn test_ppm_table_len_three_keys() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        builder.add_ppm("b".to_string(), "c".to_string(), 14);
+        let table = builder.build().expect("Table should be buildable.");
+        assert_eq!(table.len(), 3);
+        assert!(!table.is_empty());
+        assert_eq!(table.num_edges(), 3);
+        assert_eq!(table.num_edges(), table.edges().count());
+    }
+
+    #[test]
+    fn test_ppm_table_index_accessors() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        builder.add_ppm("b".to_string(), "c".to_string(), 14);
+        let table = builder.build().expect("Table should be buildable.");
+
+        assert!(table.contains_key("b"));
+        assert!(!table.contains_key("d"));
+        assert_eq!(table.index_of("a"), Some(0));
+        assert_eq!(table.index_of("c"), Some(2));
+        assert_eq!(table.index_of("d"), None);
+        assert_eq!(table.key_at(1), Some("b"));
+        assert_eq!(table.key_at(3), None);
+
+        assert_eq!(table.get_ppm_by_index(0, 2), Some(20));
+        assert_eq!(table.get_ppm_by_index(2, 1), Some(14));
+        assert_eq!(table.get_ppm_by_index(1, 1), None);
+        assert_eq!(table.get_ppm_by_index(0, 3), None);
+    }
+
+    fn four_key_table() -> PpmTable {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        builder.add_ppm("a".to_string(), "d".to_string(), 5);
+        builder.add_ppm("b".to_string(), "c".to_string(), 14);
+        builder.add_ppm("b".to_string(), "d".to_string(), 30);
+        builder.add_ppm("c".to_string(), "d".to_string(), 14);
+        builder.build().expect("Table should be buildable.")
+    }
+
+    fn assert_removed(key: &str, expected: &[(&str, &str, u32)]) {
+        let mut table = four_key_table();
+        assert!(table.remove_key(key));
+        assert!(!table.contains_key(key));
+        assert_eq!(table.len(), 3);
+
+        let edges = table.edges().collect::<HashSet<_>>();
+        assert_eq!(edges, expected.iter().copied().collect::<HashSet<_>>());
+        for (l, r, ppm) in edges {
+            assert_eq!(table.ppm(l, r), Some(ppm));
+        }
+        for (i, k) in ["a", "b", "c", "d"]
+            .into_iter()
+            .filter(|k| *k != key)
+            .enumerate()
+        {
+            assert_eq!(table.index_of(k), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_ppm_table_remove_first_key() {
+        assert_removed("a", &[("b", "c", 14), ("b", "d", 30), ("c", "d", 14)]);
+    }
+
+    #[test]
+    fn test_ppm_table_remove_middle_key() {
+        assert_removed("b", &[("a", "c", 20), ("a", "d", 5), ("c", "d", 14)]);
+    }
+
+    #[test]
+    fn test_ppm_table_remove_last_key() {
+        assert_removed("d", &[("a", "b", 10), ("a", "c", 20), ("b", "c", 14)]);
+    }
+
+    #[test]
+    fn test_ppm_table_remove_missing_key() {
+        let mut table = four_key_table();
+        assert!(!table.remove_key("e"));
+        assert_eq!(table, four_key_table());
+    }
+
+    #[test]
+    fn test_ppm_table_rename_key_keeps_order() {
+        let mut table = four_key_table();
+        table
+            .rename_key("b", "bb".to_string())
+            .expect("Rename should succeed.");
+        assert!(!table.contains_key("b"));
+        assert_eq!(table.index_of("bb"), Some(1));
+        assert_eq!(table[("a", "bb")], 10);
+        assert_eq!(table[("bb", "d")], 30);
+    }
+
+    #[test]
+    fn test_ppm_table_rename_key_reorders() {
+        let mut table = four_key_table();
+        table
+            .rename_key("a", "e".to_string())
+            .expect("Rename should succeed.");
+
+        assert_eq!(table.index_of("b"), Some(0));
+        assert_eq!(table.index_of("e"), Some(3));
+        let expected = HashSet::from([
+            ("b", "e", 10),
+            ("c", "e", 20),
+            ("d", "e", 5),
+            ("b", "c", 14),
+            ("b", "d", 30),
+            ("c", "d", 14),
+        ]);
+        assert_eq!(table.edges().collect::<HashSet<_>>(), expected);
+        for (l, r, ppm) in expected {
+            assert_eq!(table.ppm(r, l), Some(ppm));
+        }
+    }
+
+    #[test]
+    fn test_ppm_table_with_key_prefix() {
+        let table = four_key_table().with_key_prefix("a2/");
+        assert_eq!(table.check_invariants(), Ok(()));
+        assert_eq!(table.index_of("a2/c"), Some(2));
+        assert_eq!(table[("a2/a", "a2/d")], 5);
+        assert!(!table.contains_key("a"));
+
+        // "a10/" sorts before "a2/", so the second table's keys come first in the combined one.
+        let mut other = PpmTableBuilder::default();
+        other.add_ppm("a".to_string(), "b".to_string(), 7);
+        let other = other
+            .build()
+            .expect("Table should be buildable.")
+            .with_key_prefix("a10/");
+        let mut builder = table.into_builder();
+        builder
+            .merge(other.into_builder(), MergePolicy::Error)
+            .expect("Builders should not conflict.");
+        let combined = builder.build_with_default(ppm::MAX_PPM);
+
+        assert_eq!(combined.check_invariants(), Ok(()));
+        assert_eq!(
+            (0..combined.len())
+                .map(|i| combined.key_at(i).unwrap())
+                .collect::<Vec<_>>(),
+            ["a10/a", "a10/b", "a2/a", "a2/b", "a2/c", "a2/d"]
+        );
+        assert_eq!(combined[("a10/a", "a10/b")], 7);
+        assert_eq!(combined[("a2/b", "a2/c")], 14);
+        assert_eq!(combined[("a10/b", "a2/a")], ppm::MAX_PPM);
+    }
+
+    #[test]
+    fn test_ppm_table_rename_key_errors() {
+        let mut table = four_key_table();
+        assert_eq!(
+            table.rename_key("a", "b".to_string()),
+            Err(RenameError::DuplicateKey("b".to_string()))
+        );
+        assert_eq!(
+            table.rename_key("e", "f".to_string()),
+            Err(RenameError::UnknownKey("e".to_string()))
+        );
+        assert_eq!(table, four_key_table());
+    }
+
+    #[test]
+    fn test_ppm_table_builder_merge_disjoint_chunks() {
+        let mut left = PpmTableBuilder::default();
+        left.add_ppm("a".to_string(), "b".to_string(), 10);
+        left.add_ppm("a".to_string(), "c".to_string(), 20);
+        left.add_ppm("a".to_string(), "d".to_string(), 5);
+        let mut right = PpmTableBuilder::default();
+        right.add_ppm("b".to_string(), "c".to_string(), 14);
+        right.add_ppm("b".to_string(), "d".to_string(), 30);
+        right.add_ppm("c".to_string(), "d".to_string(), 14);
+
+        left.merge(right, MergePolicy::Error)
+            .expect("Chunks should not conflict.");
+        let table = left.build().expect("Table should be buildable.");
+        assert_eq!(table, four_key_table());
+    }
+
+    #[test]
+    fn test_ppm_table_builder_merge_policies() {
+        let chunk = |ppm| {
+            let mut builder = PpmTableBuilder::default();
+            builder.add_ppm("a".to_string(), "b".to_string(), ppm);
+            builder
+        };
+
+        let mut builder = chunk(10);
+        builder.merge(chunk(20), MergePolicy::KeepMin).unwrap();
+        assert_eq!(builder.build().unwrap()[("a", "b")], 10);
+
+        let mut builder = chunk(10);
+        builder.merge(chunk(20), MergePolicy::KeepLast).unwrap();
+        assert_eq!(builder.build().unwrap()[("a", "b")], 20);
+
+        let mut builder = chunk(10);
+        builder.merge(chunk(10), MergePolicy::Error).unwrap();
+        assert_eq!(builder.build().unwrap()[("a", "b")], 10);
+
+        let mut builder = chunk(10);
+        assert_eq!(
+            builder.merge(chunk(20), MergePolicy::Error),
+            Err(MergeError::ConflictingPpm {
+                l: "a".to_string(),
+                r: "b".to_string(),
+                first: 10,
+                second: 20,
+            })
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_merge_different_keys() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        builder.add_ppm("b".to_string(), "c".to_string(), 14);
+        let left = builder.build().expect("Table should be buildable.");
+
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("c".to_string(), "d".to_string(), 14);
+        let right = builder.build().expect("Table should be buildable.");
+
+        let err = left
+            .merge(right, MergePolicy::Error)
+            .expect_err("Merged graph should be incomplete.");
+        assert_eq!(
+            err,
+            MergeError::IncompleteGraph {
+                missing: vec![
+                    ("a".to_string(), "d".to_string()),
+                    ("b".to_string(), "d".to_string()),
+                ]
+            }
+        );
+
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        let left = builder.build().expect("Table should be buildable.");
+
+        let mut builder = PpmTableBuilder::default();
+        builder.add_key("c");
+        let right = builder.build().expect("Table should be buildable.");
+
+        let err = left
+            .merge(right, MergePolicy::Error)
+            .expect_err("Merged graph should be incomplete.");
+        assert_eq!(
+            err,
+            MergeError::IncompleteGraph {
+                missing: vec![
+                    ("a".to_string(), "c".to_string()),
+                    ("b".to_string(), "c".to_string()),
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_subtable() {
+        let table = four_key_table();
+        let subtable = table
+            .subtable(["d", "b", "a", "d"])
+            .expect("All keys should be present.");
+
+        assert_eq!(subtable.len(), 3);
+        assert_eq!(subtable.index_of("d"), Some(2));
+        assert_eq!(
+            subtable.edges().collect::<HashSet<_>>(),
+            HashSet::from([("a", "b", 10), ("a", "d", 5), ("b", "d", 30)])
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_subtable_unknown_key() {
+        let table = four_key_table();
+        assert_eq!(
+            table.subtable(["a", "e", "b"]),
+            Err(UnknownKeyError("e".to_string()))
+        );
+
+        let subtable = table.subtable_lenient(["a", "e", "b"]);
+        assert_eq!(subtable.edges().collect::<Vec<_>>(), vec![("a", "b", 10)]);
+    }
+
+    #[test]
+    fn test_ppm_table_edges_below_large() {
+        let n = 500;
+        let keys = (0..n).map(|i| format!("{:03}", i)).collect::<Vec<_>>();
+        let mut builder = PpmTableBuilder::default();
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let ppm = ((i * 7919 + j * 104729) % 1_000_000) as u32;
+                builder.add_ppm(keys[i].clone(), keys[j].clone(), ppm);
+            }
+        }
+        let table = builder.build().expect("Table should be buildable.");
+
+        let max_ppm = 20_000;
+        let naive = table
+            .edges()
+            .filter(|e| e.2 <= max_ppm)
+            .collect::<HashSet<_>>();
+        assert!(!naive.is_empty());
+        assert_eq!(table.edges_below(max_ppm).collect::<HashSet<_>>(), naive);
+
+        let sparse = table
+            .sparse_edges_below(max_ppm)
+            .into_iter()
+            .map(|(i, j, ppm)| (table.key_at(i).unwrap(), table.key_at(j).unwrap(), ppm))
+            .collect::<HashSet<_>>();
+        assert_eq!(sparse, naive);
+    }
+
+    #[test]
+    fn test_ppm_table_edges_sorted() {
+        let table = four_key_table();
+        assert_eq!(
+            table.edges_sorted().collect::<Vec<_>>(),
+            vec![
+                ("a", "d", 5),
+                ("a", "b", 10),
+                ("b", "c", 14),
+                ("c", "d", 14),
+                ("a", "c", 20),
+                ("b", "d", 30),
+            ]
+        );
+        assert_eq!(
+            table.edges_sorted_below(14).collect::<Vec<_>>(),
+            vec![
+                ("a", "d", 5),
+                ("a", "b", 10),
+                ("b", "c", 14),
+                ("c", "d", 14)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_min_max_edge() {
+        let table = four_key_table();
+        assert_eq!(table.min_edge(), Some(("a", "d", 5)));
+        assert_eq!(table.max_edge(), Some(("b", "d", 30)));
+
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 7);
+        builder.add_ppm("a".to_string(), "c".to_string(), 7);
+        builder.add_ppm("b".to_string(), "c".to_string(), 7);
+        let table = builder.build().expect("Table should be buildable.");
+        assert_eq!(table.min_edge(), Some(("a", "b", 7)));
+        assert_eq!(table.max_edge(), Some(("a", "b", 7)));
+    }
+
+    #[test]
+    fn test_ppm_table_stats() {
+        let stats = four_key_table().stats().expect("Table has edges.");
+        assert_eq!(stats.count, 6);
+        assert_eq!(stats.mean, 93.0 / 6.0);
+        assert_eq!(stats.median, 14.0);
+        let variance = [10.0, 20.0, 5.0, 14.0, 30.0, 14.0]
+            .iter()
+            .map(|ppm: &f64| (ppm - 93.0 / 6.0).powi(2))
+            .sum::<f64>()
+            / 6.0;
+        assert!((stats.std_dev - variance.sqrt()).abs() < 1e-9);
+
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        builder.add_ppm("b".to_string(), "c".to_string(), 15);
+        let stats = builder.build().unwrap().stats().expect("Table has edges.");
+        assert_eq!(stats.median, 15.0);
+    }
+
+    #[test]
+    fn test_ppm_table_stats_no_edges() {
+        let empty = PpmTableBuilder::default().build().unwrap();
+        assert_eq!(empty.min_edge(), None);
+        assert_eq!(empty.max_edge(), None);
+        assert_eq!(empty.stats(), None);
+
+        let single = PpmTable::<u32, RandomState> {
+            ppm_table: Vec::<u32>::new(),
+            indices: BiHashMap::from_iter([("a".to_string(), 0)]),
+        };
+        assert_eq!(single.min_edge(), None);
+        assert_eq!(single.max_edge(), None);
+        assert_eq!(single.stats(), None);
+    }
+
+    #[test]
+    fn test_ppm_table_quantile() {
+        // 15 keys have 105 edges, given the PPMs 0, 10, ..., 1040 in shuffled order.
+        let mut builder = PpmTableBuilder::default();
+        let mut flat_idx = 0_u32;
+        for i in 0..15 {
+            for j in i + 1..15 {
+                let ppm = (flat_idx * 37 % 105) * 10;
+                builder.add_ppm(format!("{:02}", i), format!("{:02}", j), ppm);
+                flat_idx += 1;
+            }
+        }
+        let table = builder.build().expect("Table should be buildable.");
+
+        assert_eq!(table.quantile(0.0), Ok(Some(0)));
+        assert_eq!(table.quantile(0.01), Ok(Some(10)));
+        assert_eq!(table.quantile(0.25), Ok(Some(260)));
+        assert_eq!(table.quantile(0.5), Ok(Some(520)));
+        assert_eq!(table.quantile(1.0), Ok(Some(1040)));
+        assert_eq!(
+            table.quantiles(&[0.5, 0.0, 0.01, 1.0, 0.25]),
+            Ok(Some(vec![520, 0, 10, 1040, 260]))
+        );
+        assert_eq!(table.quantiles(&[]), Ok(Some(vec![])));
+
+        assert_eq!(table.quantile(1.5), Err(QuantileOutOfRangeError(1.5)));
+        assert_eq!(
+            table.quantiles(&[0.5, -0.1]),
+            Err(QuantileOutOfRangeError(-0.1))
+        );
+        assert!(table.quantile(f64::NAN).is_err());
+
+        let empty = PpmTableBuilder::default().build().unwrap();
+        assert_eq!(empty.quantile(0.5), Ok(None));
+        assert_eq!(empty.quantiles(&[0.5]), Ok(None));
+    }
+
+    #[test]
+    fn test_ppm_table_histogram() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 0);
+        builder.add_ppm("a".to_string(), "c".to_string(), 9);
+        builder.add_ppm("a".to_string(), "d".to_string(), 10);
+        builder.add_ppm("a".to_string(), "e".to_string(), 15);
+        builder.add_ppm("b".to_string(), "c".to_string(), 19);
+        builder.add_ppm("b".to_string(), "d".to_string(), 20);
+        builder.add_ppm("b".to_string(), "e".to_string(), 41);
+        builder.add_ppm("c".to_string(), "d".to_string(), 42);
+        builder.add_ppm("c".to_string(), "e".to_string(), 3);
+        builder.add_ppm("d".to_string(), "e".to_string(), 5);
+        let table = builder.build().expect("Table should be buildable.");
+
+        assert_eq!(
+            table.histogram(10),
+            Ok(vec![
+                (0..=9, 4),
+                (10..=19, 3),
+                (20..=29, 1),
+                (30..=39, 0),
+                (40..=42, 2)
+            ])
+        );
+        assert_eq!(table.histogram(100), Ok(vec![(0..=42, 10)]));
+        assert_eq!(table.histogram(0), Err(ZeroBucketWidthError));
+    }
+
+    #[test]
+    fn test_ppm_table_histogram_empty() {
+        let table = PpmTableBuilder::default().build().unwrap();
+        assert_eq!(table.histogram(10), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_ppm_table_top_k() {
+        let table = four_key_table();
+        assert_eq!(table.top_k(0), vec![]);
+        assert_eq!(
+            table.top_k(3),
+            vec![("a", "d", 5), ("a", "b", 10), ("b", "c", 14)]
+        );
+        assert_eq!(table.top_k(10), table.edges_sorted().collect::<Vec<_>>());
+        assert_eq!(
+            table.top_k(usize::MAX),
+            table.edges_sorted().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_nearest_neighbors() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 30);
+        builder.add_ppm("b".to_string(), "c".to_string(), 5);
+        builder.add_ppm("a".to_string(), "d".to_string(), 40);
+        builder.add_ppm("b".to_string(), "d".to_string(), 40);
+        builder.add_ppm("c".to_string(), "d".to_string(), 40);
+        let table = builder.build().expect("Table should be buildable.");
+
+        assert_eq!(
+            table.nearest_neighbors().collect::<Vec<_>>(),
+            vec![("a", "b", 10), ("b", "c", 5), ("c", "b", 5), ("d", "a", 40),]
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_eq() {
+        let mut builder = PpmTableBuilder::<u32, BuildHasherDefault<DefaultHasher>>::new();
+        builder.add_ppm("c".to_string(), "d".to_string(), 14);
+        builder.add_ppm("d".to_string(), "b".to_string(), 30);
+        builder.add_ppm("c".to_string(), "b".to_string(), 14);
+        builder.add_ppm("d".to_string(), "a".to_string(), 5);
+        builder.add_ppm("c".to_string(), "a".to_string(), 20);
+        builder.add_ppm("b".to_string(), "a".to_string(), 10);
+        let reordered = builder.build().expect("Table should be buildable.");
+
+        assert_eq!(four_key_table(), four_key_table());
+        assert!(four_key_table() == reordered);
+    }
+
+    #[test]
+    fn test_ppm_table_ne_different_ppm() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("a".to_string(), "c".to_string(), 20);
+        builder.add_ppm("a".to_string(), "d".to_string(), 5);
+        builder.add_ppm("b".to_string(), "c".to_string(), 14);
+        builder.add_ppm("b".to_string(), "d".to_string(), 31);
+        builder.add_ppm("c".to_string(), "d".to_string(), 14);
+        let table = builder.build().expect("Table should be buildable.");
+
+        assert_ne!(four_key_table(), table);
+    }
+
+    #[test]
+    fn test_ppm_table_ne_different_keys() {
+        let mut renamed = four_key_table();
+        renamed.rename_key("d", "e".to_string()).unwrap();
+        assert_ne!(four_key_table(), renamed);
+
+        let mut removed = four_key_table();
+        removed.remove_key("d");
+        assert_ne!(four_key_table(), removed);
+    }
+
+    #[test]
+    fn test_ppm_table_hash_independent_of_insertion_order() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("d".to_string(), "c".to_string(), 14);
+        builder.add_ppm("b".to_string(), "d".to_string(), 30);
+        builder.add_ppm("c".to_string(), "b".to_string(), 14);
+        builder.add_ppm("a".to_string(), "d".to_string(), 5);
+        builder.add_ppm("c".to_string(), "a".to_string(), 20);
+        builder.add_ppm("b".to_string(), "a".to_string(), 10);
+        let reordered = builder.build().expect("Table should be buildable.");
+
+        let hash = |table: &PpmTable| {
+            let mut hasher = DefaultHasher::new();
+            table.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&four_key_table()), hash(&reordered));
+        assert_eq!(
+            four_key_table().canonical_bytes(),
+            reordered.canonical_bytes()
+        );
+
+        let mut removed = four_key_table();
+        removed.remove_key("d");
+        assert_ne!(hash(&four_key_table()), hash(&removed));
+        assert_ne!(
+            four_key_table().canonical_bytes(),
+            removed.canonical_bytes()
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_into_builder_round_trip() {
+        let builder = four_key_table().into_builder();
+        let table = builder.clone().build().expect("Table should be buildable.");
+        assert_eq!(table, four_key_table());
+        assert_eq!(table.into_builder(), builder);
+    }
+
+    #[test]
+    fn test_ppm_table_into_builder_add_key() {
+        let mut builder = four_key_table().into_builder();
+        builder.add_ppm("a".to_string(), "e".to_string(), 1);
+        builder.add_ppm("b".to_string(), "e".to_string(), 2);
+        builder.add_ppm("c".to_string(), "e".to_string(), 3);
+        let incomplete = builder.clone();
+        assert_eq!(builder.build().unwrap_err(), incomplete);
+
+        let mut builder = incomplete;
+        builder.add_ppm("d".to_string(), "e".to_string(), 4);
+        let table = builder.build().expect("Table should be buildable.");
+        assert_eq!(table.len(), 5);
+        assert_eq!(table[("e", "d")], 4);
+        assert_eq!(table[("a", "b")], 10);
+    }
+
+    #[test]
+    fn test_ppm_table_into_builder_single_key() {
+        let single = PpmTable::<u32, RandomState> {
+            ppm_table: Vec::new(),
+            indices: BiHashMap::from_iter([("a".to_string(), 0)]),
+        };
+        let table = single.clone().into_builder().build().unwrap();
+        assert_eq!(table, single);
+    }
+
+    #[test]
+    fn test_ppm_table_map_ppms() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 21_900);
+        builder.add_ppm("a".to_string(), "c".to_string(), 1_000_000);
+        builder.add_ppm("b".to_string(), "c".to_string(), 99);
+        let table = builder.build().expect("Table should be buildable.");
+
+        let per_ten_thousand = table.clone().map_ppms(|ppm| ppm / 100);
+        assert_eq!(per_ten_thousand[("a", "b")], 219);
+        assert_eq!(per_ten_thousand[("a", "c")], 10_000);
+        assert_eq!(per_ten_thousand[("b", "c")], 0);
+        assert_eq!(per_ten_thousand.index_of("c"), table.index_of("c"));
+
+        let percent = table.clone().map_ppms(|ppm| ppm as f64 / 10_000.0);
+        assert_eq!(percent[("a", "b")], 2.19);
+    }
+
+    #[test]
+    fn test_ppm_table_try_map_ppms() {
+        let table = four_key_table();
+        let doubled = table
+            .clone()
+            .try_map_ppms(|ppm| ppm.checked_mul(2).ok_or(ppm))
+            .expect("No value should overflow.");
+        assert_eq!(doubled[("b", "d")], 60);
+
+        let overflowed = table.try_map_ppms(|ppm| ppm.checked_mul(u32::MAX / 29).ok_or(ppm));
+        assert_eq!(overflowed, Err(30));
+    }
+
+    #[test]
+    fn test_ppm_table_diff() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("b".to_string(), "c".to_string(), 14);
+        builder.add_ppm("b".to_string(), "d".to_string(), 31);
+        builder.add_ppm("b".to_string(), "e".to_string(), 1);
+        builder.add_ppm("c".to_string(), "d".to_string(), 12);
+        builder.add_ppm("c".to_string(), "e".to_string(), 2);
+        builder.add_ppm("d".to_string(), "e".to_string(), 3);
+        let other = builder.build().expect("Table should be buildable.");
+
+        let diff = four_key_table().diff(&other);
+        assert_eq!(
+            diff,
+            TableDiff {
+                only_in_self: vec!["a".to_string()],
+                only_in_other: vec!["e".to_string()],
+                changed: vec![
+                    ChangedPpm {
+                        l: "b".to_string(),
+                        r: "d".to_string(),
+                        before: 30,
+                        after: 31,
+                    },
+                    ChangedPpm {
+                        l: "c".to_string(),
+                        r: "d".to_string(),
+                        before: 14,
+                        after: 12,
+                    },
+                ],
+            }
+        );
+        assert_eq!(
+            four_key_table().diff(&four_key_table()),
+            TableDiff::default()
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_ppm_by_value() {
+        let table = four_key_table();
+        assert_eq!(table.ppm("a", "b"), Some(10));
+        assert_eq!(table.ppm("d", "b"), Some(30));
+        assert_eq!(table.ppm("a", "a"), None);
+        assert_eq!(table.ppm("a", "e"), None);
+    }
+
+    #[test]
+    fn test_ppm_table_index_by_string() {
+        let table = four_key_table();
+        let (a, d) = ("a".to_string(), "d".to_string());
+        assert_eq!(table[("a", "d")], 5);
+        assert_eq!(table[(&d, &a)], 5);
+        assert_eq!(table[(a, d)], 5);
+    }
+
+    #[test]
+    fn test_ppm_table_edges_structured() {
+        let table = four_key_table();
+        let mut edges = table.edges_structured().collect::<Vec<_>>();
+        assert_eq!(
+            edges
+                .iter()
+                .map(|e| (e.left, e.right, e.ppm))
+                .collect::<Vec<_>>(),
+            table.edges().collect::<Vec<_>>()
+        );
+
+        edges.sort();
+        assert_eq!(
+            edges,
+            table.edges_sorted().map(Edge::from).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            edges[2],
+            Edge {
+                left: "b",
+                right: "c",
+                ppm: 14
+            }
+        );
+        assert!(edges[2] < edges[3]);
+    }
+
+    #[test]
+    fn test_ppm_table_into_edges() {
+        let table = four_key_table();
+        let borrowed = table
+            .edges()
+            .map(|(l, r, ppm)| (l.to_string(), r.to_string(), ppm))
+            .collect::<Vec<_>>();
+        assert_eq!(table.clone().into_edges(), borrowed);
+        assert_eq!(table.into_iter().len(), 6);
+
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 3);
+        let single_edge = builder.build().unwrap();
+        assert_eq!(
+            single_edge.into_edges(),
+            vec![("a".to_string(), "b".to_string(), 3)]
+        );
+
+        let mut builder = PpmTableBuilder::default();
+        builder.keys.insert("a".into());
+        assert!(builder.build().unwrap().into_edges().is_empty());
+    }
+
+    #[test]
+    fn test_ppm_table_check_invariants() {
+        assert_eq!(four_key_table().check_invariants(), Ok(()));
+        assert_eq!(
+            PpmTableBuilder::default()
+                .build()
+                .unwrap()
+                .check_invariants(),
+            Ok(())
+        );
+
+        let mut gap = four_key_table();
+        gap.indices.insert("d".to_string(), 4);
+        assert_eq!(
+            gap.check_invariants(),
+            Err(InvariantError::IndexOutOfRange {
+                key: "d".to_string(),
+                index: 4,
+                len: 4
+            })
+        );
+
+        let mut short_row = four_key_table();
+        short_row.ppm_table.pop();
+        assert_eq!(
+            short_row.check_invariants(),
+            Err(InvariantError::WrongPpmCount {
+                len: 4,
+                expected: 6,
+                actual: 5
+            })
+        );
+
+        let mut unsorted = four_key_table();
+        unsorted.indices.insert("b".to_string(), 2);
+        unsorted.indices.insert("c".to_string(), 1);
+        assert_eq!(
+            unsorted.check_invariants(),
+            Err(InvariantError::UnsortedKeys {
+                first: "c".to_string(),
+                second: "b".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_ppm_table_to_dot() {
+        assert_eq!(
+            four_key_table().to_dot(14),
+            concat!(
+                "graph {\n",
+                "    \"a\" -- \"b\" [label=\"0.0\"];\n",
+                "    \"a\" -- \"d\" [label=\"0.0\"];\n",
+                "    \"b\" -- \"c\" [label=\"0.0\"];\n",
+                "    \"c\" -- \"d\" [label=\"0.0\"];\n",
+                "}\n",
+            )
+        );
+
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm(r#"a "quoted"/x"#.to_string(), r"b\c".to_string(), 21_910);
+        builder.add_ppm(r#"a "quoted"/x"#.to_string(), "z".to_string(), 90_000);
+        builder.add_ppm(r"b\c".to_string(), "z".to_string(), 90_000);
+        let table = builder.build().unwrap();
+        assert_eq!(
+            table.to_dot(60_000),
+            "graph {\n    \"a \\\"quoted\\\"/x\" -- \"b\\\\c\" [label=\"2.1\"];\n}\n"
+        );
+        assert_eq!(table.to_dot(0), "graph {\n}\n");
+    }
+
+    #[test]
+    fn test_ppm_table_to_matrix() {
+        let table = four_key_table();
+        let (keys, matrix) = table.to_matrix();
+        assert_eq!(keys, vec!["a", "b", "c", "d"]);
+        assert_eq!(
+            matrix,
+            vec![
+                vec![0, 10, 20, 5],
+                vec![10, 0, 14, 30],
+                vec![20, 14, 0, 14],
+                vec![5, 30, 14, 0],
+            ]
+        );
+        for (i, l) in keys.iter().enumerate() {
+            for (j, r) in keys.iter().enumerate() {
+                assert_eq!(matrix[i][j], matrix[j][i]);
+                assert_eq!(table.ppm(l, r).unwrap_or(0), matrix[i][j]);
+            }
+        }
+
+        let empty = PpmTableBuilder::default().build().unwrap().to_matrix();
+        assert_eq!(empty, (vec![], vec![]));
+    }
+
+    #[cfg(feature = "petgraph")]
+    #[test]
+    fn test_ppm_table_to_graph() {
+        let table = four_key_table();
+
+        let graph = table.to_graph(None);
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(graph.edge_count(), 6);
+        for (l, r, &ppm) in graph.all_edges() {
+            assert_eq!(Some(ppm), table.ppm(l, r));
+        }
+
+        let graph = table.to_graph(Some(10));
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.edge_count(), 2);
+        assert_eq!(graph.edge_weight("a", "d"), Some(&5));
+        assert_eq!(graph.edge_weight("b", "a"), Some(&10));
+        assert!(!graph.contains_node("c"));
+
+        assert_eq!(table.to_graph(Some(0)).node_count(), 0);
+    }
+
+    #[test]
+    fn test_ppm_table_heap_size() {
+        let table_of = |n: usize| {
+            let mut builder = PpmTableBuilder::default();
+            for i in 0..n {
+                for j in i + 1..n {
+                    builder.add_ppm(format!("course/{:04}", i), format!("course/{:04}", j), 0);
+                }
+            }
+            builder.build().expect("Table should be buildable.")
+        };
+        let sizes = [0, 2, 10, 50].map(|n| table_of(n).heap_size());
+        assert!(sizes.windows(2).all(|w| w[0] < w[1]), "{:?}", sizes);
+
+        let mut table = table_of(10);
+        table.shrink_to_fit();
+        let shrunk = table.heap_size();
+        table.ppm_table.reserve(1000);
+        let mut key = table.key_at(0).unwrap().to_string();
+        table.indices.remove_by_left(&key);
+        key.reserve(1000);
+        table.indices.insert(key, 0);
+        assert!(table.heap_size() > shrunk + 1000);
+
+        let before = table.clone();
+        table.shrink_to_fit();
+        assert_eq!(table.heap_size(), shrunk);
+        assert_eq!(table, before);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_ppm_table_sample_edges() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut builder = PpmTableBuilder::default();
+        for i in 0..40 {
+            for j in i + 1..40 {
+                builder.add_ppm(format!("{:02}", i), format!("{:02}", j), (i * j) as u32);
+            }
+        }
+        let table = builder.build().expect("Table should be buildable.");
+
+        let sample = table.sample_edges(25, &mut StdRng::seed_from_u64(7));
+        assert_eq!(sample.len(), 25);
+        assert_eq!(
+            sample,
+            table.sample_edges(25, &mut StdRng::seed_from_u64(7))
+        );
+        assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 25);
+        for &(l, r, ppm) in &sample {
+            assert_eq!(table.ppm(l, r), Some(ppm));
+        }
+        assert_eq!(
+            table.sample_edges(10_000, &mut StdRng::seed_from_u64(7)),
+            table.edges().collect::<Vec<_>>()
+        );
+
+        let sample = table.sample_edges_below(100, 25, &mut StdRng::seed_from_u64(7));
+        assert_eq!(sample.len(), 25);
+        assert_eq!(
+            sample,
+            table.sample_edges_below(100, 25, &mut StdRng::seed_from_u64(7))
+        );
+        assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 25);
+        for &(l, r, ppm) in &sample {
+            assert!(ppm <= 100);
+            assert_eq!(table.ppm(l, r), Some(ppm));
+        }
+        assert_eq!(
+            table.sample_edges_below(100, 10_000, &mut StdRng::seed_from_u64(7)),
+            table.edges_below(100).collect::<Vec<_>>()
+        );
+        assert!(table
+            .sample_edges_below(100, 0, &mut StdRng::seed_from_u64(7))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_ppm_table_medoid() {
+        let table = four_key_table();
+        // The largest PPMs are a: 20, b: 30, c: 20 and d: 30, so a wins the tie with c.
+        assert_eq!(table.medoid(), Some(("a", 20)));
+        // The smallest PPMs are a: 5, b: 10, c: 14 and d: 5.
+        assert_eq!(table.most_isolated(), Some(("c", 14)));
+
+        let single = PpmTable::<u32, RandomState> {
+            ppm_table: Vec::new(),
+            indices: BiHashMap::from_iter([("a".to_string(), 0)]),
+        };
+        assert_eq!(single.medoid(), None);
+        assert_eq!(single.most_isolated(), None);
+    }
+
+    #[test]
+    fn test_ppm_table_minimum_spanning_tree() {
+        assert_eq!(
+            four_key_table().minimum_spanning_tree(),
+            vec![("a", "d", 5), ("a", "b", 10), ("b", "c", 14)]
+        );
+
+        // A square with a diagonal, all with the same PPM.
+        let mut builder = PpmTableBuilder::default();
+        for (l, r) in [
+            ("a", "b"),
+            ("a", "c"),
+            ("a", "d"),
+            ("b", "c"),
+            ("b", "d"),
+            ("c", "d"),
+        ] {
+            builder.add_ppm(l.to_string(), r.to_string(), 7);
+        }
+        let table = builder.build().unwrap();
+        let tree = table.minimum_spanning_tree();
+        assert_eq!(tree, vec![("a", "b", 7), ("a", "c", 7), ("a", "d", 7)]);
+
+        let mut builder = PpmTableBuilder::default();
+        builder.keys.insert("a".into());
+        assert!(builder.build().unwrap().minimum_spanning_tree().is_empty());
+    }
+
+    #[test]
+    fn test_edge_percent() {
+        let edge = Edge {
+            left: "a",
+            right: "b",
+            ppm: 21_910,
+        };
+        assert_eq!(edge.percent(), 2.191);
+    }
+
+    #[test]
+    fn test_ppm_table_neighbors() {
+        let table = four_key_table();
+
+        let neighbors = table
+            .neighbors("c")
+            .expect("Key should be present.")
+            .collect::<Vec<_>>();
+        assert_eq!(neighbors, vec![("a", 20), ("b", 14), ("d", 14)]);
+
+        let sorted = table
+            .neighbors_sorted("c")
+            .expect("Key should be present.")
+            .collect::<Vec<_>>();
+        assert_eq!(sorted, vec![("b", 14), ("d", 14), ("a", 20)]);
+
+        assert!(table.neighbors("e").is_none());
     }
 }
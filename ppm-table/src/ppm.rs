@@ -0,0 +1,104 @@
+use std::fmt::{Display, Formatter};
+
+use thiserror::Error;
+
+/// The number of PPM in one percent.
+pub const PPM_PER_PERCENT: u32 = 10_000;
+
+/// The largest PPM that corresponds to a valid percentage.
+pub const MAX_PPM: u32 = 100 * PPM_PER_PERCENT;
+
+#[derive(Clone, Copy, Debug, Error, PartialEq)]
+#[error("The percentage {0} is not between 0 and 100.")]
+pub struct OutOfRange(pub f64);
+
+pub fn ppm_to_percent(ppm: u32) -> f64 {
+    ppm as f64 / PPM_PER_PERCENT as f64
+}
+
+/// Converts a percentage in `0.0..=100.0` to the nearest whole PPM.
+pub fn percent_to_ppm(pct: f64) -> Result<u32, OutOfRange> {
+    if !(0.0..=100.0).contains(&pct) {
+        return Err(OutOfRange(pct));
+    }
+    Ok((pct * PPM_PER_PERCENT as f64).round() as u32)
+}
+
+/// A PPM value that displays as a percentage.
+///
+/// Digits past the requested precision are truncated rather than rounded, so `{}` of 59,999 PPM
+/// is `5.9`, never `6.0`. The precision defaults to one decimal place and can be set with the
+/// usual `{:.N}` syntax.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Ppm(pub u32);
+
+impl Ppm {
+    pub fn percent(self) -> f64 {
+        ppm_to_percent(self.0)
+    }
+}
+
+impl From<u32> for Ppm {
+    fn from(value: u32) -> Self {
+        Ppm(value)
+    }
+}
+
+impl Display for Ppm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        const FRACTION_DIGITS: usize = 4;
+
+        let precision = f.precision().unwrap_or(1);
+        write!(f, "{}", self.0 / PPM_PER_PERCENT)?;
+        if precision == 0 {
+            return Ok(());
+        }
+
+        let fraction = format!(
+            "{:0width$}",
+            self.0 % PPM_PER_PERCENT,
+            width = FRACTION_DIGITS
+        );
+        let shown = precision.min(FRACTION_DIGITS);
+        write!(f, ".{}", &fraction[..shown])?;
+        for _ in shown..precision {
+            write!(f, "0")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ppm_to_percent() {
+        assert_eq!(ppm_to_percent(0), 0.0);
+        assert_eq!(ppm_to_percent(59_999), 5.9999);
+        assert_eq!(ppm_to_percent(MAX_PPM), 100.0);
+        assert_eq!(ppm_to_percent(1_500_000), 150.0);
+    }
+
+    #[test]
+    fn test_percent_to_ppm() {
+        assert_eq!(percent_to_ppm(0.0), Ok(0));
+        assert_eq!(percent_to_ppm(6.0), Ok(60_000));
+        assert_eq!(percent_to_ppm(2.19105), Ok(21_911));
+        assert_eq!(percent_to_ppm(100.0), Ok(MAX_PPM));
+        assert_eq!(percent_to_ppm(100.1), Err(OutOfRange(100.1)));
+        assert_eq!(percent_to_ppm(-0.5), Err(OutOfRange(-0.5)));
+        assert!(percent_to_ppm(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_ppm_display() {
+        assert_eq!(Ppm(59_999).to_string(), "5.9");
+        assert_eq!(format!("{:.0}", Ppm(59_999)), "5");
+        assert_eq!(format!("{:.2}", Ppm(50_500)), "5.05");
+        assert_eq!(format!("{:.4}", Ppm(7)), "0.0007");
+        assert_eq!(format!("{:.6}", Ppm(12_345)), "1.234500");
+        assert_eq!(Ppm(MAX_PPM).to_string(), "100.0");
+        assert_eq!(Ppm(2_500_000).to_string(), "250.0");
+    }
+}
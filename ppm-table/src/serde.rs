@@ -1,33 +1,177 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash::BuildHasher;
 use std::marker::PhantomData;
 
+use bimap::BiHashMap;
 use serde::de::{Deserializer, Error, MapAccess, SeqAccess, Visitor};
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Serialize, Serializer};
 
-use crate::PpmTable;
+use crate::{PpmTable, PpmTableBuilder};
+
+/// An edge in the representation used by human-readable formats such as JSON.
+#[derive(Deserialize, Serialize)]
+struct EdgeRecord<K, P> {
+    l: K,
+    r: K,
+    ppm: P,
+}
 
 #[cfg(feature = "serde")]
-impl<R: BuildHasher + Default> Serialize for PpmTable<R> {
+impl<P: Serialize + Copy, R: BuildHasher + Default> Serialize for PpmTable<P, R> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        // Human-readable formats get a list of edges that can be read without knowing the
+        // triangular layout, at the cost of dropping keys that have no edges.
+        if serializer.is_human_readable() {
+            return serializer.collect_seq(self.edges().map(|(l, r, ppm)| EdgeRecord {
+                l,
+                r,
+                ppm,
+            }));
+        }
+
         let mut state = serializer.serialize_struct("PpmTable", 2)?;
-        state.serialize_field("ppm_table", &self.ppm_table)?;
-        state.serialize_field("indices", &self.indices)?;
+        state.serialize_field("ppm_table", &Rows(self))?;
+        state.serialize_field("keys", &Keys(self))?;
         state.end()
     }
 }
 
-impl<'de, R: BuildHasher + Default> Deserialize<'de> for PpmTable<R> {
+/// Serializes the keys in index order, which is sorted order, so that equal tables always
+/// serialize identically. Each key's index is implied by its position.
+struct Keys<'a, P, R: BuildHasher + Default>(&'a PpmTable<P, R>);
+
+impl<P: Copy, R: BuildHasher + Default> Serialize for Keys<'_, P, R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq((0..self.0.len()).map(|i| self.0.str_from_table_index(i)))
+    }
+}
+
+/// Assembles a table from its nested rows, checking that it is well-formed so that a corrupt
+/// file is reported here rather than causing a panic later.
+fn table_from_parts<E: Error, P: Copy, R: BuildHasher + Default>(
+    rows: Vec<Vec<P>>,
+    indices: BiHashMap<String, usize, R, R>,
+) -> Result<PpmTable<P, R>, E> {
+    let n = indices.len();
+    if rows.len() != n {
+        return Err(Error::custom(format_args!(
+            "expected {} rows of PPMs for {} keys, found {}",
+            n,
+            n,
+            rows.len()
+        )));
+    }
+    if let Some((i, row)) = rows
+        .iter()
+        .enumerate()
+        .find(|(i, row)| row.len() != n - i - 1)
+    {
+        return Err(Error::custom(format_args!(
+            "expected row {} to have {} PPMs, found {}",
+            i,
+            n - i - 1,
+            row.len()
+        )));
+    }
+
+    let table = PpmTable {
+        ppm_table: rows.into_iter().flatten().collect(),
+        indices,
+    };
+    table.check_invariants().map_err(Error::custom)?;
+    Ok(table)
+}
+
+fn indices_from_keys<E: Error, R: BuildHasher + Default>(
+    keys: Vec<String>,
+) -> Result<BiHashMap<String, usize, R, R>, E> {
+    if let Some(pair) = keys.windows(2).find(|pair| pair[0] >= pair[1]) {
+        return Err(Error::custom(format_args!(
+            "the keys {:?} and {:?} are not in strictly sorted order",
+            pair[0], pair[1]
+        )));
+    }
+    let mut indices =
+        BiHashMap::with_capacity_and_hashers(keys.len(), Default::default(), Default::default());
+    for (i, key) in keys.into_iter().enumerate() {
+        indices.insert(key, i);
+    }
+    Ok(indices)
+}
+
+/// A [`PpmTable`] in the format written before keys were serialized as a sorted list, when they
+/// were written as a map from key to index. This will be removed in a future release.
+///
+/// Binary formats such as postcard cannot tell the two formats apart, so files written by older
+/// versions must be read through this type, e.g. after reading them as a [`PpmTable`] fails.
+/// Self-describing formats such as JSON can read either format as a [`PpmTable`].
+#[derive(Clone, Debug)]
+pub struct LegacyPpmTable<P = u32, R: BuildHasher + Default = std::hash::RandomState>(
+    pub PpmTable<P, R>,
+);
+
+impl<'de, P: Copy + Deserialize<'de>, R: BuildHasher + Default> Deserialize<'de>
+    for LegacyPpmTable<P, R>
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(
+            rename = "PpmTable",
+            bound(deserialize = "P: Deserialize<'de>, R: BuildHasher + Default")
+        )]
+        struct Fields<P, R: BuildHasher + Default> {
+            ppm_table: Vec<Vec<P>>,
+            indices: BiHashMap<String, usize, R, R>,
+        }
+
+        let fields = Fields::<P, R>::deserialize(deserializer)?;
+        table_from_parts(fields.ppm_table, fields.indices).map(LegacyPpmTable)
+    }
+}
+
+/// Serializes the flat triangular storage as one sequence per row, matching the layout of tables
+/// saved before the storage was flattened.
+struct Rows<'a, P, R: BuildHasher + Default>(&'a PpmTable<P, R>);
+
+impl<P: Serialize + Copy, R: BuildHasher + Default> Serialize for Rows<'_, P, R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq((0..self.0.len()).map(|i| self.0.row(i)))
+    }
+}
+
+impl<'de, P: Copy + Deserialize<'de>, R: BuildHasher + Default> Deserialize<'de>
+    for PpmTable<P, R>
+{
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
+        if deserializer.is_human_readable() {
+            let edges = Vec::<EdgeRecord<String, P>>::deserialize(deserializer)?;
+            let builder = edges
+                .into_iter()
+                .map(|edge| (edge.l, edge.r, edge.ppm))
+                .collect::<PpmTableBuilder<P, R>>();
+            return builder.build().map_err(Error::custom);
+        }
+
         enum Field {
             PpmTable,
+            Keys,
             Indices,
         }
 
@@ -42,7 +186,7 @@ impl<'de, R: BuildHasher + Default> Deserialize<'de> for PpmTable<R> {
                     type Value = Field;
 
                     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                        formatter.write_str("`ppm_table` or `indices`")
+                        formatter.write_str("`ppm_table` or `keys`")
                     }
 
                     fn visit_str<E>(self, value: &str) -> Result<Field, E>
@@ -51,6 +195,7 @@ impl<'de, R: BuildHasher + Default> Deserialize<'de> for PpmTable<R> {
                     {
                         match value {
                             "ppm_table" => Ok(Field::PpmTable),
+                            "keys" => Ok(Field::Keys),
                             "indices" => Ok(Field::Indices),
                             _ => Err(Error::unknown_field(value, FIELDS)),
                         }
@@ -61,12 +206,14 @@ impl<'de, R: BuildHasher + Default> Deserialize<'de> for PpmTable<R> {
             }
         }
 
-        struct PpmTableVisitor<R: BuildHasher + Default> {
-            phantom: PhantomData<R>,
+        struct PpmTableVisitor<P, R: BuildHasher + Default> {
+            phantom: PhantomData<(P, R)>,
         }
 
-        impl<'de, R: BuildHasher + Default> Visitor<'de> for PpmTableVisitor<R> {
-            type Value = PpmTable<R>;
+        impl<'de, P: Copy + Deserialize<'de>, R: BuildHasher + Default> Visitor<'de>
+            for PpmTableVisitor<P, R>
+        {
+            type Value = PpmTable<P, R>;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
                 formatter.write_str("struct PpmTable")
@@ -76,21 +223,22 @@ impl<'de, R: BuildHasher + Default> Deserialize<'de> for PpmTable<R> {
             where
                 V: SeqAccess<'de>,
             {
-                let ppm_table = seq
+                let ppm_table: Vec<Vec<P>> = seq
                     .next_element()?
                     .ok_or_else(|| Error::invalid_length(0, &self))?;
-                let indices = seq
+                let keys = seq
                     .next_element()?
                     .ok_or_else(|| Error::invalid_length(1, &self))?;
-                Ok(PpmTable { ppm_table, indices })
+                table_from_parts(ppm_table, indices_from_keys(keys)?)
             }
 
             fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
             where
                 V: MapAccess<'de>,
             {
-                let mut ppm_table = None;
+                let mut ppm_table: Option<Vec<Vec<P>>> = None;
                 let mut indices = None;
+                let mut keys = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::PpmTable => {
@@ -99,8 +247,15 @@ impl<'de, R: BuildHasher + Default> Deserialize<'de> for PpmTable<R> {
                             }
                             ppm_table = Some(map.next_value()?);
                         }
+                        Field::Keys => {
+                            if keys.is_some() || indices.is_some() {
+                                return Err(Error::duplicate_field("keys"));
+                            }
+                            keys = Some(map.next_value()?);
+                        }
+                        // Tables written before keys were serialized as a sorted list.
                         Field::Indices => {
-                            if indices.is_some() {
+                            if keys.is_some() || indices.is_some() {
                                 return Err(Error::duplicate_field("indices"));
                             }
                             indices = Some(map.next_value()?);
@@ -108,18 +263,379 @@ impl<'de, R: BuildHasher + Default> Deserialize<'de> for PpmTable<R> {
                     }
                 }
                 let ppm_table = ppm_table.ok_or_else(|| Error::missing_field("ppm_table"))?;
-                let indices = indices.ok_or_else(|| Error::missing_field("indices"))?;
-                Ok(PpmTable { ppm_table, indices })
+                let indices = match (keys, indices) {
+                    (Some(keys), _) => indices_from_keys(keys)?,
+                    (None, Some(indices)) => indices,
+                    (None, None) => return Err(Error::missing_field("keys")),
+                };
+                table_from_parts(ppm_table, indices)
             }
         }
 
-        const FIELDS: &[&str] = &["ppm_table", "indices"];
+        const FIELDS: &[&str] = &["ppm_table", "keys"];
         deserializer.deserialize_struct(
             "PpmTable",
             FIELDS,
-            PpmTableVisitor::<R> {
+            PpmTableVisitor::<P, R> {
                 phantom: Default::default(),
             },
         )
     }
 }
+
+/// Only the PPMs and keys are serialized. A deserialized builder uses the default
+/// [`DuplicatePolicy`](crate::DuplicatePolicy) and has no recorded conflicts. Deserializing
+/// checks each pair as [`PpmTableBuilder::add_ppm`] would have stored it, so that a corrupt
+/// checkpoint is reported here rather than causing a panic when it is built.
+impl<P: Serialize, R: BuildHasher + Default> Serialize for PpmTableBuilder<P, R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("PpmTableBuilder", 2)?;
+        state.serialize_field("ppms", &self.ppms)?;
+        state.serialize_field("keys", &self.keys)?;
+        state.end()
+    }
+}
+
+impl<'de, P: Copy + Deserialize<'de>, R: BuildHasher + Default> Deserialize<'de>
+    for PpmTableBuilder<P, R>
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(
+            rename = "PpmTableBuilder",
+            bound(deserialize = "P: Deserialize<'de>, R: BuildHasher + Default")
+        )]
+        struct Fields<P, R: BuildHasher + Default> {
+            ppms: HashMap<String, HashMap<String, P, R>, R>,
+            keys: HashSet<String, R>,
+        }
+
+        let fields = Fields::<P, R>::deserialize(deserializer)?;
+        let mut builder = PpmTableBuilder::new();
+        for key in &fields.keys {
+            builder.intern(key);
+        }
+        for (l, r_ppms) in &fields.ppms {
+            for (r, &ppm) in r_ppms {
+                if let Some(key) = [l, r].into_iter().find(|&key| !fields.keys.contains(key)) {
+                    return Err(Error::custom(format_args!(
+                        "the pair ({:?}, {:?}) has the key {:?}, which is not one of the keys",
+                        l, r, key
+                    )));
+                }
+                if l == r {
+                    return Err(Error::custom(format_args!(
+                        "the pair ({:?}, {:?}) pairs a key with itself",
+                        l, r
+                    )));
+                }
+                let (l, r) = if l < r { (l, r) } else { (r, l) };
+                if builder
+                    .ppms
+                    .get(l.as_str())
+                    .is_some_and(|r_ppms| r_ppms.contains_key(r.as_str()))
+                {
+                    return Err(Error::custom(format_args!(
+                        "the pair ({:?}, {:?}) has more than one PPM",
+                        l, r
+                    )));
+                }
+                builder.insert_ppm(l, r, ppm);
+            }
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bimap::BiHashMap;
+    use serde::{Deserialize, Serialize};
+
+    use super::LegacyPpmTable;
+    use crate::{ChangedPpm, PpmTable, PpmTableBuilder, TableDiff};
+
+    /// CBOR is self-describing but not human-readable, so it exercises the map-based path.
+    fn to_cbor<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).expect("Value should serialize.");
+        bytes
+    }
+
+    fn from_cbor<T: for<'de> Deserialize<'de>>(
+        bytes: &[u8],
+    ) -> Result<T, ciborium::de::Error<std::io::Error>> {
+        ciborium::from_reader(bytes)
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct NestedPpmTable {
+        ppm_table: Vec<Vec<u32>>,
+        keys: Vec<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct IndexedPpmTable {
+        ppm_table: Vec<Vec<u32>>,
+        indices: BiHashMap<String, usize>,
+    }
+
+    #[test]
+    fn test_serde_nested_rows_format() {
+        let nested = NestedPpmTable {
+            ppm_table: vec![vec![10, 20], vec![14], vec![]],
+            keys: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+        };
+        let bytes = postcard::to_stdvec(&nested).expect("Table should serialize.");
+        let table = postcard::from_bytes::<PpmTable>(&bytes).expect("Table should deserialize.");
+        assert_eq!(table[("a", "b")], 10);
+        assert_eq!(table[("a", "c")], 20);
+        assert_eq!(table[("b", "c")], 14);
+
+        let bytes = postcard::to_stdvec(&table).expect("Table should serialize.");
+        let round_tripped =
+            postcard::from_bytes::<NestedPpmTable>(&bytes).expect("Table should deserialize.");
+        assert_eq!(round_tripped, nested);
+    }
+
+    #[test]
+    fn test_serde_deterministic_bytes() {
+        let mut builder = PpmTableBuilder::default();
+        for (i, l) in ["e", "d", "c", "b", "a"].into_iter().enumerate() {
+            for r in ["v", "w", "x", "y", "z"] {
+                builder.add_ppm(l.to_string(), r.to_string(), i as u32);
+            }
+        }
+        // Each table gets its own randomly-seeded hashers.
+        let first = builder.clone().build_with_default(0);
+        let second = builder.build_with_default(0);
+        assert_eq!(
+            postcard::to_stdvec(&first).unwrap(),
+            postcard::to_stdvec(&second).unwrap()
+        );
+        assert_eq!(to_cbor(&first), to_cbor(&second));
+    }
+
+    #[test]
+    fn test_serde_unsorted_keys() {
+        let nested = NestedPpmTable {
+            ppm_table: vec![vec![10], vec![]],
+            keys: vec!["b".to_string(), "a".to_string()],
+        };
+        let bytes = postcard::to_stdvec(&nested).expect("Table should serialize.");
+        assert!(postcard::from_bytes::<PpmTable>(&bytes).is_err());
+    }
+
+    fn assert_rejected(nested: NestedPpmTable, message: &str) {
+        let bytes = postcard::to_stdvec(&nested).expect("Table should serialize.");
+        assert!(
+            postcard::from_bytes::<PpmTable>(&bytes).is_err(),
+            "{}",
+            message
+        );
+        let err = from_cbor::<PpmTable>(&to_cbor(&nested)).expect_err(message);
+        assert!(err.to_string().contains(message), "{}", err);
+    }
+
+    #[test]
+    fn test_serde_rejects_corrupt_rows() {
+        let keys = || vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert_rejected(
+            NestedPpmTable {
+                ppm_table: vec![vec![10, 20], vec![14]],
+                keys: keys(),
+            },
+            "expected 3 rows of PPMs for 3 keys, found 2",
+        );
+        assert_rejected(
+            NestedPpmTable {
+                ppm_table: vec![vec![10], vec![14], vec![]],
+                keys: keys(),
+            },
+            "expected row 0 to have 2 PPMs, found 1",
+        );
+        assert_rejected(
+            NestedPpmTable {
+                ppm_table: vec![vec![10, 20], vec![14, 15], vec![]],
+                keys: keys(),
+            },
+            "expected row 1 to have 1 PPMs, found 2",
+        );
+    }
+
+    #[test]
+    fn test_serde_rejects_corrupt_legacy_indices() {
+        let indexed = IndexedPpmTable {
+            ppm_table: vec![vec![10, 20], vec![14], vec![]],
+            indices: BiHashMap::from_iter([
+                ("a".to_string(), 0),
+                ("b".to_string(), 1),
+                ("c".to_string(), 3),
+            ]),
+        };
+        let bytes = postcard::to_stdvec(&indexed).expect("Table should serialize.");
+        assert!(postcard::from_bytes::<LegacyPpmTable>(&bytes).is_err());
+        let err = from_cbor::<PpmTable>(&to_cbor(&indexed)).expect_err("Index 3 is out of range.");
+        assert!(err.to_string().contains("has index 3"), "{}", err);
+    }
+
+    #[test]
+    fn test_serde_legacy_indices_format() {
+        let indexed = IndexedPpmTable {
+            ppm_table: vec![vec![10, 20], vec![14], vec![]],
+            indices: BiHashMap::from_iter([
+                ("a".to_string(), 0),
+                ("b".to_string(), 1),
+                ("c".to_string(), 2),
+            ]),
+        };
+
+        let bytes = postcard::to_stdvec(&indexed).expect("Table should serialize.");
+        let LegacyPpmTable(table) =
+            postcard::from_bytes(&bytes).expect("Table should deserialize.");
+        assert_eq!(table[("c", "a")], 20);
+
+        let from_cbor =
+            from_cbor::<PpmTable>(&to_cbor(&indexed)).expect("Table should deserialize.");
+        assert_eq!(from_cbor, table);
+    }
+
+    #[test]
+    fn test_serde_human_readable_edge_list() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("001".to_string(), "002".to_string(), 2191);
+        builder.add_ppm("003".to_string(), "001".to_string(), 2155);
+        builder.add_ppm("002".to_string(), "003".to_string(), 2232);
+        let table = builder.build().expect("Table should be buildable.");
+
+        let json = serde_json::to_string(&table).expect("Table should serialize.");
+        assert_eq!(
+            json,
+            concat!(
+                r#"[{"l":"001","r":"002","ppm":2191},{"l":"001","r":"003","ppm":2155},"#,
+                r#"{"l":"002","r":"003","ppm":2232}]"#
+            )
+        );
+        assert_eq!(serde_json::from_str::<PpmTable>(&json).unwrap(), table);
+
+        let bytes = postcard::to_stdvec(&table).expect("Table should serialize.");
+        assert_eq!(postcard::from_bytes::<PpmTable>(&bytes).unwrap(), table);
+    }
+
+    #[test]
+    fn test_serde_human_readable_incomplete() {
+        let json = r#"[{"l":"a","r":"b","ppm":1},{"l":"b","r":"c","ppm":2}]"#;
+        let err = serde_json::from_str::<PpmTable>(json).expect_err("Graph should be incomplete.");
+        assert!(err.to_string().contains("1 pairs have no PPM"), "{}", err);
+    }
+
+    #[test]
+    fn test_serde_round_trip_f64() {
+        let mut builder = PpmTableBuilder::<f64>::new();
+        builder.add_ppm("a".to_string(), "b".to_string(), 0.25);
+        builder.add_ppm("a".to_string(), "c".to_string(), 0.5);
+        builder.add_ppm("b".to_string(), "c".to_string(), 0.125);
+        let table = builder.build().expect("Table should be buildable.");
+
+        let bytes = postcard::to_stdvec(&table).expect("Table should serialize.");
+        let deserialized =
+            postcard::from_bytes::<PpmTable<f64>>(&bytes).expect("Table should deserialize.");
+        assert_eq!(deserialized, table);
+        assert_eq!(deserialized[("c", "a")], 0.5);
+        assert_eq!(deserialized.edges_below(0.3).count(), 2);
+    }
+
+    #[test]
+    fn test_serde_round_trip_u64() {
+        let mut builder = PpmTableBuilder::<u64>::new();
+        builder.add_ppm("a".to_string(), "b".to_string(), u64::MAX);
+        builder.add_ppm("a".to_string(), "c".to_string(), 1 << 40);
+        builder.add_ppm("b".to_string(), "c".to_string(), 7);
+        let table = builder.build().expect("Table should be buildable.");
+
+        let bytes = postcard::to_stdvec(&table).expect("Table should serialize.");
+        let deserialized =
+            postcard::from_bytes::<PpmTable<u64>>(&bytes).expect("Table should deserialize.");
+        assert_eq!(deserialized, table);
+        assert_eq!(deserialized.min_edge(), Some(("b", "c", 7)));
+        assert_eq!(deserialized[("b", "a")], u64::MAX);
+    }
+
+    #[test]
+    fn test_serde_builder_checkpoint() {
+        let mut builder = PpmTableBuilder::default();
+        builder.add_ppm("a".to_string(), "b".to_string(), 10);
+        builder.add_ppm("c".to_string(), "a".to_string(), 20);
+
+        let bytes = postcard::to_stdvec(&builder).expect("Builder should serialize.");
+        let mut resumed =
+            postcard::from_bytes::<PpmTableBuilder>(&bytes).expect("Builder should deserialize.");
+        assert_eq!(resumed, builder);
+
+        resumed.add_ppm("b".to_string(), "c".to_string(), 14);
+        let table = resumed.build().expect("Table should be buildable.");
+        assert_eq!(table[("a", "c")], 20);
+        assert_eq!(table[("c", "b")], 14);
+
+        let json = serde_json::to_string(&PpmTableBuilder::<u32>::default()).unwrap();
+        assert_eq!(json, r#"{"ppms":{},"keys":[]}"#);
+    }
+
+    #[test]
+    fn test_serde_builder_rejects_corrupt_pairs() {
+        let assert_rejected = |json: &str, message: &str| {
+            let err = serde_json::from_str::<PpmTableBuilder>(json).expect_err(message);
+            assert!(err.to_string().contains(message), "{}", err);
+        };
+        assert_rejected(
+            r#"{"ppms":{"a":{"b":5},"b":{"a":5}},"keys":["a","b"]}"#,
+            r#"the pair ("a", "b") has more than one PPM"#,
+        );
+        assert_rejected(
+            r#"{"ppms":{"a":{"a":5}},"keys":["a"]}"#,
+            r#"the pair ("a", "a") pairs a key with itself"#,
+        );
+        assert_rejected(
+            r#"{"ppms":{"a":{"c":5}},"keys":["a","b"]}"#,
+            r#"has the key "c", which is not one of the keys"#,
+        );
+
+        // Pairs stored under their larger key are put in order, so the builder is complete.
+        let builder = serde_json::from_str::<PpmTableBuilder>(
+            r#"{"ppms":{"b":{"a":5},"c":{"a":1,"b":2}},"keys":["a","b","c"]}"#,
+        )
+        .expect("Builder should deserialize.");
+        let table = builder.build().expect("Table should be buildable.");
+        assert_eq!(table[("a", "b")], 5);
+        assert_eq!(table[("c", "b")], 2);
+    }
+
+    #[test]
+    fn test_serde_table_diff_json() {
+        let diff = TableDiff {
+            only_in_self: vec!["001".to_string()],
+            only_in_other: vec![],
+            changed: vec![ChangedPpm {
+                l: "002".to_string(),
+                r: "003".to_string(),
+                before: 2191,
+                after: 2232,
+            }],
+        };
+        let json = serde_json::to_string(&diff).expect("Diff should serialize.");
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"only_in_self":["001"],"only_in_other":[],"#,
+                r#""changed":[{"l":"002","r":"003","before":2191,"after":2232}]}"#
+            )
+        );
+        assert_eq!(serde_json::from_str::<TableDiff>(&json).unwrap(), diff);
+    }
+}